@@ -0,0 +1,261 @@
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{model::connection_profile::ConnectionProfile, util::PartialFile};
+
+const PROFILES_FILE: &str = "profiles.toml";
+const SETTINGS_FILE: &str = "settings.toml";
+
+/// Directory holding slonik's configuration files
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("slonik")
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ProfilesFile {
+    #[serde(default)]
+    profiles: Vec<ConnectionProfile>,
+}
+
+/// TOML-backed collection of connection profiles
+#[derive(Debug)]
+pub struct ProfileStore {
+    path: PathBuf,
+    profiles: Vec<ConnectionProfile>,
+}
+
+impl ProfileStore {
+    /// Loads profiles from the default location, see [`config_dir`]
+    pub fn load_default() -> Result<Self> {
+        Self::load(config_dir().join(PROFILES_FILE))
+    }
+
+    /// Loads profiles from `path`. Missing file results in an empty store.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let file = match fs::read_to_string(&path) {
+            Ok(content) => Self::parse(&content)
+                .with_context(|| format!("Malformed profiles file {}", path.display()))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => ProfilesFile::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            path,
+            profiles: file.profiles,
+        })
+    }
+
+    fn parse(content: &str) -> Result<ProfilesFile> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Writes the profiles, passwords included, to a file only its owner can read
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = ProfilesFile {
+            profiles: self.profiles.clone(),
+        };
+
+        let mut out = PartialFile::create_private(&self.path)?;
+        out.write_all(toml::to_string_pretty(&file)?.as_bytes())
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        out.persist()
+    }
+
+    pub fn profiles(&self) -> &[ConnectionProfile] {
+        &self.profiles
+    }
+
+    pub fn find(&self, name: &str) -> Option<&ConnectionProfile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// Inserts the profile or replaces an existing one with the same name
+    pub fn upsert(&mut self, profile: ConnectionProfile) {
+        match self.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<ConnectionProfile> {
+        let idx = self.profiles.iter().position(|p| p.name == name)?;
+        Some(self.profiles.remove(idx))
+    }
+}
+
+/// Application wide preferences
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Number of rows fetched at once from a query result
+    pub fetch_size: u32,
+    /// Seconds without traffic after which TCP keepalive probes are sent, 0 disables them
+    pub keepalive_idle: u32,
+    /// Seconds to wait for a connection to be established, 0 waits as long as the system does
+    pub connect_timeout: u32,
+    /// Tries to replace a lost connection before giving up, 0 leaves it to the next request
+    pub reconnect_attempts: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            fetch_size: 1000,
+            keepalive_idle: 60,
+            connect_timeout: 10,
+            reconnect_attempts: 5,
+        }
+    }
+}
+
+impl Settings {
+    /// Loads settings from the default location, see [`config_dir`]
+    pub fn load_default() -> Result<Self> {
+        Self::load(config_dir().join(SETTINGS_FILE))
+    }
+
+    /// Loads settings from `path`. Missing file results in defaults.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .with_context(|| format!("Malformed settings file {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Applies the keepalive and timeout settings to the connection `config`
+    pub fn configure(&self, config: &mut tokio_postgres::Config) {
+        config.keepalives(self.keepalive_idle > 0);
+        if self.keepalive_idle > 0 {
+            config.keepalives_idle(Duration::from_secs(self.keepalive_idle.into()));
+        }
+
+        if self.connect_timeout > 0 {
+            config.connect_timeout(Duration::from_secs(self.connect_timeout.into()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{model::connection_profile::SslMode, util::TempDir};
+
+    #[test]
+    fn parse_profiles_with_defaults() {
+        let file = ProfileStore::parse(
+            r##"
+            [[profiles]]
+            name = "local"
+
+            [[profiles]]
+            name = "reporting"
+            host = "db.internal"
+            port = 6432
+            user = "analyst"
+            dbname = "reports"
+            options = "-c statement_timeout=5min"
+            color = "#e01b24"
+            sslmode = "verify-full"
+            sslrootcert = "/etc/ssl/reporting-ca.pem"
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(file.profiles.len(), 2);
+        assert_eq!(
+            file.profiles[0],
+            ConnectionProfile {
+                name: "local".into(),
+                ..Default::default()
+            }
+        );
+        assert_eq!(file.profiles[1].port, 6432);
+        assert_eq!(file.profiles[1].color.as_deref(), Some("#e01b24"));
+        assert_eq!(file.profiles[1].sslmode, SslMode::VerifyFull);
+        assert_eq!(file.profiles[0].sslmode, SslMode::Prefer);
+    }
+
+    #[test]
+    fn settings_defaults() {
+        let settings: Settings = toml::from_str("").unwrap();
+        assert_eq!(settings, Settings::default());
+
+        let settings: Settings = toml::from_str("fetch_size = 50").unwrap();
+        assert_eq!(settings.fetch_size, 50);
+        assert_eq!(settings.reconnect_attempts, 5);
+
+        let settings: Settings = toml::from_str("keepalive_idle = 0").unwrap();
+        assert_eq!(settings.keepalive_idle, 0);
+        assert_eq!(settings.fetch_size, 1000);
+    }
+
+    #[test]
+    fn upsert_replaces_by_name() {
+        let mut store = ProfileStore {
+            path: PathBuf::new(),
+            profiles: vec![],
+        };
+
+        store.upsert(ConnectionProfile {
+            name: "a".into(),
+            ..Default::default()
+        });
+        store.upsert(ConnectionProfile {
+            name: "a".into(),
+            dbname: "other".into(),
+            ..Default::default()
+        });
+
+        assert_eq!(store.profiles().len(), 1);
+        assert_eq!(store.find("a").unwrap().dbname, "other");
+        assert!(store.remove("a").is_some());
+        assert!(store.profiles().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saved_profiles_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new("config");
+        let mut store = ProfileStore::load(dir.join(PROFILES_FILE)).unwrap();
+        store.upsert(ConnectionProfile {
+            name: "a".into(),
+            password: Some("secret".into()),
+            ..Default::default()
+        });
+        store.save().unwrap();
+
+        let mode = fs::metadata(dir.join(PROFILES_FILE))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(
+            ProfileStore::load(dir.join(PROFILES_FILE))
+                .unwrap()
+                .profiles()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,1038 @@
+//! Reconstruction of `CREATE` statements from the system catalog
+
+use anyhow::{bail, Result};
+use tokio_postgres::{types::ToSql, Client, Row};
+
+use crate::model::catalog::{quote_ident, quote_literal, CatalogObject, ObjectKind};
+
+/// Resolves a possibly schema qualified name the way the server would, trying relations,
+/// functions, types and schemas in turn
+pub async fn resolve(client: &Client, name: &str) -> Result<CatalogObject> {
+    // rows are made of schema, name, relkind or a pseudo kind, function arguments and the oid
+    const LOOKUPS: &[&str] = &[
+        "SELECT n.nspname::text, c.relname::text, c.relkind::text, NULL::text, c.oid \
+         FROM pg_catalog.pg_class c \
+         JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace \
+         WHERE c.oid = pg_catalog.to_regclass($1::text)",
+        "SELECT n.nspname::text, p.proname::text, 'f', \
+                pg_catalog.pg_get_function_identity_arguments(p.oid), p.oid \
+         FROM pg_catalog.pg_proc p \
+         JOIN pg_catalog.pg_namespace n ON n.oid = p.pronamespace \
+         WHERE p.oid = pg_catalog.to_regproc($1::text)",
+        "SELECT n.nspname::text, t.typname::text, 'T', NULL::text, t.oid \
+         FROM pg_catalog.pg_type t \
+         JOIN pg_catalog.pg_namespace n ON n.oid = t.typnamespace \
+         WHERE t.oid = pg_catalog.to_regtype($1::text)",
+        "SELECT nspname::text, nspname::text, 'n', NULL::text, oid \
+         FROM pg_catalog.pg_namespace WHERE nspname = $1",
+    ];
+
+    for lookup in LOOKUPS {
+        let row = match client.query_opt(*lookup, &[&name]).await? {
+            Some(row) => row,
+            None => continue,
+        };
+
+        let kind = match row.get::<_, &str>(2) {
+            "r" | "p" => ObjectKind::Table,
+            "v" => ObjectKind::View,
+            "m" => ObjectKind::MaterializedView,
+            "S" => ObjectKind::Sequence,
+            "i" | "I" => ObjectKind::Index,
+            "f" => ObjectKind::Function,
+            "T" => ObjectKind::Type,
+            "n" => ObjectKind::Schema,
+            relkind => bail!("'{}' is a relation of unsupported kind '{}'", name, relkind),
+        };
+
+        return Ok(CatalogObject {
+            kind,
+            schema: row.get(0),
+            relation: None,
+            name: row.get(1),
+            detail: row.get(3),
+            oid: row.get(4),
+        });
+    }
+
+    bail!("'{}' is not a relation, function, type or schema", name)
+}
+
+/// Script creating `object` together with its comments, ownership and privileges
+pub async fn generate(client: &Client, object: &CatalogObject) -> Result<String> {
+    let schema = object.schema.as_deref().unwrap_or_default();
+    let name = object.name.as_str();
+
+    match object.kind {
+        ObjectKind::Table | ObjectKind::View | ObjectKind::MaterializedView => {
+            relation(client, schema, name).await
+        }
+        ObjectKind::Sequence => sequence(client, schema, name).await,
+        ObjectKind::Function => match object.oid {
+            Some(oid) => function(client, oid).await,
+            None => bail!("Function '{}' was not loaded from the catalog", name),
+        },
+        ObjectKind::Type => data_type(client, schema, name).await,
+        ObjectKind::Schema => namespace(client, name).await,
+        ObjectKind::Extension => Ok(extension(object)),
+        ObjectKind::Index => {
+            let row = client
+                .query_one(
+                    "SELECT pg_catalog.pg_get_indexdef(c.oid) \
+                     FROM pg_catalog.pg_class c \
+                     JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace \
+                     WHERE n.nspname = $1 AND c.relname = $2",
+                    &[&schema, &name],
+                )
+                .await?;
+            Ok(format!("{};\n", row.get::<_, String>(0)))
+        }
+        ObjectKind::Trigger => {
+            let relation = object.relation.as_deref().unwrap_or_default();
+            let row = client
+                .query_one(
+                    "SELECT pg_catalog.pg_get_triggerdef(t.oid, true) \
+                     FROM pg_catalog.pg_trigger t \
+                     JOIN pg_catalog.pg_class c ON c.oid = t.tgrelid \
+                     JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace \
+                     WHERE n.nspname = $1 AND c.relname = $2 AND t.tgname = $3",
+                    &[&schema, &relation, &name],
+                )
+                .await?;
+            Ok(format!("{};\n", row.get::<_, String>(0)))
+        }
+        ObjectKind::Database | ObjectKind::Column => {
+            bail!("DDL of {:?} objects is not supported", object.kind)
+        }
+    }
+}
+
+/// Definition of a table column
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnDef {
+    pub name: String,
+    pub type_name: String,
+    /// Qualified collation, set only when it differs from the default of the type
+    pub collation: Option<String>,
+    /// Default expression, or generation expression of a generated column
+    pub default: Option<String>,
+    /// `a` for `GENERATED ALWAYS`, `d` for `GENERATED BY DEFAULT` identity columns
+    pub identity: Option<char>,
+    pub generated: bool,
+    pub not_null: bool,
+}
+
+/// Table definition without the statements which follow `CREATE TABLE`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableDef {
+    pub name: String,
+    pub unlogged: bool,
+    pub columns: Vec<ColumnDef>,
+    /// Constraint names with their definitions
+    pub constraints: Vec<(String, String)>,
+    pub partition_key: Option<String>,
+    /// Parent table and partition bound of a partition, which takes its columns from the parent
+    pub partition_of: Option<(String, String)>,
+}
+
+/// Privilege granted on an object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    /// Quoted role name or `PUBLIC`
+    pub grantee: String,
+    pub privilege: String,
+    pub grantable: bool,
+}
+
+pub fn create_table(table: &TableDef) -> String {
+    let columns = table.columns.iter().map(|column| {
+        let mut def = format!("{} {}", quote_ident(&column.name), column.type_name);
+
+        if let Some(collation) = &column.collation {
+            def.push_str(&format!(" COLLATE {}", collation));
+        }
+        match (&column.default, column.generated) {
+            (Some(expr), true) => def.push_str(&format!(" GENERATED ALWAYS AS ({}) STORED", expr)),
+            (Some(expr), false) => def.push_str(&format!(" DEFAULT {}", expr)),
+            (None, _) => {}
+        }
+        match column.identity {
+            Some('a') => def.push_str(" GENERATED ALWAYS AS IDENTITY"),
+            Some('d') => def.push_str(" GENERATED BY DEFAULT AS IDENTITY"),
+            _ => {}
+        }
+        if column.not_null {
+            def.push_str(" NOT NULL");
+        }
+
+        def
+    });
+
+    let constraints = table
+        .constraints
+        .iter()
+        .map(|(name, def)| format!("CONSTRAINT {} {}", quote_ident(name), def));
+
+    let unlogged = if table.unlogged { "UNLOGGED " } else { "" };
+    let body = |lines: Vec<String>| {
+        lines
+            .iter()
+            .map(|line| format!("    {}", line))
+            .collect::<Vec<_>>()
+            .join(",\n")
+    };
+
+    let mut ddl = match &table.partition_of {
+        Some((parent, bound)) => {
+            let constraints = constraints.collect::<Vec<_>>();
+            let mut ddl = format!(
+                "CREATE {}TABLE {} PARTITION OF {}",
+                unlogged, table.name, parent
+            );
+            if !constraints.is_empty() {
+                ddl.push_str(&format!(" (\n{}\n)", body(constraints)));
+            }
+            ddl.push_str(&format!("\n{}", bound));
+            ddl
+        }
+        None => format!(
+            "CREATE {}TABLE {} (\n{}\n)",
+            unlogged,
+            table.name,
+            body(columns.chain(constraints).collect())
+        ),
+    };
+    if let Some(key) = &table.partition_key {
+        ddl.push_str(&format!(" PARTITION BY {}", key));
+    }
+    ddl.push(';');
+
+    ddl
+}
+
+/// `GRANT` statements, one per grantee and grant option
+pub fn format_grants(object: &str, grants: &[Grant]) -> Vec<String> {
+    let mut statements: Vec<(&str, bool, Vec<&str>)> = vec![];
+
+    for grant in grants {
+        let key = (grant.grantee.as_str(), grant.grantable);
+        match statements.iter_mut().find(|(g, o, _)| (*g, *o) == key) {
+            Some((_, _, privileges)) => privileges.push(&grant.privilege),
+            None => statements.push((key.0, key.1, vec![&grant.privilege])),
+        }
+    }
+
+    statements
+        .into_iter()
+        .map(|(grantee, grantable, privileges)| {
+            format!(
+                "GRANT {} ON {} TO {}{};",
+                privileges.join(", "),
+                object,
+                grantee,
+                if grantable { " WITH GRANT OPTION" } else { "" }
+            )
+        })
+        .collect()
+}
+
+/// `REVOKE` of the privileges `PUBLIC` holds by default and lost, as `(privilege, held)` pairs
+pub fn format_revokes(object: &str, public_defaults: &[(String, bool)]) -> Vec<String> {
+    let revoked = public_defaults
+        .iter()
+        .filter(|(_, held)| !held)
+        .map(|(privilege, _)| privilege.as_str())
+        .collect::<Vec<_>>();
+
+    if revoked.is_empty() {
+        vec![]
+    } else if revoked.len() == public_defaults.len() {
+        vec![format!("REVOKE ALL ON {} FROM PUBLIC;", object)]
+    } else {
+        vec![format!(
+            "REVOKE {} ON {} FROM PUBLIC;",
+            revoked.join(", "),
+            object
+        )]
+    }
+}
+
+/// Comment, owner and privileges of an object, shared by all object kinds
+struct Epilogue<'a> {
+    /// Object as written after `ON` and `ALTER`, e.g. `TABLE public.orders`
+    object: &'a str,
+    comment: Option<String>,
+    /// Further `COMMENT` statements, like those on table columns
+    comments: Vec<String>,
+    owner: String,
+    /// Default privileges of `PUBLIC` and whether they are still held
+    public_defaults: Vec<(String, bool)>,
+    grants: Vec<Grant>,
+}
+
+impl Epilogue<'_> {
+    fn append_to(self, sections: &mut Vec<String>) {
+        let object = self.object;
+        let comment = self
+            .comment
+            .map(|comment| format!("COMMENT ON {} IS {};", object, quote_literal(&comment)));
+        let comments = comment.into_iter().chain(self.comments).collect::<Vec<_>>();
+        if !comments.is_empty() {
+            sections.push(comments.join("\n"));
+        }
+
+        sections.push(format!(
+            "ALTER {} OWNER TO {};",
+            self.object,
+            quote_ident(&self.owner)
+        ));
+
+        let grants = format_revokes(self.object, &self.public_defaults)
+            .into_iter()
+            .chain(format_grants(self.object, &self.grants))
+            .collect::<Vec<_>>();
+        if !grants.is_empty() {
+            sections.push(grants.join("\n"));
+        }
+    }
+}
+
+fn script(sections: Vec<String>) -> String {
+    let mut script = sections.join("\n\n");
+    script.push('\n');
+
+    script
+}
+
+async fn query_strings(
+    client: &Client,
+    sql: &str,
+    params: &[&(dyn ToSql + Sync)],
+) -> Result<Vec<String>> {
+    let rows = client.query(sql, params).await?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Privileges of the object `oid` from the ACL column of a catalog, owner privileges are implied
+async fn grants(client: &Client, catalog: &str, prefix: &str, oid: u32) -> Result<Vec<Grant>> {
+    let sql = format!(
+        "SELECT CASE WHEN a.grantee = 0 THEN 'PUBLIC' \
+                     ELSE pg_catalog.quote_ident(pg_catalog.pg_get_userbyid(a.grantee)) END, \
+                a.privilege_type::text, a.is_grantable \
+         FROM pg_catalog.{catalog} o, pg_catalog.aclexplode(o.{prefix}acl) a \
+         WHERE o.oid = $1 AND a.grantee <> o.{prefix}owner \
+         ORDER BY 1, 2",
+        catalog = catalog,
+        prefix = prefix
+    );
+
+    let rows = client.query(sql.as_str(), &[&oid]).await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| Grant {
+            grantee: row.get(0),
+            privilege: row.get(1),
+            grantable: row.get(2),
+        })
+        .collect())
+}
+
+/// Privileges `PUBLIC` is granted on objects of `acl_kind` by default, e.g. `EXECUTE` on
+/// functions, each with whether the ACL of `oid` still holds it. An ACL which was never changed
+/// holds all of them.
+async fn public_defaults(
+    client: &Client,
+    catalog: &str,
+    prefix: &str,
+    acl_kind: char,
+    oid: u32,
+) -> Result<Vec<(String, bool)>> {
+    let sql = format!(
+        "SELECT d.privilege_type::text, \
+                o.{prefix}acl IS NULL OR EXISTS ( \
+                    SELECT 1 FROM pg_catalog.aclexplode(o.{prefix}acl) a \
+                    WHERE a.grantee = 0 AND a.privilege_type = d.privilege_type) \
+         FROM pg_catalog.{catalog} o, \
+              pg_catalog.aclexplode(pg_catalog.acldefault('{kind}', o.{prefix}owner)) d \
+         WHERE o.oid = $1 AND d.grantee = 0 \
+         ORDER BY 1",
+        catalog = catalog,
+        prefix = prefix,
+        kind = acl_kind
+    );
+
+    let rows = client.query(sql.as_str(), &[&oid]).await?;
+
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+async fn relation(client: &Client, schema: &str, name: &str) -> Result<String> {
+    let row = client
+        .query_one(
+            "SELECT c.oid, c.relkind::text, pg_catalog.pg_get_userbyid(c.relowner)::text, \
+                    pg_catalog.obj_description(c.oid, 'pg_class'), c.relpersistence::text, \
+                    CASE WHEN c.relkind = 'p' THEN pg_catalog.pg_get_partkeydef(c.oid) END, \
+                    CASE WHEN c.relkind IN ('v', 'm') THEN pg_catalog.pg_get_viewdef(c.oid, true) END, \
+                    c.relispartition, \
+                    (SELECT pg_catalog.quote_ident(pn.nspname) || '.' \
+                            || pg_catalog.quote_ident(p.relname) \
+                     FROM pg_catalog.pg_inherits i \
+                     JOIN pg_catalog.pg_class p ON p.oid = i.inhparent \
+                     JOIN pg_catalog.pg_namespace pn ON pn.oid = p.relnamespace \
+                     WHERE i.inhrelid = c.oid AND c.relispartition), \
+                    pg_catalog.pg_get_expr(c.relpartbound, c.oid) \
+             FROM pg_catalog.pg_class c \
+             JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace \
+             WHERE n.nspname = $1 AND c.relname = $2",
+            &[&schema, &name],
+        )
+        .await?;
+
+    let oid: u32 = row.get(0);
+    let relkind: &str = row.get(1);
+    let partition: bool = row.get(7);
+    let qualified = format!("{}.{}", quote_ident(schema), quote_ident(name));
+
+    let mut sections = vec![];
+    let object = match relkind {
+        "v" | "m" => {
+            let query = row.get::<_, String>(6);
+            let query = query.trim_end().trim_end_matches(';');
+
+            if relkind == "v" {
+                sections.push(format!(
+                    "CREATE OR REPLACE VIEW {} AS\n{};",
+                    qualified, query
+                ));
+                format!("VIEW {}", qualified)
+            } else {
+                sections.push(format!(
+                    "CREATE MATERIALIZED VIEW {} AS\n{}\nWITH DATA;",
+                    qualified, query
+                ));
+                format!("MATERIALIZED VIEW {}", qualified)
+            }
+        }
+        "r" | "p" => {
+            // constraints inherited from the parent come with PARTITION OF
+            let table = TableDef {
+                name: qualified.clone(),
+                unlogged: row.get::<_, &str>(4) == "u",
+                columns: table_columns(client, oid).await?,
+                constraints: client
+                    .query(
+                        "SELECT conname::text, pg_catalog.pg_get_constraintdef(oid, true) \
+                         FROM pg_catalog.pg_constraint \
+                         WHERE conrelid = $1 AND contype <> 'n' AND (conislocal OR NOT $2) \
+                         ORDER BY CASE contype WHEN 'p' THEN 0 WHEN 'f' THEN 2 ELSE 1 END, conname",
+                        &[&oid, &partition],
+                    )
+                    .await?
+                    .iter()
+                    .map(|row| (row.get(0), row.get(1)))
+                    .collect(),
+                partition_key: row.get(5),
+                partition_of: row
+                    .get::<_, Option<String>>(8)
+                    .map(|parent| (parent, row.get(9))),
+            };
+            sections.push(create_table(&table));
+            format!("TABLE {}", qualified)
+        }
+        relkind => bail!("relation of kind '{}' is not supported", relkind),
+    };
+
+    // indexes backing constraints are created by the constraints, and partitions of an index by
+    // the index of the parent table
+    let indexes = query_strings(
+        client,
+        "SELECT pg_catalog.pg_get_indexdef(i.indexrelid) || ';' \
+         FROM pg_catalog.pg_index i \
+         WHERE i.indrelid = $1 AND NOT EXISTS ( \
+             SELECT 1 FROM pg_catalog.pg_constraint c \
+             WHERE c.conrelid = i.indrelid AND c.conindid = i.indexrelid \
+               AND c.contype IN ('p', 'u', 'x')) \
+           AND NOT EXISTS ( \
+             SELECT 1 FROM pg_catalog.pg_inherits h WHERE h.inhrelid = i.indexrelid) \
+         ORDER BY 1",
+        &[&oid],
+    )
+    .await?;
+    if !indexes.is_empty() {
+        sections.push(indexes.join("\n"));
+    }
+
+    // triggers cloned from the parent table are created with the partition, tgparentid is read
+    // through row_to_json as older servers mark them internal instead
+    let triggers = query_strings(
+        client,
+        "SELECT pg_catalog.pg_get_triggerdef(t.oid, true) || ';' \
+         FROM pg_catalog.pg_trigger t \
+         WHERE t.tgrelid = $1 AND NOT t.tgisinternal \
+           AND coalesce(pg_catalog.row_to_json(t)->>'tgparentid', '0') = '0' \
+         ORDER BY t.tgname",
+        &[&oid],
+    )
+    .await?;
+    if !triggers.is_empty() {
+        sections.push(triggers.join("\n"));
+    }
+
+    let column_comments = client
+        .query(
+            "SELECT a.attname::text, d.description \
+             FROM pg_catalog.pg_attribute a \
+             JOIN pg_catalog.pg_description d \
+               ON d.objoid = a.attrelid AND d.objsubid = a.attnum \
+              AND d.classoid = 'pg_catalog.pg_class'::regclass \
+             WHERE a.attrelid = $1 AND a.attnum > 0 AND NOT a.attisdropped \
+             ORDER BY a.attnum",
+            &[&oid],
+        )
+        .await?;
+    let column_comments = column_comments
+        .iter()
+        .map(|row| {
+            format!(
+                "COMMENT ON COLUMN {}.{} IS {};",
+                qualified,
+                quote_ident(row.get(0)),
+                quote_literal(row.get(1))
+            )
+        })
+        .collect::<Vec<_>>();
+
+    Epilogue {
+        object: &object,
+        comment: row.get(3),
+        comments: column_comments,
+        owner: row.get(2),
+        public_defaults: vec![],
+        grants: grants(client, "pg_class", "rel", oid).await?,
+    }
+    .append_to(&mut sections);
+
+    Ok(script(sections))
+}
+
+async fn table_columns(client: &Client, oid: u32) -> Result<Vec<ColumnDef>> {
+    // identity and generated columns are read through row_to_json, older servers lack them
+    let rows = client
+        .query(
+            "SELECT a.attname::text, pg_catalog.format_type(a.atttypid, a.atttypmod), \
+                    a.attnotnull, pg_catalog.pg_get_expr(d.adbin, d.adrelid), \
+                    pg_catalog.row_to_json(a)->>'attidentity', \
+                    pg_catalog.row_to_json(a)->>'attgenerated', \
+                    (SELECT pg_catalog.quote_ident(cn.nspname) || '.' \
+                            || pg_catalog.quote_ident(co.collname) \
+                     FROM pg_catalog.pg_collation co \
+                     JOIN pg_catalog.pg_namespace cn ON cn.oid = co.collnamespace \
+                     WHERE co.oid = a.attcollation AND a.attcollation <> t.typcollation) \
+             FROM pg_catalog.pg_attribute a \
+             JOIN pg_catalog.pg_type t ON t.oid = a.atttypid \
+             LEFT JOIN pg_catalog.pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum \
+             WHERE a.attrelid = $1 AND a.attnum > 0 AND NOT a.attisdropped \
+             ORDER BY a.attnum",
+            &[&oid],
+        )
+        .await?;
+
+    Ok(rows.iter().map(column_def).collect())
+}
+
+fn column_def(row: &Row) -> ColumnDef {
+    let flag = |idx| {
+        row.get::<_, Option<&str>>(idx)
+            .and_then(|flag| flag.chars().next())
+    };
+
+    ColumnDef {
+        name: row.get(0),
+        type_name: row.get(1),
+        not_null: row.get(2),
+        default: row.get(3),
+        identity: flag(4),
+        generated: flag(5) == Some('s'),
+        collation: row.get(6),
+    }
+}
+
+async fn sequence(client: &Client, schema: &str, name: &str) -> Result<String> {
+    // the owning column comes with the kind of dependency: 'a' for OWNED BY, 'i' for identity
+    let row = client
+        .query_one(
+            "SELECT c.oid, pg_catalog.pg_get_userbyid(c.relowner)::text, \
+                    pg_catalog.obj_description(c.oid, 'pg_class'), \
+                    pg_catalog.format_type(s.seqtypid, NULL), \
+                    s.seqincrement::text, s.seqmin::text, s.seqmax::text, s.seqstart::text, \
+                    s.seqcache::text, s.seqcycle, owner.name, owner.deptype \
+             FROM pg_catalog.pg_class c \
+             JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace \
+             JOIN pg_catalog.pg_sequence s ON s.seqrelid = c.oid \
+             LEFT JOIN LATERAL ( \
+                 SELECT pg_catalog.quote_ident(tn.nspname) || '.' \
+                        || pg_catalog.quote_ident(t.relname) || '.' \
+                        || pg_catalog.quote_ident(a.attname) AS name, \
+                        d.deptype::text AS deptype \
+                 FROM pg_catalog.pg_depend d \
+                 JOIN pg_catalog.pg_class t ON t.oid = d.refobjid \
+                 JOIN pg_catalog.pg_namespace tn ON tn.oid = t.relnamespace \
+                 JOIN pg_catalog.pg_attribute a \
+                   ON a.attrelid = d.refobjid AND a.attnum = d.refobjsubid \
+                 WHERE d.classid = 'pg_catalog.pg_class'::regclass AND d.objid = c.oid \
+                   AND d.deptype IN ('a', 'i') AND d.refobjsubid > 0 \
+             ) owner ON true \
+             WHERE n.nspname = $1 AND c.relname = $2",
+            &[&schema, &name],
+        )
+        .await?;
+
+    let owned_by = row.get::<_, Option<String>>(10);
+    if row.get::<_, Option<&str>>(11) == Some("i") {
+        bail!(
+            "'{}' belongs to the identity column {}, it is created with its table",
+            name,
+            owned_by.unwrap_or_default()
+        );
+    }
+
+    let oid: u32 = row.get(0);
+    let qualified = format!("{}.{}", quote_ident(schema), quote_ident(name));
+    let text = |idx| row.get::<_, String>(idx);
+
+    let mut sections = vec![format!(
+        "CREATE SEQUENCE {}\n    AS {}\n    INCREMENT BY {}\n    MINVALUE {}\n    MAXVALUE {}\n    \
+         START WITH {}\n    CACHE {}\n    {}CYCLE;",
+        qualified,
+        text(3),
+        text(4),
+        text(5),
+        text(6),
+        text(7),
+        text(8),
+        if row.get(9) { "" } else { "NO " },
+    )];
+
+    if let Some(column) = owned_by {
+        sections.push(format!("ALTER SEQUENCE {} OWNED BY {};", qualified, column));
+    }
+
+    let object = format!("SEQUENCE {}", qualified);
+    Epilogue {
+        object: &object,
+        comment: row.get(2),
+        comments: vec![],
+        owner: row.get(1),
+        public_defaults: vec![],
+        grants: grants(client, "pg_class", "rel", oid).await?,
+    }
+    .append_to(&mut sections);
+
+    Ok(script(sections))
+}
+
+async fn function(client: &Client, oid: u32) -> Result<String> {
+    // prokind is read through row_to_json, older servers lack it
+    let row = client
+        .query_one(
+            "SELECT p.oid::pg_catalog.regprocedure::text, pg_catalog.pg_get_functiondef(p.oid), \
+                    pg_catalog.pg_get_userbyid(p.proowner)::text, \
+                    pg_catalog.obj_description(p.oid, 'pg_proc'), \
+                    pg_catalog.row_to_json(p)->>'prokind' = 'p' \
+             FROM pg_catalog.pg_proc p \
+             WHERE p.oid = $1",
+            &[&oid],
+        )
+        .await?;
+
+    // argument types only, qualified unless visible, as accepted by ALTER and GRANT
+    let signature: &str = row.get(0);
+    let definition: String = row.get(1);
+    let procedure = row.get::<_, Option<bool>>(4) == Some(true);
+
+    let mut sections = vec![format!("{};", definition.trim_end())];
+
+    let object = format!(
+        "{} {}",
+        if procedure { "PROCEDURE" } else { "FUNCTION" },
+        signature
+    );
+    Epilogue {
+        object: &object,
+        comment: row.get(3),
+        comments: vec![],
+        owner: row.get(2),
+        public_defaults: public_defaults(client, "pg_proc", "pro", 'f', oid).await?,
+        grants: grants(client, "pg_proc", "pro", oid).await?,
+    }
+    .append_to(&mut sections);
+
+    Ok(script(sections))
+}
+
+async fn data_type(client: &Client, schema: &str, name: &str) -> Result<String> {
+    let row = client
+        .query_one(
+            "SELECT t.oid, t.typtype::text, pg_catalog.pg_get_userbyid(t.typowner)::text, \
+                    pg_catalog.obj_description(t.oid, 'pg_type'), t.typrelid, \
+                    pg_catalog.format_type(t.typbasetype, t.typtypmod), t.typnotnull, \
+                    t.typdefault \
+             FROM pg_catalog.pg_type t \
+             JOIN pg_catalog.pg_namespace n ON n.oid = t.typnamespace \
+             WHERE n.nspname = $1 AND t.typname = $2",
+            &[&schema, &name],
+        )
+        .await?;
+
+    let oid: u32 = row.get(0);
+    let typtype: &str = row.get(1);
+    let qualified = format!("{}.{}", quote_ident(schema), quote_ident(name));
+
+    let (create, object) = match typtype {
+        "e" => {
+            let labels = query_strings(
+                client,
+                "SELECT enumlabel::text FROM pg_catalog.pg_enum \
+                 WHERE enumtypid = $1 ORDER BY enumsortorder",
+                &[&oid],
+            )
+            .await?;
+            let labels = labels
+                .iter()
+                .map(|label| format!("    {}", quote_literal(label)))
+                .collect::<Vec<_>>();
+
+            (
+                format!(
+                    "CREATE TYPE {} AS ENUM (\n{}\n);",
+                    qualified,
+                    labels.join(",\n")
+                ),
+                "TYPE",
+            )
+        }
+        "c" => {
+            let typrelid: u32 = row.get(4);
+            let attributes = query_strings(
+                client,
+                "SELECT pg_catalog.quote_ident(attname) || ' ' \
+                        || pg_catalog.format_type(atttypid, atttypmod) \
+                 FROM pg_catalog.pg_attribute \
+                 WHERE attrelid = $1 AND attnum > 0 AND NOT attisdropped ORDER BY attnum",
+                &[&typrelid],
+            )
+            .await?;
+            let attributes = attributes
+                .iter()
+                .map(|attribute| format!("    {}", attribute))
+                .collect::<Vec<_>>();
+
+            (
+                format!(
+                    "CREATE TYPE {} AS (\n{}\n);",
+                    qualified,
+                    attributes.join(",\n")
+                ),
+                "TYPE",
+            )
+        }
+        "d" => {
+            let mut create = format!("CREATE DOMAIN {} AS {}", qualified, row.get::<_, String>(5));
+            if let Some(default) = row.get::<_, Option<String>>(7) {
+                create.push_str(&format!("\n    DEFAULT {}", default));
+            }
+            if row.get(6) {
+                create.push_str("\n    NOT NULL");
+            }
+
+            let constraints = client
+                .query(
+                    "SELECT conname::text, pg_catalog.pg_get_constraintdef(oid, true) \
+                     FROM pg_catalog.pg_constraint \
+                     WHERE contypid = $1 AND contype <> 'n' ORDER BY conname",
+                    &[&oid],
+                )
+                .await?;
+            for constraint in &constraints {
+                create.push_str(&format!(
+                    "\n    CONSTRAINT {} {}",
+                    quote_ident(constraint.get(0)),
+                    constraint.get::<_, &str>(1)
+                ));
+            }
+            create.push(';');
+
+            (create, "DOMAIN")
+        }
+        "r" => {
+            let subtype = client
+                .query_one(
+                    "SELECT pg_catalog.format_type(rngsubtype, NULL) \
+                     FROM pg_catalog.pg_range WHERE rngtypid = $1",
+                    &[&oid],
+                )
+                .await?;
+
+            (
+                format!(
+                    "CREATE TYPE {} AS RANGE (\n    SUBTYPE = {}\n);",
+                    qualified,
+                    subtype.get::<_, String>(0)
+                ),
+                "TYPE",
+            )
+        }
+        typtype => bail!("DDL of types of kind '{}' is not supported", typtype),
+    };
+
+    let mut sections = vec![create];
+    let object = format!("{} {}", object, qualified);
+    Epilogue {
+        object: &object,
+        comment: row.get(3),
+        comments: vec![],
+        owner: row.get(2),
+        public_defaults: public_defaults(client, "pg_type", "typ", 'T', oid).await?,
+        grants: grants(client, "pg_type", "typ", oid).await?,
+    }
+    .append_to(&mut sections);
+
+    Ok(script(sections))
+}
+
+async fn namespace(client: &Client, name: &str) -> Result<String> {
+    let row = client
+        .query_one(
+            "SELECT oid, pg_catalog.pg_get_userbyid(nspowner)::text, \
+                    pg_catalog.obj_description(oid, 'pg_namespace') \
+             FROM pg_catalog.pg_namespace WHERE nspname = $1",
+            &[&name],
+        )
+        .await?;
+
+    let oid: u32 = row.get(0);
+    let owner: String = row.get(1);
+    let qualified = quote_ident(name);
+
+    let mut sections = vec![format!(
+        "CREATE SCHEMA {} AUTHORIZATION {};",
+        qualified,
+        quote_ident(&owner)
+    )];
+
+    let object = format!("SCHEMA {}", qualified);
+    Epilogue {
+        object: &object,
+        comment: row.get(2),
+        comments: vec![],
+        owner,
+        public_defaults: vec![],
+        grants: grants(client, "pg_namespace", "nsp", oid).await?,
+    }
+    .append_to(&mut sections);
+
+    Ok(script(sections))
+}
+
+fn extension(object: &CatalogObject) -> String {
+    let mut create = format!(
+        "CREATE EXTENSION IF NOT EXISTS {}",
+        quote_ident(&object.name)
+    );
+    if let Some(schema) = &object.schema {
+        create.push_str(&format!(" WITH SCHEMA {}", quote_ident(schema)));
+    }
+    if let Some(version) = &object.detail {
+        create.push_str(&format!(" VERSION {}", quote_literal(version)));
+    }
+    create.push_str(";\n");
+
+    create
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::connection_profile::ConnectionProfile;
+
+    #[test]
+    fn table_definition() {
+        let table = TableDef {
+            name: "public.orders".into(),
+            unlogged: false,
+            columns: vec![
+                ColumnDef {
+                    name: "id".into(),
+                    type_name: "integer".into(),
+                    identity: Some('a'),
+                    not_null: true,
+                    ..Default::default()
+                },
+                ColumnDef {
+                    name: "Note".into(),
+                    type_name: "text".into(),
+                    collation: Some("pg_catalog.\"C\"".into()),
+                    default: Some("''::text".into()),
+                    ..Default::default()
+                },
+                ColumnDef {
+                    name: "total".into(),
+                    type_name: "numeric(10,2)".into(),
+                    default: Some("(price * qty)".into()),
+                    generated: true,
+                    ..Default::default()
+                },
+            ],
+            constraints: vec![("orders_pkey".into(), "PRIMARY KEY (id)".into())],
+            partition_key: Some("RANGE (id)".into()),
+            partition_of: None,
+        };
+
+        assert_eq!(
+            create_table(&table),
+            "CREATE TABLE public.orders (\n    \
+             id integer GENERATED ALWAYS AS IDENTITY NOT NULL,\n    \
+             \"Note\" text COLLATE pg_catalog.\"C\" DEFAULT ''::text,\n    \
+             total numeric(10,2) GENERATED ALWAYS AS ((price * qty)) STORED,\n    \
+             CONSTRAINT orders_pkey PRIMARY KEY (id)\n\
+             ) PARTITION BY RANGE (id);"
+        );
+    }
+
+    #[test]
+    fn grants_grouped_by_grantee() {
+        let grant = |grantee: &str, privilege: &str, grantable| Grant {
+            grantee: grantee.into(),
+            privilege: privilege.into(),
+            grantable,
+        };
+        let grants = [
+            grant("PUBLIC", "SELECT", false),
+            grant("reporting", "SELECT", false),
+            grant("reporting", "UPDATE", true),
+            grant("reporting", "INSERT", false),
+        ];
+
+        assert_eq!(
+            format_grants("TABLE public.orders", &grants),
+            vec![
+                "GRANT SELECT ON TABLE public.orders TO PUBLIC;",
+                "GRANT SELECT, INSERT ON TABLE public.orders TO reporting;",
+                "GRANT UPDATE ON TABLE public.orders TO reporting WITH GRANT OPTION;",
+            ]
+        );
+    }
+
+    #[test]
+    fn partition_definition() {
+        let partition = TableDef {
+            name: "public.orders_2021".into(),
+            constraints: vec![("positive_total".into(), "CHECK (total > 0)".into())],
+            partition_of: Some((
+                "public.orders".into(),
+                "FOR VALUES FROM ('2021-01-01') TO ('2022-01-01')".into(),
+            )),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            create_table(&partition),
+            "CREATE TABLE public.orders_2021 PARTITION OF public.orders (\n    \
+             CONSTRAINT positive_total CHECK (total > 0)\n\
+             )\nFOR VALUES FROM ('2021-01-01') TO ('2022-01-01');"
+        );
+
+        let default = TableDef {
+            name: "public.orders_other".into(),
+            partition_of: Some(("public.orders".into(), "DEFAULT".into())),
+            partition_key: Some("LIST (region)".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            create_table(&default),
+            "CREATE TABLE public.orders_other PARTITION OF public.orders\nDEFAULT \
+             PARTITION BY LIST (region);"
+        );
+    }
+
+    #[test]
+    fn revokes_of_public_defaults() {
+        let defaults = |held: &[bool]| {
+            ["TEMPORARY", "CONNECT"]
+                .iter()
+                .zip(held)
+                .map(|(privilege, held)| (privilege.to_string(), *held))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(format_revokes("DATABASE shop", &defaults(&[true, true])).is_empty());
+        assert!(format_revokes("SCHEMA sales", &[]).is_empty());
+        assert_eq!(
+            format_revokes("FUNCTION public.f()", &[("EXECUTE".into(), false)]),
+            vec!["REVOKE ALL ON FUNCTION public.f() FROM PUBLIC;"]
+        );
+        assert_eq!(
+            format_revokes("DATABASE shop", &defaults(&[false, true])),
+            vec!["REVOKE TEMPORARY ON DATABASE shop FROM PUBLIC;"]
+        );
+    }
+
+    #[test]
+    fn extension_definition() {
+        let object = CatalogObject {
+            kind: ObjectKind::Extension,
+            schema: Some("public".into()),
+            relation: None,
+            name: "pg_trgm".into(),
+            detail: Some("1.5".into()),
+            oid: None,
+        };
+
+        assert_eq!(
+            extension(&object),
+            "CREATE EXTENSION IF NOT EXISTS pg_trgm WITH SCHEMA public VERSION '1.5';\n"
+        );
+    }
+
+    /// Client of the server described by the `PG_*` variables, tests needing a server are
+    /// skipped unless `SLONIK_TEST_DB` is set
+    async fn test_client() -> Option<Client> {
+        std::env::var_os("SLONIK_TEST_DB")?;
+
+        let (client, connection) = ConnectionProfile::from_env()
+            .pg_config()
+            .connect(tokio_postgres::NoTls)
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        Some(client)
+    }
+
+    #[tokio::test]
+    async fn function_with_named_and_variadic_arguments() {
+        let client = match test_client().await {
+            Some(client) => client,
+            None => return,
+        };
+        client
+            .batch_execute(
+                "CREATE FUNCTION pg_temp.total(base integer, VARIADIC rest integer[]) \
+                 RETURNS integer LANGUAGE sql AS 'SELECT base'",
+            )
+            .await
+            .unwrap();
+
+        let object = resolve(&client, "pg_temp.total").await.unwrap();
+        assert_eq!(
+            object.detail.as_deref(),
+            Some("base integer, VARIADIC rest integer[]")
+        );
+
+        let script = generate(&client, &object).await.unwrap();
+        assert!(script.contains("total(base integer, VARIADIC rest integer[])"));
+        assert!(script.contains("ALTER FUNCTION pg_temp"));
+    }
+}
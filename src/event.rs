@@ -1,24 +1,184 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
 use futures::{
     channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
     StreamExt,
 };
-use tokio_postgres::AsyncMessage;
 
-use crate::model::pg_response::PgResponse;
+use crate::history::HistoryEntry;
+use crate::model::{
+    catalog::{CatalogObject, CatalogQuery},
+    completion::CatalogCache,
+    connection_profile::ConnectionProfile,
+    copy::CopyStatement,
+    explain::ExplainMode,
+    import::{ImportReport, ImportRequest},
+    notification::Notification,
+    pg_message::ServerMessage,
+    pg_response::{PgResponse, QueryStats, Row},
+    query_error::QueryError,
+    query_params::{ParsedQuery, QueryParam},
+};
 
 #[derive(Debug)]
 pub enum AppEvent {
     Started,
     AppAction(AppAction),
     PgRequest(PgRequest),
-    PgMessage(Box<AsyncMessage>),
+    /// Statement with placeholders waiting for parameter values
+    PgParamsRequired {
+        id: usize,
+        query: ParsedQuery,
+    },
+    /// `COPY` statement waiting for the local file it reads or writes
+    PgCopyFileRequired {
+        id: usize,
+        text: String,
+        copy: CopyStatement,
+    },
+    /// Notice or warning the server sent a session
+    PgMessage(Arc<ServerMessage>),
+    /// Message received on a channel any session listens to
+    PgNotification(Arc<Notification>),
+    /// Request to the session of the notifications panel
+    NotifyRequest(NotifyCommand),
+    /// Channels the session of the notifications panel listens to
+    NotifyChannels(Arc<Vec<String>>),
     PgResponses {
         id: usize,
         responses: Arc<Vec<PgResponse>>,
+        stats: QueryStats,
+    },
+    /// Query sent to session `id` succeeded or failed
+    PgQueryFinished {
+        id: usize,
+        entry: Arc<HistoryEntry>,
+    },
+    /// Request to fetch further rows of the open result of session `id`
+    PgFetch {
+        id: usize,
+        count: FetchCount,
+    },
+    /// Rows fetched for the open result of session `id`, they extend its last table
+    PgRowsFetched {
+        id: usize,
+        rows: Arc<Vec<Row>>,
+        stats: QueryStats,
+    },
+    /// Session `id` connected using `profile`
+    PgConnected {
+        id: usize,
+        profile: Arc<ConnectionProfile>,
+    },
+    /// Connection of session `id` is being opened, was opened or broke
+    PgConnectionState {
+        id: usize,
+        state: ConnectionState,
+    },
+    /// Output of session `id` took `duration` to show its results
+    OutputRendered {
+        id: usize,
+        duration: Duration,
+    },
+    /// Whether the last result of session `id` has more rows to fetch
+    PgCursorState {
+        id: usize,
+        more: bool,
+    },
+    /// Request to cancel the query running in session `id`
+    PgCancel {
+        id: usize,
+    },
+    /// Query of session `id` was terminated by a cancel request
+    PgQueryCanceled {
+        id: usize,
+    },
+    /// Query of session `id` failed on the server
+    PgQueryFailed {
+        id: usize,
+        error: Arc<QueryError>,
+    },
+    /// Session `id` started or finished processing a request
+    PgSessionBusy {
+        id: usize,
+        busy: bool,
+    },
+    /// Transaction control request for session `id`
+    PgTransactionControl {
+        id: usize,
+        control: TransactionControl,
+    },
+    /// Transaction state of session `id` after its last command
+    PgTransactionStatus {
+        id: usize,
+        status: TransactionStatus,
+        auto_commit: bool,
+    },
+    /// Catalog objects known to session `id`, reloaded after DDL statements
+    PgCatalogCache {
+        id: usize,
+        cache: Arc<CatalogCache>,
+    },
+    /// Request to list catalog objects for the schema explorer
+    CatalogRequest(CatalogQuery),
+    /// Objects listed by `query`, `None` when loading failed
+    CatalogLoaded {
+        query: CatalogQuery,
+        objects: Option<Arc<Vec<CatalogObject>>>,
+    },
+    /// Request to generate the DDL of an object
+    DdlRequest(DdlTarget),
+    /// Script to be opened in a new editor buffer
+    OpenScript {
+        title: String,
+        text: String,
+    },
+    /// Query to run in the session of the current editor tab
+    RunQuery(String),
+    /// File to open in an editor tab, or to switch to if it is open already
+    OpenFile(PathBuf),
+    /// Request to write the buffer of the editor tab of session `id` to `path`
+    SaveFile {
+        id: usize,
+        path: PathBuf,
+    },
+    /// Periodic request to persist open buffers
+    Autosave,
+    /// Editor tab of session `id` became the current one
+    EditorTabSelected {
+        id: usize,
+    },
+    /// Request to close the editor tab of session `id`, confirmed if a transaction is open
+    CloseEditorTab {
+        id: usize,
+    },
+    /// Request to connect session `id` before its first statement, so that completion knows the
+    /// names of the database
+    PgConnect {
+        id: usize,
+    },
+    /// Session `id` is no longer used, its connection closes once queued commands are done
+    PgCloseSession {
+        id: usize,
     },
     OutputModeChanged(OutputModeChange),
+    /// Result whose last table is exported to a file, `partial` while it can be fetched further
+    ExportTable {
+        responses: Arc<Vec<PgResponse>>,
+        partial: bool,
+    },
+    /// File to be loaded into a table
+    ImportRequest(Arc<ImportRequest>),
+    /// Rows of the running import sent so far and bytes of its file read
+    ImportProgress {
+        rows: u64,
+        bytes: u64,
+    },
+    /// Import ended, `None` when it failed before its rows were checked
+    ImportFinished(Option<Arc<ImportReport>>),
+    /// Profile picked in the connection dialog, selected once confirmed if a transaction is open
+    ProfileRequested(Arc<ConnectionProfile>),
+    ProfileSelected(Arc<ConnectionProfile>),
     Err(anyhow::Error),
 }
 
@@ -27,17 +187,102 @@ pub enum OutputModeChange {
     TabularRaw,
     Csv,
     Tabular,
+    /// Tree of an `EXPLAIN` plan, chosen for results holding one
+    Plan,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum AppAction {
     FetchRows,
+    /// Runs only the statement under the cursor
+    RunStatement,
+    FetchMore,
+    FetchAll,
+    /// Opens the DDL of the object named under the cursor
+    ShowDefinition,
+    /// Shows the plan of the statement under the cursor
+    Explain(ExplainMode),
+    /// Writes the result of the current tab to a file
+    Export,
+    /// Loads a file into a table
+    Import,
+    CancelQuery,
+    Commit,
+    Rollback,
+    SetAutoCommit(bool),
+    SelectConnection,
+    NewTab,
+    CloseTab,
+    Open,
+    Save,
+    SaveAs,
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchCount {
+    /// Single page of the configured fetch size
+    Page,
+    /// All remaining rows, still delivered page by page
+    All,
+}
+
+/// Object to generate the DDL of
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DdlTarget {
+    Object(CatalogObject),
+    /// Possibly schema qualified name, resolved by the server
+    Name(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotifyCommand {
+    Listen(String),
+    Unlisten(String),
+    /// Sends `payload` to `channel`, mainly to test listeners
+    Notify {
+        channel: String,
+        payload: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionControl {
+    Commit,
+    Rollback,
+    /// In manual mode a transaction is opened before the first statement and stays open
+    /// until committed or rolled back
+    SetAutoCommit(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// `attempt` counts the tries to replace a lost connection, it is 0 for the first connection
+    Connecting {
+        attempt: u32,
+    },
+    Connected,
+    /// Connection broke or could not be opened, the next request tries again
+    Lost,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionStatus {
+    Idle,
+    InTransaction,
+    /// Transaction block aborted by an error, only rollback is possible
+    Failed,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PgRequest {
     pub id: usize,
     pub text: String,
+    /// Values bound to `$n` placeholders of `text`
+    pub params: Vec<QueryParam>,
+    /// Local file read by `COPY ... FROM STDIN` or written by `COPY ... TO STDOUT`, whose output
+    /// is shown as a table without one
+    pub copy_file: Option<PathBuf>,
 }
 
 pub trait EventListener {
@@ -0,0 +1,394 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::workspace::state_dir;
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// Entries kept in the history, the oldest ones are dropped first
+const HISTORY_LIMIT: usize = 10_000;
+
+/// Executed query as recorded in the history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Start of the execution in milliseconds since the Unix epoch
+    pub executed_at: i64,
+    /// Name of the connection profile
+    pub profile: String,
+    pub database: String,
+    pub query: String,
+    pub duration_ms: u64,
+    /// Rows returned or affected by the last statement
+    pub rows: Option<u64>,
+    /// Error message of a failed query
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    /// `None` for a time out of range, e.g. of an entry edited by hand
+    pub fn executed_at(&self) -> Option<DateTime<Local>> {
+        Local.timestamp_millis_opt(self.executed_at).single()
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms)
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Today,
+    Week,
+    Month,
+}
+
+impl Period {
+    /// Earliest start of a query within the period, in milliseconds since the Unix epoch
+    fn since(self, now: DateTime<Local>) -> Option<i64> {
+        let since = match self {
+            Period::Today => now
+                .with_hour(0)
+                .and_then(|t| t.with_minute(0))
+                .and_then(|t| t.with_second(0))
+                .and_then(|t| t.with_nanosecond(0))?,
+            Period::Week => now - chrono::Duration::days(7),
+            Period::Month => now - chrono::Duration::days(30),
+        };
+
+        Some(since.timestamp_millis())
+    }
+}
+
+/// Criteria of history entries shown by the history panel
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryFilter {
+    /// Words which all have to appear in the query or its error, case insensitive
+    pub text: String,
+    pub database: Option<String>,
+    /// Only queries executed within the period
+    pub period: Option<Period>,
+    /// Only succeeded or only failed queries
+    pub succeeded: Option<bool>,
+}
+
+impl HistoryFilter {
+    /// Whether `entry` matches, `words` are the searched text in lower case and `searched` the
+    /// query and error of the entry in lower case
+    fn matches(
+        &self,
+        entry: &HistoryEntry,
+        searched: &str,
+        words: &[String],
+        now: DateTime<Local>,
+    ) -> bool {
+        if matches!(&self.database, Some(database) if *database != entry.database) {
+            return false;
+        }
+
+        if matches!(self.succeeded, Some(succeeded) if succeeded != entry.succeeded()) {
+            return false;
+        }
+
+        let since = self.period.and_then(|period| period.since(now));
+        if matches!(since, Some(since) if entry.executed_at < since) {
+            return false;
+        }
+
+        words.iter().all(|word| searched.contains(word.as_str()))
+    }
+}
+
+/// Append-only file of executed queries, one JSON object per line. Once it grows past
+/// [`HISTORY_LIMIT`] the oldest entries are dropped and the file is written anew.
+#[derive(Debug)]
+pub struct QueryHistory {
+    path: PathBuf,
+    entries: Vec<HistoryEntry>,
+    /// Query and error of every entry in lower case, so that filters do not fold them again
+    searched: Vec<String>,
+}
+
+impl QueryHistory {
+    /// Loads the history from the default location, see [`state_dir`]
+    pub fn load_default() -> Result<Self> {
+        Self::load(state_dir().join(HISTORY_FILE))
+    }
+
+    /// Loads the history from `path`. Missing file results in an empty history, lines which
+    /// cannot be read (e.g. cut short by a crash) are skipped.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let entries = match fs::read_to_string(&path) {
+            Ok(content) => Self::parse(&content),
+            Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut history = Self::in_memory();
+        history.path = path;
+        for entry in entries {
+            history.push(entry);
+        }
+
+        if history.entries.len() > HISTORY_LIMIT {
+            history.truncate();
+            history.rewrite()?;
+        }
+
+        Ok(history)
+    }
+
+    /// History which is not written to disk
+    pub fn in_memory() -> Self {
+        Self {
+            path: PathBuf::new(),
+            entries: vec![],
+            searched: vec![],
+        }
+    }
+
+    fn parse(content: &str) -> Vec<HistoryEntry> {
+        content
+            .lines()
+            .filter_map(|line| match serde_json::from_str::<HistoryEntry>(line) {
+                Ok(entry) if entry.executed_at().is_some() => Some(entry),
+                Ok(entry) => {
+                    log::warn!("Skipping history entry at {}", entry.executed_at);
+                    None
+                }
+                Err(err) => {
+                    log::warn!("Skipping history entry: {}", err);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn push(&mut self, entry: HistoryEntry) {
+        let mut searched = entry.query.to_lowercase();
+        if let Some(error) = &entry.error {
+            searched.push('\n');
+            searched.push_str(&error.to_lowercase());
+        }
+
+        self.entries.push(entry);
+        self.searched.push(searched);
+    }
+
+    /// Drops the oldest entries above the limit
+    fn truncate(&mut self) {
+        let excess = self.entries.len().saturating_sub(HISTORY_LIMIT);
+        self.entries.drain(..excess);
+        self.searched.drain(..excess);
+    }
+
+    /// Records the entry, in memory even if writing it fails
+    pub fn append(&mut self, entry: HistoryEntry) -> Result<()> {
+        let line = serde_json::to_string(&entry)?;
+        self.push(entry);
+
+        if self.path.as_os_str().is_empty() {
+            return Ok(());
+        }
+
+        // dropping a tenth at once spares writing the whole file for every query
+        if self.entries.len() > HISTORY_LIMIT + HISTORY_LIMIT / 10 {
+            self.truncate();
+            return self.rewrite();
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+
+        writeln!(file, "{}", line)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+
+    /// Replaces the file with the entries kept, through a temporary file renamed over it
+    fn rewrite(&self) -> Result<()> {
+        let mut content = String::new();
+        for entry in &self.entries {
+            content.push_str(&serde_json::to_string(entry)?);
+            content.push('\n');
+        }
+
+        let temp = self.path.with_extension("jsonl.tmp");
+        fs::write(&temp, content)
+            .and_then(|()| fs::rename(&temp, &self.path))
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+
+    /// Entries in the order of execution
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /// Entries matching `filter` with their indexes, newest first
+    pub fn matching<'a>(
+        &'a self,
+        filter: &'a HistoryFilter,
+        now: DateTime<Local>,
+    ) -> impl Iterator<Item = (usize, &'a HistoryEntry)> + 'a {
+        let words = filter
+            .text
+            .to_lowercase()
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        self.entries
+            .iter()
+            .zip(&self.searched)
+            .enumerate()
+            .rev()
+            .filter(move |(_, (entry, searched))| filter.matches(entry, searched, &words, now))
+            .map(|(idx, (entry, _))| (idx, entry))
+    }
+
+    /// Databases the history has entries for, sorted
+    pub fn databases(&self) -> Vec<&str> {
+        let mut databases = self
+            .entries
+            .iter()
+            .map(|entry| entry.database.as_str())
+            .collect::<Vec<_>>();
+        databases.sort_unstable();
+        databases.dedup();
+
+        databases
+    }
+}
+
+/// Current time as stored in `HistoryEntry::executed_at`
+pub fn now_millis() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::TempDir;
+
+    fn local(time: &str) -> DateTime<Local> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Local)
+    }
+
+    fn entry(query: &str, database: &str, error: Option<&str>) -> HistoryEntry {
+        HistoryEntry {
+            executed_at: local("2021-08-20T10:00:00Z").timestamp_millis(),
+            profile: "local".into(),
+            database: database.into(),
+            query: query.into(),
+            duration_ms: 12,
+            rows: Some(1),
+            error: error.map(String::from),
+        }
+    }
+
+    fn matches(filter: &HistoryFilter, entry: &HistoryEntry, now: DateTime<Local>) -> bool {
+        let mut history = QueryHistory::in_memory();
+        history.append(entry.clone()).unwrap();
+
+        let matches = history.matching(filter, now).count() == 1;
+        matches
+    }
+
+    #[test]
+    fn filter_entries() {
+        let now = local("2021-08-20T10:00:01Z");
+        let select = entry("SELECT * FROM Orders WHERE id = 1", "shop", None);
+        let failed = entry(
+            "select * from order",
+            "shop",
+            Some("relation \"order\" does not exist"),
+        );
+
+        let filter = HistoryFilter {
+            text: "orders SELECT".into(),
+            ..Default::default()
+        };
+        assert!(matches(&filter, &select, now));
+        assert!(!matches(&filter, &failed, now));
+
+        let filter = HistoryFilter {
+            text: "does not exist".into(),
+            succeeded: Some(false),
+            ..Default::default()
+        };
+        assert!(matches(&filter, &failed, now));
+        assert!(!matches(&filter, &select, now));
+
+        let filter = HistoryFilter {
+            database: Some("reports".into()),
+            ..Default::default()
+        };
+        assert!(!matches(&filter, &select, now));
+
+        let filter = HistoryFilter {
+            period: Some(Period::Today),
+            ..Default::default()
+        };
+        assert!(matches(&filter, &select, now));
+        assert!(!matches(&filter, &select, now + chrono::Duration::days(1)));
+    }
+
+    #[test]
+    fn parse_skips_malformed_lines() {
+        let select = entry("select 1", "shop", None);
+        let out_of_range = HistoryEntry {
+            executed_at: i64::MAX,
+            ..select.clone()
+        };
+        let content = format!(
+            "{}\n{{\"executed_at\": 1\n{}\n",
+            serde_json::to_string(&select).unwrap(),
+            serde_json::to_string(&out_of_range).unwrap()
+        );
+
+        assert_eq!(QueryHistory::parse(&content), vec![select]);
+    }
+
+    #[test]
+    fn oldest_entries_dropped() {
+        let dir = TempDir::new("history");
+        let path = dir.join(HISTORY_FILE);
+
+        let content = (0..HISTORY_LIMIT + 5)
+            .map(|idx| {
+                let entry = entry(&format!("select {}", idx), "shop", None);
+                serde_json::to_string(&entry).unwrap() + "\n"
+            })
+            .collect::<String>();
+        fs::write(&path, content).unwrap();
+
+        let history = QueryHistory::load(&path).unwrap();
+        assert_eq!(history.entries().len(), HISTORY_LIMIT);
+        assert_eq!(history.entries()[0].query, "select 5");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap().lines().count(),
+            HISTORY_LIMIT
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Loading of files into tables through `COPY ... FROM STDIN`
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{pin_mut, SinkExt};
+use tokio::task::block_in_place;
+use tokio_postgres::{error::SqlState, Client};
+
+use crate::event::{AppEvent, Emitter};
+use crate::model::import::{
+    copy_error_position, ImportReport, ImportRequest, RecordReader, RowError, CHUNK_ROWS,
+    MAX_ROW_ERRORS,
+};
+
+/// Copies the file of `request` within a transaction, which is committed only if every row was
+/// accepted and it is not a dry run
+pub async fn run(
+    client: &Client,
+    request: &ImportRequest,
+    emitter: &Emitter,
+) -> Result<ImportReport> {
+    let mut records = RecordReader::open(&request.path, request.format)?;
+
+    client.batch_execute("BEGIN").await?;
+
+    let result = copy(client, request, &mut records, emitter).await;
+    let commit = matches!(&result, Ok(report) if report.committed());
+
+    // rolls back the transaction aborted by a failed COPY as well
+    let end = client
+        .batch_execute(if commit { "COMMIT" } else { "ROLLBACK" })
+        .await;
+
+    let report = result?;
+    end?;
+
+    Ok(report)
+}
+
+async fn copy(
+    client: &Client,
+    request: &ImportRequest,
+    records: &mut RecordReader,
+    emitter: &Emitter,
+) -> Result<ImportReport> {
+    if request.create_table {
+        client.batch_execute(&request.create_table_sql()).await?;
+    }
+
+    let sink = client
+        .copy_in::<_, Bytes>(request.copy_sql().as_str())
+        .await?;
+    pin_mut!(sink);
+
+    // file line of every row sent, the server reports errors by its own line numbers
+    let mut lines = vec![];
+    let mut errors = vec![];
+
+    let sent = loop {
+        let (chunk, done) =
+            block_in_place(|| read_chunk(request, records, &mut lines, &mut errors));
+
+        if !chunk.is_empty() {
+            if let Err(err) = sink.send(Bytes::from(chunk)).await {
+                break Err(err);
+            }
+        }
+
+        emitter.emit(AppEvent::ImportProgress {
+            rows: lines.len() as u64,
+            bytes: records.bytes_read(),
+        });
+
+        if done {
+            break Ok(());
+        }
+    };
+
+    let copied = match sent {
+        Ok(()) => sink.as_mut().finish().await,
+        Err(err) => Err(err),
+    };
+
+    let rows = match copied {
+        Ok(rows) => rows,
+        Err(err) => match rejected_row(&err, &lines) {
+            Some(error) => {
+                errors.push(error);
+                0
+            }
+            None => return Err(err.into()),
+        },
+    };
+
+    Ok(ImportReport {
+        rows,
+        dry_run: request.dry_run,
+        errors,
+    })
+}
+
+/// Encodes up to `CHUNK_ROWS` records, rows which cannot be read are collected in `errors`.
+///
+/// Reading is done once the file ends or too many rows are rejected.
+fn read_chunk(
+    request: &ImportRequest,
+    records: &mut RecordReader,
+    lines: &mut Vec<u64>,
+    errors: &mut Vec<RowError>,
+) -> (Vec<u8>, bool) {
+    let mut chunk = vec![];
+    let start = lines.len();
+
+    while lines.len() - start < CHUNK_ROWS {
+        if errors.len() >= MAX_ROW_ERRORS {
+            return (chunk, true);
+        }
+
+        match records.next() {
+            Some(Ok(record)) => {
+                request.encode(&record, &mut chunk);
+                lines.push(record.line);
+            }
+            Some(Err(err)) => errors.push(err),
+            None => return (chunk, true),
+        }
+    }
+
+    (chunk, false)
+}
+
+/// Row of the file the server rejected, found by the line of the data `COPY` failed at
+fn rejected_row(err: &tokio_postgres::Error, lines: &[u64]) -> Option<RowError> {
+    let db_err = err.as_db_error()?;
+    if db_err.code() == &SqlState::QUERY_CANCELED {
+        return None;
+    }
+
+    let (line, column) = copy_error_position(db_err.where_()?)?;
+    let line = *lines.get(line.checked_sub(1)? as usize)?;
+
+    let message = match column {
+        Some(column) => format!("column {}: {}", column, db_err.message()),
+        None => db_err.message().to_string(),
+    };
+
+    Some(RowError { line, message })
+}
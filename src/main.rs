@@ -1,8 +1,17 @@
+pub mod config;
+pub mod ddl;
 pub mod debug_logger;
 pub mod event;
+pub mod history;
+pub mod import;
 pub mod model;
 pub mod pg_session;
+pub mod tls;
+pub mod util;
 pub mod widgets;
+pub mod workspace;
+
+use std::path::PathBuf;
 
 use anyhow::{bail, Result};
 use event::{AppAction, AppEvent, Emitter};
@@ -13,8 +22,8 @@ use mimalloc::MiMalloc;
 use tokio::runtime::Runtime;
 
 use crate::{
-    debug_logger::DebugLogger, event::EventDispatcher, pg_session::PgEventLoopProxy,
-    widgets::Editor,
+    debug_logger::DebugLogger, event::EventDispatcher, model::explain::ExplainMode,
+    pg_session::PgEventLoopProxy, widgets::EditorTabs,
 };
 use widgets::MainWindow;
 
@@ -65,16 +74,56 @@ fn build_app(runtime: &Runtime, app: &gtk4::Application) {
     event_dispatcher.register_listener(PgEventLoopProxy::initialize(
         event_dispatcher.create_emitter(),
     ));
-    event_dispatcher.register_listener(widgets::Output::create(
+    event_dispatcher.register_listener(widgets::Messages::create(&builder));
+    event_dispatcher.register_listener(widgets::ErrorPanel::create(&builder));
+    event_dispatcher.register_listener(widgets::History::create(
+        &builder,
+        event_dispatcher.create_emitter(),
+    ));
+    event_dispatcher.register_listener(widgets::Notifications::create(
+        &builder,
+        event_dispatcher.create_emitter(),
+    ));
+
+    event_dispatcher.register_listener(widgets::ConnectionPicker::create(
+        &builder,
+        event_dispatcher.create_emitter(),
+    ));
+
+    event_dispatcher.register_listener(widgets::Exporter::create(
         &builder,
         event_dispatcher.create_emitter(),
     ));
-    event_dispatcher.register_listener(widgets::Messages::create(&builder));
 
-    let _main_window = MainWindow::create(&builder, app);
-    let editor_parent = object_or_expect(&builder, "scrolled_editor");
-    let editor = Editor::create(&editor_parent, event_dispatcher.create_emitter());
-    event_dispatcher.register_listener(editor);
+    event_dispatcher.register_listener(widgets::Importer::create(
+        &builder,
+        event_dispatcher.create_emitter(),
+    ));
+
+    event_dispatcher.register_listener(widgets::ParamsDialog::create(
+        &builder,
+        event_dispatcher.create_emitter(),
+    ));
+
+    event_dispatcher.register_listener(widgets::CopyDialog::create(
+        &builder,
+        event_dispatcher.create_emitter(),
+    ));
+
+    event_dispatcher.register_listener(widgets::Explorer::create(
+        &builder,
+        event_dispatcher.create_emitter(),
+    ));
+
+    event_dispatcher.register_listener(MainWindow::create(
+        &builder,
+        app,
+        event_dispatcher.create_emitter(),
+    ));
+    event_dispatcher.register_listener(EditorTabs::create(
+        &builder,
+        event_dispatcher.create_emitter(),
+    ));
 
     ctx.spawn_local_with_priority(PRIORITY_HIGH_IDLE, event_dispatcher.listen());
 }
@@ -86,14 +135,109 @@ pub fn object_or_expect<T: IsA<Object>>(builder: &gtk4::Builder, object_name: &s
 }
 
 pub fn register_actions(app: &gtk4::Application, emitter: Emitter) {
-    let quit = SimpleAction::new("quit", None);
-    quit.connect_activate(clone!(@weak app => move |_,_| app.quit()));
+    let quit = make_action("quit", AppAction::Quit, emitter.clone());
     app.add_action(&quit);
     app.set_accels_for_action("app.quit", &["<Ctrl>q"]);
 
-    let fetch_rows = make_action("fetch_rows", AppAction::FetchRows, emitter);
+    let new_tab = make_action("new_tab", AppAction::NewTab, emitter.clone());
+    app.add_action(&new_tab);
+    app.set_accels_for_action("app.new_tab", &["<Ctrl>t"]);
+
+    let close_tab = make_action("close_tab", AppAction::CloseTab, emitter.clone());
+    app.add_action(&close_tab);
+    app.set_accels_for_action("app.close_tab", &["<Ctrl>w"]);
+
+    let open = make_action("open", AppAction::Open, emitter.clone());
+    app.add_action(&open);
+    app.set_accels_for_action("app.open", &["<Ctrl>o"]);
+
+    let save = make_action("save", AppAction::Save, emitter.clone());
+    app.add_action(&save);
+    app.set_accels_for_action("app.save", &["<Ctrl>s"]);
+
+    let save_as = make_action("save_as", AppAction::SaveAs, emitter.clone());
+    app.add_action(&save_as);
+    app.set_accels_for_action("app.save_as", &["<Ctrl><Shift>s"]);
+
+    let open_recent = SimpleAction::new("open_recent", Some(glib::VariantTy::STRING));
+    open_recent.connect_activate(clone!(@strong emitter => move |_, path| {
+        if let Some(path) = path.and_then(|p| p.get::<String>()) {
+            emitter.emit(AppEvent::OpenFile(PathBuf::from(path)));
+        }
+    }));
+    app.add_action(&open_recent);
+
+    let fetch_rows = make_action("fetch_rows", AppAction::FetchRows, emitter.clone());
     app.add_action(&fetch_rows);
     app.set_accels_for_action("app.fetch_rows", &["F5"]);
+
+    let run_statement = make_action("run_statement", AppAction::RunStatement, emitter.clone());
+    app.add_action(&run_statement);
+    app.set_accels_for_action("app.run_statement", &["<Ctrl>Return"]);
+
+    let fetch_more = make_action("fetch_more", AppAction::FetchMore, emitter.clone());
+    app.add_action(&fetch_more);
+    app.set_accels_for_action("app.fetch_more", &["F6"]);
+
+    let fetch_all = make_action("fetch_all", AppAction::FetchAll, emitter.clone());
+    app.add_action(&fetch_all);
+    app.set_accels_for_action("app.fetch_all", &["<Shift>F6"]);
+
+    let export = make_action("export", AppAction::Export, emitter.clone());
+    app.add_action(&export);
+    app.set_accels_for_action("app.export", &["<Ctrl>e"]);
+
+    let import = make_action("import", AppAction::Import, emitter.clone());
+    app.add_action(&import);
+    app.set_accels_for_action("app.import", &["<Ctrl>i"]);
+
+    let show_definition = make_action(
+        "show_definition",
+        AppAction::ShowDefinition,
+        emitter.clone(),
+    );
+    app.add_action(&show_definition);
+    app.set_accels_for_action("app.show_definition", &["F4"]);
+
+    let explain = make_action(
+        "explain",
+        AppAction::Explain(ExplainMode::Plan),
+        emitter.clone(),
+    );
+    app.add_action(&explain);
+    app.set_accels_for_action("app.explain", &["F7"]);
+
+    let explain_analyze = make_action(
+        "explain_analyze",
+        AppAction::Explain(ExplainMode::Analyze),
+        emitter.clone(),
+    );
+    app.add_action(&explain_analyze);
+    app.set_accels_for_action("app.explain_analyze", &["<Shift>F7"]);
+
+    let cancel_query = make_action("cancel_query", AppAction::CancelQuery, emitter.clone());
+    app.add_action(&cancel_query);
+    app.set_accels_for_action("app.cancel_query", &["<Shift>F5"]);
+
+    let commit = make_action("commit", AppAction::Commit, emitter.clone());
+    app.add_action(&commit);
+    app.set_accels_for_action("app.commit", &["<Ctrl><Shift>Return"]);
+
+    let rollback = make_action("rollback", AppAction::Rollback, emitter.clone());
+    app.add_action(&rollback);
+    app.set_accels_for_action("app.rollback", &["<Ctrl><Shift>BackSpace"]);
+
+    let auto_commit = SimpleAction::new_stateful("auto_commit", None, &true.to_variant());
+    auto_commit.connect_activate(clone!(@strong emitter => move |action, _| {
+        let enabled = !action.state().and_then(|s| s.get::<bool>()).unwrap_or(true);
+        action.set_state(&enabled.to_variant());
+        emitter.emit(AppEvent::AppAction(AppAction::SetAutoCommit(enabled)));
+    }));
+    app.add_action(&auto_commit);
+
+    let select_connection = make_action("select_connection", AppAction::SelectConnection, emitter);
+    app.add_action(&select_connection);
+    app.set_accels_for_action("app.select_connection", &["<Ctrl>d"]);
 }
 
 fn make_action(name: &str, app_action: AppAction, emitter: Emitter) -> SimpleAction {
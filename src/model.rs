@@ -1,2 +1,14 @@
+pub mod catalog;
+pub mod completion;
+pub mod connection_profile;
+pub mod copy;
+pub mod explain;
+pub mod export;
+pub mod import;
+pub mod notification;
 pub mod pg_message;
 pub mod pg_response;
+pub mod query_error;
+pub mod query_params;
+pub mod sql_splitter;
+pub mod value;
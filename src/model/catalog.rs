@@ -0,0 +1,510 @@
+/// Kind of a database object shown by the schema explorer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectKind {
+    Database,
+    Schema,
+    Table,
+    View,
+    MaterializedView,
+    Function,
+    Sequence,
+    Type,
+    Extension,
+    Column,
+    Index,
+    Trigger,
+}
+
+impl ObjectKind {
+    /// Relations which can be selected from
+    pub fn is_relation(self) -> bool {
+        matches!(
+            self,
+            ObjectKind::Table | ObjectKind::View | ObjectKind::MaterializedView
+        )
+    }
+}
+
+/// Listing of catalog objects, every expandable folder of the explorer is backed by one
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CatalogQuery {
+    Databases,
+    Schemas,
+    Extensions,
+    /// Objects of `kind` within `schema`
+    SchemaObjects {
+        schema: String,
+        kind: ObjectKind,
+    },
+    /// Columns, indexes or triggers of a relation
+    RelationObjects {
+        schema: String,
+        relation: String,
+        kind: ObjectKind,
+    },
+}
+
+impl CatalogQuery {
+    /// Kind of the listed objects
+    pub fn kind(&self) -> ObjectKind {
+        match self {
+            CatalogQuery::Databases => ObjectKind::Database,
+            CatalogQuery::Schemas => ObjectKind::Schema,
+            CatalogQuery::Extensions => ObjectKind::Extension,
+            CatalogQuery::SchemaObjects { kind, .. } => *kind,
+            CatalogQuery::RelationObjects { kind, .. } => *kind,
+        }
+    }
+
+    /// Query text, its rows are made of schema, relation, name and detail text columns
+    pub fn sql(&self) -> String {
+        match self {
+            CatalogQuery::Databases => "\
+                SELECT NULL::text, NULL::text, datname::text, NULL::text, oid \
+                FROM pg_catalog.pg_database \
+                WHERE NOT datistemplate AND datallowconn \
+                ORDER BY datname"
+                .into(),
+            CatalogQuery::Schemas => "\
+                SELECT nspname::text, NULL::text, nspname::text, NULL::text, oid \
+                FROM pg_catalog.pg_namespace \
+                WHERE nspname !~ '^pg_(toast|temp_|toast_temp_)' \
+                ORDER BY nspname"
+                .into(),
+            CatalogQuery::Extensions => "\
+                SELECT n.nspname::text, NULL::text, e.extname::text, e.extversion::text, e.oid \
+                FROM pg_catalog.pg_extension e \
+                JOIN pg_catalog.pg_namespace n ON n.oid = e.extnamespace \
+                ORDER BY e.extname"
+                .into(),
+            CatalogQuery::SchemaObjects { kind, .. } => schema_objects_sql(*kind),
+            CatalogQuery::RelationObjects { kind, .. } => relation_objects_sql(*kind),
+        }
+    }
+
+    /// Values bound to the placeholders of `sql`
+    pub fn params(&self) -> Vec<&str> {
+        match self {
+            CatalogQuery::Databases | CatalogQuery::Schemas | CatalogQuery::Extensions => vec![],
+            CatalogQuery::SchemaObjects { schema, .. } => vec![schema],
+            CatalogQuery::RelationObjects {
+                schema, relation, ..
+            } => vec![schema, relation],
+        }
+    }
+
+    /// Object made of a row returned by `sql`
+    pub fn object(
+        &self,
+        schema: Option<String>,
+        relation: Option<String>,
+        name: String,
+        detail: Option<String>,
+        oid: Option<u32>,
+    ) -> CatalogObject {
+        CatalogObject {
+            kind: self.kind(),
+            schema,
+            relation,
+            name,
+            detail,
+            oid,
+        }
+    }
+}
+
+fn schema_objects_sql(kind: ObjectKind) -> String {
+    let relkinds = match kind {
+        ObjectKind::Table => "'r', 'p'",
+        ObjectKind::View => "'v'",
+        ObjectKind::MaterializedView => "'m'",
+        ObjectKind::Sequence => "'S'",
+        ObjectKind::Function => {
+            return "\
+                SELECT n.nspname::text, NULL::text, p.proname::text, \
+                       pg_catalog.pg_get_function_identity_arguments(p.oid), p.oid \
+                FROM pg_catalog.pg_proc p \
+                JOIN pg_catalog.pg_namespace n ON n.oid = p.pronamespace \
+                WHERE n.nspname = $1 \
+                ORDER BY p.proname, 4"
+                .into()
+        }
+        ObjectKind::Type => {
+            // composite types of tables and array types are left out
+            return "\
+                SELECT n.nspname::text, NULL::text, t.typname::text, \
+                       CASE t.typtype \
+                           WHEN 'c' THEN 'composite' WHEN 'd' THEN 'domain' \
+                           WHEN 'e' THEN 'enum' WHEN 'r' THEN 'range' \
+                           WHEN 'm' THEN 'multirange' ELSE 'base' \
+                       END, \
+                       t.oid \
+                FROM pg_catalog.pg_type t \
+                JOIN pg_catalog.pg_namespace n ON n.oid = t.typnamespace \
+                WHERE n.nspname = $1 \
+                  AND (t.typrelid = 0 OR (SELECT c.relkind = 'c' FROM pg_catalog.pg_class c \
+                                          WHERE c.oid = t.typrelid)) \
+                  AND NOT EXISTS (SELECT 1 FROM pg_catalog.pg_type el \
+                                  WHERE el.oid = t.typelem AND el.typarray = t.oid) \
+                ORDER BY t.typname"
+                .into();
+        }
+        kind => unreachable!("{:?} does not belong to a schema", kind),
+    };
+
+    format!(
+        "SELECT n.nspname::text, NULL::text, c.relname::text, NULL::text, c.oid \
+         FROM pg_catalog.pg_class c \
+         JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace \
+         WHERE n.nspname = $1 AND c.relkind IN ({}) \
+         ORDER BY c.relname",
+        relkinds
+    )
+}
+
+fn relation_objects_sql(kind: ObjectKind) -> String {
+    const RELATION: &str = "\
+        JOIN pg_catalog.pg_class c ON c.oid = {relid} \
+        JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace \
+        WHERE n.nspname = $1 AND c.relname = $2";
+
+    let (select, from, relid, filter, order) = match kind {
+        ObjectKind::Column => (
+            "a.attname::text, pg_catalog.format_type(a.atttypid, a.atttypmod), NULL::oid",
+            "pg_catalog.pg_attribute a",
+            "a.attrelid",
+            "a.attnum > 0 AND NOT a.attisdropped",
+            "a.attnum",
+        ),
+        ObjectKind::Index => (
+            "ic.relname::text, \
+             CASE WHEN i.indisprimary THEN 'primary key' WHEN i.indisunique THEN 'unique' END, \
+             ic.oid",
+            "pg_catalog.pg_index i JOIN pg_catalog.pg_class ic ON ic.oid = i.indexrelid",
+            "i.indrelid",
+            "true",
+            "ic.relname",
+        ),
+        ObjectKind::Trigger => (
+            "t.tgname::text, NULL::text, t.oid",
+            "pg_catalog.pg_trigger t",
+            "t.tgrelid",
+            "NOT t.tgisinternal",
+            "t.tgname",
+        ),
+        kind => unreachable!("{:?} does not belong to a relation", kind),
+    };
+
+    format!(
+        "SELECT n.nspname::text, c.relname::text, {} FROM {} {} AND {} ORDER BY {}",
+        select,
+        from,
+        RELATION.replace("{relid}", relid),
+        filter,
+        order
+    )
+}
+
+/// Database object loaded from the catalog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogObject {
+    pub kind: ObjectKind,
+    /// Schema the object lives in, schemas refer to themselves
+    pub schema: Option<String>,
+    /// Relation owning a column, index or trigger
+    pub relation: Option<String>,
+    pub name: String,
+    /// Short description shown next to the name, like a column type or function arguments
+    pub detail: Option<String>,
+    /// Row of the object in its system catalog, columns have none
+    pub oid: Option<u32>,
+}
+
+/// Expandable node listing objects of `query`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogFolder {
+    pub label: &'static str,
+    pub query: CatalogQuery,
+}
+
+impl CatalogFolder {
+    fn new(label: &'static str, query: CatalogQuery) -> Self {
+        Self { label, query }
+    }
+}
+
+/// Top level folders of the explorer
+pub fn root_folders() -> Vec<CatalogFolder> {
+    vec![
+        CatalogFolder::new("Databases", CatalogQuery::Databases),
+        CatalogFolder::new("Schemas", CatalogQuery::Schemas),
+        CatalogFolder::new("Extensions", CatalogQuery::Extensions),
+    ]
+}
+
+impl CatalogObject {
+    /// Folders nested below the object
+    pub fn folders(&self) -> Vec<CatalogFolder> {
+        let schema = || self.schema.clone().unwrap_or_default();
+        let in_schema = |label, kind| {
+            CatalogFolder::new(
+                label,
+                CatalogQuery::SchemaObjects {
+                    schema: schema(),
+                    kind,
+                },
+            )
+        };
+        let in_relation = |label, kind| {
+            CatalogFolder::new(
+                label,
+                CatalogQuery::RelationObjects {
+                    schema: schema(),
+                    relation: self.name.clone(),
+                    kind,
+                },
+            )
+        };
+
+        match self.kind {
+            ObjectKind::Schema => vec![
+                in_schema("Tables", ObjectKind::Table),
+                in_schema("Views", ObjectKind::View),
+                in_schema("Materialized views", ObjectKind::MaterializedView),
+                in_schema("Functions", ObjectKind::Function),
+                in_schema("Sequences", ObjectKind::Sequence),
+                in_schema("Types", ObjectKind::Type),
+            ],
+            ObjectKind::Table => vec![
+                in_relation("Columns", ObjectKind::Column),
+                in_relation("Indexes", ObjectKind::Index),
+                in_relation("Triggers", ObjectKind::Trigger),
+            ],
+            ObjectKind::View => vec![
+                in_relation("Columns", ObjectKind::Column),
+                in_relation("Triggers", ObjectKind::Trigger),
+            ],
+            ObjectKind::MaterializedView => vec![
+                in_relation("Columns", ObjectKind::Column),
+                in_relation("Indexes", ObjectKind::Index),
+            ],
+            _ => vec![],
+        }
+    }
+
+    /// Schema qualified and quoted name of the object
+    pub fn qualified_name(&self) -> String {
+        match &self.schema {
+            Some(schema) if self.kind != ObjectKind::Schema => {
+                format!("{}.{}", quote_ident(schema), quote_ident(&self.name))
+            }
+            _ => quote_ident(&self.name),
+        }
+    }
+
+    /// Query previewing the first `limit` rows of a relation
+    pub fn select_top(&self, limit: usize) -> Option<String> {
+        if !self.kind.is_relation() {
+            return None;
+        }
+
+        Some(format!(
+            "SELECT * FROM {} LIMIT {}",
+            self.qualified_name(),
+            limit
+        ))
+    }
+}
+
+/// Reserved keywords which cannot be used as bare identifiers
+const RESERVED_KEYWORDS: &[&str] = &[
+    "all",
+    "analyse",
+    "analyze",
+    "and",
+    "any",
+    "array",
+    "as",
+    "asc",
+    "asymmetric",
+    "authorization",
+    "binary",
+    "both",
+    "case",
+    "cast",
+    "check",
+    "collate",
+    "collation",
+    "column",
+    "concurrently",
+    "constraint",
+    "create",
+    "cross",
+    "current_catalog",
+    "current_date",
+    "current_role",
+    "current_schema",
+    "current_time",
+    "current_timestamp",
+    "current_user",
+    "default",
+    "deferrable",
+    "desc",
+    "distinct",
+    "do",
+    "else",
+    "end",
+    "except",
+    "false",
+    "fetch",
+    "for",
+    "foreign",
+    "freeze",
+    "from",
+    "full",
+    "grant",
+    "group",
+    "having",
+    "ilike",
+    "in",
+    "initially",
+    "inner",
+    "intersect",
+    "into",
+    "is",
+    "isnull",
+    "join",
+    "lateral",
+    "leading",
+    "left",
+    "like",
+    "limit",
+    "localtime",
+    "localtimestamp",
+    "natural",
+    "not",
+    "notnull",
+    "null",
+    "offset",
+    "on",
+    "only",
+    "or",
+    "order",
+    "outer",
+    "overlaps",
+    "placing",
+    "primary",
+    "references",
+    "returning",
+    "right",
+    "select",
+    "session_user",
+    "similar",
+    "some",
+    "symmetric",
+    "table",
+    "tablesample",
+    "then",
+    "to",
+    "trailing",
+    "true",
+    "union",
+    "unique",
+    "user",
+    "using",
+    "variadic",
+    "verbose",
+    "when",
+    "where",
+    "window",
+    "with",
+];
+
+/// Quotes `ident` unless it can be written bare
+pub fn quote_ident(ident: &str) -> String {
+    let mut chars = ident.chars();
+    let bare = matches!(chars.next(), Some(c) if c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '$')
+        && !RESERVED_KEYWORDS.contains(&ident);
+
+    if bare {
+        ident.to_string()
+    } else {
+        format!("\"{}\"", ident.replace('"', "\"\""))
+    }
+}
+
+/// Quotes the parts of a possibly schema qualified name
+pub fn quote_qualified(name: &str) -> String {
+    name.split('.')
+        .map(quote_ident)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Quotes `text` as a string literal
+pub fn quote_literal(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn object(kind: ObjectKind, schema: &str, name: &str) -> CatalogObject {
+        CatalogObject {
+            kind,
+            schema: Some(schema.into()),
+            relation: None,
+            name: name.into(),
+            detail: None,
+            oid: None,
+        }
+    }
+
+    #[test]
+    fn identifiers_quoting() {
+        assert_eq!(quote_ident("orders"), "orders");
+        assert_eq!(quote_ident("_tmp$1"), "_tmp$1");
+        assert_eq!(quote_ident("Orders"), "\"Orders\"");
+        assert_eq!(quote_ident("user"), "\"user\"");
+        assert_eq!(quote_ident("1st"), "\"1st\"");
+        assert_eq!(quote_ident("a\"b"), "\"a\"\"b\"");
+        assert_eq!(quote_literal("it's"), "'it''s'");
+    }
+
+    #[test]
+    fn select_top_of_relations() {
+        let table = object(ObjectKind::Table, "Sales", "order");
+        assert_eq!(
+            table.select_top(100).as_deref(),
+            Some("SELECT * FROM \"Sales\".\"order\" LIMIT 100")
+        );
+
+        let function = object(ObjectKind::Function, "public", "f");
+        assert_eq!(function.select_top(100), None);
+    }
+
+    #[test]
+    fn nested_folders() {
+        let table = object(ObjectKind::Table, "public", "orders");
+        let folders = table.folders();
+
+        assert_eq!(
+            folders.iter().map(|f| f.label).collect::<Vec<_>>(),
+            vec!["Columns", "Indexes", "Triggers"]
+        );
+        assert_eq!(
+            folders[1].query,
+            CatalogQuery::RelationObjects {
+                schema: "public".into(),
+                relation: "orders".into(),
+                kind: ObjectKind::Index,
+            }
+        );
+        assert_eq!(folders[1].query.params(), vec!["public", "orders"]);
+        assert!(object(ObjectKind::Sequence, "public", "s")
+            .folders()
+            .is_empty());
+    }
+}
@@ -0,0 +1,681 @@
+use super::sql_splitter::{self, statement_at, TokenKind};
+
+/// Query listing relations with their columns, rows are schema, relation, column and its type
+pub const RELATIONS_SQL: &str = "\
+    SELECT n.nspname::text, c.relname::text, a.attname::text, \
+           pg_catalog.format_type(a.atttypid, a.atttypmod) \
+    FROM pg_catalog.pg_class c \
+    JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace \
+    LEFT JOIN pg_catalog.pg_attribute a \
+      ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped \
+    WHERE c.relkind IN ('r', 'p', 'v', 'm', 'f') \
+      AND n.nspname !~ '^pg_(toast|temp_|toast_temp_)' \
+    ORDER BY 1, 2, a.attnum";
+
+/// Query listing functions, rows are schema, name, arguments and result type
+pub const FUNCTIONS_SQL: &str = "\
+    SELECT n.nspname::text, p.proname::text, \
+           pg_catalog.pg_get_function_arguments(p.oid), \
+           pg_catalog.pg_get_function_result(p.oid) \
+    FROM pg_catalog.pg_proc p \
+    JOIN pg_catalog.pg_namespace n ON n.oid = p.pronamespace \
+    WHERE n.nspname !~ '^pg_(toast|temp_|toast_temp_)' \
+    ORDER BY 1, 2";
+
+pub const SCHEMAS_SQL: &str = "\
+    SELECT nspname::text \
+    FROM pg_catalog.pg_namespace \
+    WHERE nspname !~ '^pg_(toast|temp_|toast_temp_)' \
+    ORDER BY 1";
+
+/// Schemas of the effective search path in search order
+pub const SEARCH_PATH_SQL: &str =
+    "SELECT pg_catalog.unnest(pg_catalog.current_schemas(true))::text";
+
+const KEYWORDS: &[&str] = &[
+    "all",
+    "alter",
+    "analyze",
+    "and",
+    "as",
+    "asc",
+    "begin",
+    "between",
+    "by",
+    "cascade",
+    "case",
+    "cast",
+    "check",
+    "coalesce",
+    "column",
+    "commit",
+    "constraint",
+    "create",
+    "cross",
+    "default",
+    "delete",
+    "desc",
+    "distinct",
+    "drop",
+    "else",
+    "end",
+    "except",
+    "exists",
+    "explain",
+    "false",
+    "fetch",
+    "filter",
+    "first",
+    "for",
+    "foreign",
+    "from",
+    "full",
+    "grant",
+    "group",
+    "having",
+    "ilike",
+    "in",
+    "index",
+    "inner",
+    "insert",
+    "intersect",
+    "interval",
+    "into",
+    "is",
+    "join",
+    "key",
+    "lateral",
+    "left",
+    "like",
+    "limit",
+    "materialized",
+    "natural",
+    "not",
+    "null",
+    "nulls",
+    "offset",
+    "on",
+    "or",
+    "order",
+    "outer",
+    "over",
+    "partition",
+    "primary",
+    "references",
+    "returning",
+    "revoke",
+    "right",
+    "rollback",
+    "schema",
+    "select",
+    "sequence",
+    "set",
+    "table",
+    "then",
+    "to",
+    "true",
+    "truncate",
+    "union",
+    "unique",
+    "update",
+    "using",
+    "values",
+    "view",
+    "when",
+    "where",
+    "window",
+    "with",
+];
+
+/// Words which cannot be a table alias
+const CLAUSE_KEYWORDS: &[&str] = &[
+    "cross",
+    "except",
+    "fetch",
+    "for",
+    "from",
+    "full",
+    "group",
+    "having",
+    "inner",
+    "intersect",
+    "join",
+    "lateral",
+    "left",
+    "limit",
+    "natural",
+    "offset",
+    "on",
+    "order",
+    "returning",
+    "right",
+    "set",
+    "union",
+    "using",
+    "values",
+    "where",
+    "window",
+];
+
+/// Keywords followed by a relation name
+const RELATION_KEYWORDS: &[&str] = &["from", "join", "update", "into", "table", "truncate"];
+
+const SNIPPETS: &[(&str, &str, &str)] = &[
+    ("sel", "SELECT *\nFROM ", "select from a table"),
+    ("selw", "SELECT *\nFROM \nWHERE ", "select with a condition"),
+    ("cnt", "SELECT count(*)\nFROM ", "count rows"),
+    ("ins", "INSERT INTO  ()\nVALUES ();", "insert a row"),
+    ("upd", "UPDATE \nSET \nWHERE ;", "update rows"),
+    ("del", "DELETE FROM \nWHERE ;", "delete rows"),
+    (
+        "cte",
+        "WITH  AS (\n    \n)\nSELECT *\nFROM ;",
+        "common table expression",
+    ),
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RelationInfo {
+    pub schema: String,
+    pub name: String,
+    /// Column names with their types
+    pub columns: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionInfo {
+    pub schema: String,
+    pub name: String,
+    pub arguments: String,
+    pub result: String,
+}
+
+/// Catalog objects known to a session, used to complete names in the editor
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CatalogCache {
+    pub schemas: Vec<String>,
+    /// Schemas visible without qualification, in search order
+    pub search_path: Vec<String>,
+    pub relations: Vec<RelationInfo>,
+    pub functions: Vec<FunctionInfo>,
+}
+
+impl CatalogCache {
+    /// Adds a row of `RELATIONS_SQL`, rows of one relation have to follow each other
+    pub fn push_relation_column(
+        &mut self,
+        schema: String,
+        relation: String,
+        column: Option<(String, String)>,
+    ) {
+        let same = matches!(
+            self.relations.last(),
+            Some(last) if last.schema == schema && last.name == relation
+        );
+        if !same {
+            self.relations.push(RelationInfo {
+                schema,
+                name: relation,
+                columns: vec![],
+            });
+        }
+
+        if let (Some(column), Some(last)) = (column, self.relations.last_mut()) {
+            last.columns.push(column);
+        }
+    }
+
+    fn is_visible(&self, schema: &str) -> bool {
+        self.search_path.iter().any(|s| s == schema)
+    }
+
+    fn relation(&self, schema: Option<&str>, name: &str) -> Option<&RelationInfo> {
+        match schema {
+            Some(schema) => self
+                .relations
+                .iter()
+                .find(|r| r.schema == schema && r.name == name),
+            None => self.search_path.iter().find_map(|schema| {
+                self.relations
+                    .iter()
+                    .find(|r| &r.schema == schema && r.name == name)
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProposalKind {
+    Column,
+    Relation,
+    Schema,
+    Function,
+    Keyword,
+    Snippet,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proposal {
+    pub kind: ProposalKind,
+    pub label: String,
+    /// Text replacing the typed prefix
+    pub text: String,
+    pub detail: Option<String>,
+}
+
+impl Proposal {
+    fn new(kind: ProposalKind, label: &str, text: &str, detail: Option<&str>) -> Self {
+        Self {
+            kind,
+            label: label.to_string(),
+            text: text.to_string(),
+            detail: detail.map(str::to_string),
+        }
+    }
+}
+
+/// Proposals for the word ending at `cursor`, which starts at byte `start`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Completion {
+    pub start: usize,
+    pub proposals: Vec<Proposal>,
+}
+
+/// Proposes names and keywords for the word being typed at the byte offset `cursor`.
+///
+/// After `alias.` or `table.` the columns of the relation are proposed, after `schema.` its
+/// relations and functions, after `FROM`, `JOIN` and alike the relations. Elsewhere columns of
+/// the relations referenced by the statement are proposed along with functions, keywords and
+/// snippets, but only once something was typed.
+pub fn complete(text: &str, cursor: usize, cache: &CatalogCache) -> Completion {
+    let cursor = cursor.min(text.len());
+    let start = text[..cursor]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word_char(*c))
+        .last()
+        .map_or(cursor, |(idx, _)| idx);
+    let prefix = text[start..cursor].to_lowercase();
+
+    let statement = statement_at(text, start)
+        .filter(|range| range.start <= start)
+        .unwrap_or(start..cursor);
+    let before = tokenize(&text[statement.start.min(start)..start]);
+    let references = || relation_references(&tokenize(&text[statement.clone()]));
+
+    let mut proposals = vec![];
+
+    match before.as_slice() {
+        [.., qualifier, Token::Punct('.')] => {
+            let qualifier = match qualifier.name() {
+                Some(qualifier) => qualifier,
+                None => return Completion::default(),
+            };
+
+            let referenced = references()
+                .into_iter()
+                .find(|r| r.alias.as_deref() == Some(qualifier.as_str()) || r.name == qualifier)
+                .and_then(|r| cache.relation(r.schema.as_deref(), &r.name));
+
+            if let Some(relation) = referenced.or_else(|| cache.relation(None, &qualifier)) {
+                proposals.extend(column_proposals(relation));
+            } else {
+                proposals.extend(schema_object_proposals(cache, &qualifier));
+            }
+        }
+        [.., Token::Word(keyword)] if RELATION_KEYWORDS.contains(&keyword.as_str()) => {
+            proposals.extend(relation_proposals(cache));
+        }
+        [.., Token::Word(keyword), Token::Word(_), Token::Punct(',')] if keyword == "from" => {
+            proposals.extend(relation_proposals(cache));
+        }
+        _ if prefix.is_empty() => {}
+        _ => {
+            for reference in references() {
+                if let Some(relation) = cache.relation(reference.schema.as_deref(), &reference.name)
+                {
+                    proposals.extend(column_proposals(relation));
+                }
+            }
+
+            proposals.extend(
+                cache
+                    .functions
+                    .iter()
+                    .filter(|f| cache.is_visible(&f.schema))
+                    .map(function_proposal),
+            );
+
+            let uppercase = text[start..cursor].chars().any(|c| c.is_uppercase());
+            proposals.extend(KEYWORDS.iter().map(|keyword| {
+                let text = if uppercase {
+                    keyword.to_uppercase()
+                } else {
+                    keyword.to_string()
+                };
+                Proposal::new(ProposalKind::Keyword, &text, &text, None)
+            }));
+
+            proposals.extend(SNIPPETS.iter().map(|(trigger, body, description)| {
+                Proposal::new(ProposalKind::Snippet, trigger, body, Some(description))
+            }));
+        }
+    }
+
+    proposals.retain(|p| p.label.to_lowercase().starts_with(&prefix));
+    proposals.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.label.cmp(&b.label)));
+    proposals.dedup_by(|a, b| a.kind == b.kind && a.label == b.label);
+
+    Completion { start, proposals }
+}
+
+fn column_proposals(relation: &RelationInfo) -> impl Iterator<Item = Proposal> + '_ {
+    relation
+        .columns
+        .iter()
+        .map(|(name, ty)| Proposal::new(ProposalKind::Column, name, &quote_name(name), Some(ty)))
+}
+
+/// Relations on the search path and schemas qualifying the others
+fn relation_proposals(cache: &CatalogCache) -> impl Iterator<Item = Proposal> + '_ {
+    let relations = cache
+        .relations
+        .iter()
+        .filter(move |r| cache.is_visible(&r.schema))
+        .map(|r| {
+            Proposal::new(
+                ProposalKind::Relation,
+                &r.name,
+                &quote_name(&r.name),
+                Some(&r.schema),
+            )
+        });
+
+    let schemas = cache
+        .schemas
+        .iter()
+        .map(|s| Proposal::new(ProposalKind::Schema, s, &quote_name(s), Some("schema")));
+
+    relations.chain(schemas)
+}
+
+fn schema_object_proposals<'a>(
+    cache: &'a CatalogCache,
+    schema: &'a str,
+) -> impl Iterator<Item = Proposal> + 'a {
+    let relations = cache
+        .relations
+        .iter()
+        .filter(move |r| r.schema == schema)
+        .map(|r| Proposal::new(ProposalKind::Relation, &r.name, &quote_name(&r.name), None));
+
+    let functions = cache
+        .functions
+        .iter()
+        .filter(move |f| f.schema == schema)
+        .map(function_proposal);
+
+    relations.chain(functions)
+}
+
+fn function_proposal(function: &FunctionInfo) -> Proposal {
+    Proposal::new(
+        ProposalKind::Function,
+        &format!("{}({})", function.name, function.arguments),
+        &format!("{}(", quote_name(&function.name)),
+        Some(&function.result),
+    )
+}
+
+/// Quotes names which would not survive case folding
+fn quote_name(name: &str) -> String {
+    let bare = name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && !name.starts_with(|c: char| c.is_ascii_digit());
+
+    if bare {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// Unquoted word folded to lowercase
+    Word(String),
+    Quoted(String),
+    Punct(char),
+}
+
+impl Token {
+    fn name(&self) -> Option<String> {
+        match self {
+            Token::Word(word) | Token::Quoted(word) => Some(word.clone()),
+            Token::Punct(_) => None,
+        }
+    }
+}
+
+/// Tokens of `text` as far as completion cares: comments are skipped and string literals become
+/// a single quote token
+fn tokenize(text: &str) -> Vec<Token> {
+    sql_splitter::tokenize(text)
+        .into_iter()
+        .filter_map(|token| match token.kind {
+            TokenKind::Word | TokenKind::Number | TokenKind::Param => {
+                Some(Token::Word(token.value()))
+            }
+            TokenKind::QuotedName => Some(Token::Quoted(token.value())),
+            TokenKind::String => Some(Token::Punct('\'')),
+            TokenKind::Symbol => token.text.chars().next().map(Token::Punct),
+            TokenKind::Comment => None,
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RelationRef {
+    schema: Option<String>,
+    name: String,
+    alias: Option<String>,
+}
+
+/// Relations named after `FROM`, `JOIN`, `UPDATE` and `INTO`, with their aliases
+fn relation_references(tokens: &[Token]) -> Vec<RelationRef> {
+    let mut references = vec![];
+    let mut idx = 0;
+
+    while idx < tokens.len() {
+        let keyword = match &tokens[idx] {
+            Token::Word(word) if ["from", "join", "update", "into"].contains(&word.as_str()) => {
+                word.clone()
+            }
+            _ => {
+                idx += 1;
+                continue;
+            }
+        };
+        idx += 1;
+
+        while let Some((reference, next)) = relation_reference(tokens, idx) {
+            references.push(reference);
+            idx = next;
+
+            // FROM takes a list of relations
+            if keyword == "from" && tokens.get(idx) == Some(&Token::Punct(',')) {
+                idx += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    references
+}
+
+fn relation_reference(tokens: &[Token], idx: usize) -> Option<(RelationRef, usize)> {
+    let is_name = |token: Option<&Token>| match token {
+        Some(Token::Word(word)) => !CLAUSE_KEYWORDS.contains(&word.as_str()),
+        Some(Token::Quoted(_)) => true,
+        _ => false,
+    };
+
+    if !is_name(tokens.get(idx)) {
+        return None;
+    }
+
+    let (schema, name, mut next) = match (tokens.get(idx + 1), tokens.get(idx + 2)) {
+        (Some(Token::Punct('.')), name) if is_name(name) => {
+            (tokens[idx].name(), tokens[idx + 2].name()?, idx + 3)
+        }
+        _ => (None, tokens[idx].name()?, idx + 1),
+    };
+
+    if tokens.get(next) == Some(&Token::Word("as".into())) {
+        next += 1;
+    }
+
+    let alias = if is_name(tokens.get(next)) {
+        next += 1;
+        tokens[next - 1].name()
+    } else {
+        None
+    };
+
+    Some((
+        RelationRef {
+            schema,
+            name,
+            alias,
+        },
+        next,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cache() -> CatalogCache {
+        let mut cache = CatalogCache {
+            schemas: vec!["public".into(), "sales".into()],
+            search_path: vec!["pg_catalog".into(), "public".into()],
+            ..Default::default()
+        };
+
+        let columns = [
+            ("public", "customers", "id"),
+            ("public", "customers", "name"),
+            ("sales", "orders", "id"),
+            ("sales", "orders", "customer_id"),
+            ("sales", "orders", "Total"),
+        ];
+        for (schema, relation, column) in columns.iter() {
+            cache.push_relation_column(
+                schema.to_string(),
+                relation.to_string(),
+                Some((column.to_string(), "integer".into())),
+            );
+        }
+
+        cache.functions.push(FunctionInfo {
+            schema: "pg_catalog".into(),
+            name: "count".into(),
+            arguments: "\"any\"".into(),
+            result: "bigint".into(),
+        });
+
+        cache
+    }
+
+    fn labels(text: &str) -> Vec<String> {
+        complete(text, text.len(), &cache())
+            .proposals
+            .into_iter()
+            .map(|p| p.label)
+            .collect()
+    }
+
+    #[test]
+    fn columns_of_aliased_relation() {
+        let text = "select o.c from sales.orders as o join customers c on true";
+        let cursor = "select o.c".len();
+        let completion = complete(text, cursor, &cache());
+
+        assert_eq!(completion.start, "select o.".len());
+        assert_eq!(
+            completion
+                .proposals
+                .iter()
+                .map(|p| p.text.as_str())
+                .collect::<Vec<_>>(),
+            vec!["customer_id"]
+        );
+        assert_eq!(
+            labels("select * from customers c where c."),
+            vec!["id", "name"]
+        );
+    }
+
+    #[test]
+    fn relations_after_from() {
+        assert_eq!(
+            labels("select * from "),
+            vec!["customers", "public", "sales"]
+        );
+        assert_eq!(labels("select * from customers, s"), vec!["sales"]);
+        assert_eq!(labels("select * from sales."), vec!["orders"]);
+    }
+
+    #[test]
+    fn keywords_functions_and_columns() {
+        let text = "select co from public.customers";
+        let completion = complete(text, "select co".len(), &cache());
+        let kinds = completion
+            .proposals
+            .iter()
+            .map(|p| (p.kind, p.label.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            kinds,
+            vec![
+                (ProposalKind::Function, "count(\"any\")"),
+                (ProposalKind::Keyword, "coalesce"),
+                (ProposalKind::Keyword, "column"),
+                (ProposalKind::Keyword, "commit"),
+                (ProposalKind::Keyword, "constraint"),
+            ]
+        );
+        assert_eq!(labels("SELECT * FROM customers WH"), vec!["WHEN", "WHERE"]);
+        assert!(labels("select ").is_empty());
+    }
+
+    #[test]
+    fn quoted_names_inserted_quoted() {
+        let completion = complete(
+            "select o.t from sales.orders o",
+            "select o.t".len(),
+            &cache(),
+        );
+        assert_eq!(completion.proposals[0].text, "\"Total\"");
+    }
+
+    #[test]
+    fn strings_and_comments_skipped() {
+        assert_eq!(
+            labels("select E'it\\'s' /* from /* nested */ x */ from customers c where c."),
+            vec!["id", "name"]
+        );
+        assert_eq!(
+            labels("select $$ from sales.orders o $$ from customers o where o."),
+            vec!["id", "name"]
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::{env, fmt, path::PathBuf, str::FromStr};
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::{config, Config};
+
+/// libpq-style `sslmode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SslMode {
+    /// Plain connection only
+    Disable,
+    /// TLS if the server supports it, without certificate verification
+    #[default]
+    Prefer,
+    /// TLS only, without certificate verification
+    Require,
+    /// TLS only, server certificate has to be signed by a trusted CA
+    VerifyCa,
+    /// Like `VerifyCa`, additionally the certificate has to match the host name
+    VerifyFull,
+}
+
+impl SslMode {
+    pub const ALL: [SslMode; 5] = [
+        SslMode::Disable,
+        SslMode::Prefer,
+        SslMode::Require,
+        SslMode::VerifyCa,
+        SslMode::VerifyFull,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SslMode::Disable => "disable",
+            SslMode::Prefer => "prefer",
+            SslMode::Require => "require",
+            SslMode::VerifyCa => "verify-ca",
+            SslMode::VerifyFull => "verify-full",
+        }
+    }
+}
+
+impl fmt::Display for SslMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SslMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match SslMode::ALL.iter().find(|mode| mode.as_str() == s) {
+            Some(mode) => Ok(*mode),
+            None => bail!("invalid sslmode '{}'", s),
+        }
+    }
+}
+
+/// Named set of connection parameters persisted in the profile store
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionProfile {
+    pub name: String,
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "default_user")]
+    pub user: String,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default = "default_dbname")]
+    pub dbname: String,
+    /// Command-line options passed to the server at connection startup
+    #[serde(default)]
+    pub options: Option<String>,
+    /// Color tag used to tell profiles apart in the UI, e.g. `#e01b24`
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub sslmode: SslMode,
+    /// PEM file with CA certificates trusted in addition to the system ones
+    #[serde(default)]
+    pub sslrootcert: Option<PathBuf>,
+    /// PEM file with the client certificate chain
+    #[serde(default)]
+    pub sslcert: Option<PathBuf>,
+    /// PEM file with the PKCS #8 client private key
+    #[serde(default)]
+    pub sslkey: Option<PathBuf>,
+}
+
+impl Default for ConnectionProfile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            host: default_host(),
+            port: default_port(),
+            user: default_user(),
+            password: None,
+            dbname: default_dbname(),
+            options: None,
+            color: None,
+            sslmode: SslMode::default(),
+            sslrootcert: None,
+            sslcert: None,
+            sslkey: None,
+        }
+    }
+}
+
+impl ConnectionProfile {
+    /// Profile built from `PG_*` environment variables, used when no profile was picked
+    pub fn from_env() -> Self {
+        let port = env::var("PG_PORT")
+            .ok()
+            .and_then(|port| port.parse().ok())
+            .unwrap_or_else(default_port);
+
+        let sslmode = match env::var("PG_SSLMODE") {
+            Ok(mode) => mode.parse().unwrap_or_else(|e| {
+                log::warn!("{}, falling back to default", e);
+                SslMode::default()
+            }),
+            Err(_) => SslMode::default(),
+        };
+
+        Self {
+            name: "environment".into(),
+            host: env::var("PG_HOST").unwrap_or_else(|_| default_host()),
+            port,
+            user: env::var("PG_USER").unwrap_or_else(|_| default_user()),
+            password: env::var("PG_PASS").ok(),
+            dbname: env::var("PG_DBNAME").unwrap_or_else(|_| default_dbname()),
+            options: None,
+            color: None,
+            sslmode,
+            sslrootcert: env::var_os("PG_SSLROOTCERT").map(PathBuf::from),
+            sslcert: env::var_os("PG_SSLCERT").map(PathBuf::from),
+            sslkey: env::var_os("PG_SSLKEY").map(PathBuf::from),
+        }
+    }
+
+    pub fn pg_config(&self) -> Config {
+        let mut cfg = Config::new();
+        cfg.host(&self.host);
+        cfg.port(self.port);
+        cfg.user(&self.user);
+        cfg.dbname(&self.dbname);
+        cfg.application_name("slonik");
+        cfg.ssl_mode(match self.sslmode {
+            SslMode::Disable => config::SslMode::Disable,
+            SslMode::Prefer => config::SslMode::Prefer,
+            SslMode::Require | SslMode::VerifyCa | SslMode::VerifyFull => config::SslMode::Require,
+        });
+
+        if let Some(password) = &self.password {
+            cfg.password(password);
+        }
+
+        if let Some(options) = self.options.as_deref().filter(|o| !o.is_empty()) {
+            cfg.options(options);
+        }
+
+        cfg
+    }
+}
+
+fn default_host() -> String {
+    "localhost".into()
+}
+
+fn default_port() -> u16 {
+    5432
+}
+
+fn default_user() -> String {
+    "postgres".into()
+}
+
+fn default_dbname() -> String {
+    "postgres".into()
+}
@@ -0,0 +1,364 @@
+use anyhow::{bail, Result};
+
+use super::{
+    pg_response::{Header, Row, Table},
+    sql_splitter::{self, leading_keyword, split_statements, TokenKind},
+    value::Value,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyDirection {
+    /// `COPY ... TO STDOUT`, the client receives the data
+    ToStdout,
+    /// `COPY ... FROM STDIN`, the client sends the data
+    FromStdin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyFormat {
+    Text,
+    Csv,
+    Binary,
+}
+
+impl CopyFormat {
+    /// Extension of files in the format
+    pub fn extension(self) -> &'static str {
+        match self {
+            CopyFormat::Text => "tsv",
+            CopyFormat::Csv => "csv",
+            CopyFormat::Binary => "bin",
+        }
+    }
+}
+
+/// `COPY` statement exchanging its data with the client, with the options needed to read its
+/// output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyStatement {
+    pub direction: CopyDirection,
+    pub format: CopyFormat,
+    /// Whether the first line names the columns
+    pub header: bool,
+    pub delimiter: u8,
+    pub quote: u8,
+    /// Text standing for nulls
+    pub null: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// Lowercased keyword or bare identifier
+    Word(String),
+    /// Quoted identifier, only telling it is not a keyword
+    Ident,
+    /// String literal without its quotes
+    Str(String),
+    Symbol(char),
+}
+
+impl CopyStatement {
+    /// Recognizes `text` as a single `COPY` statement reading from STDIN or writing to STDOUT,
+    /// copies from and to server files are left to the server
+    pub fn parse(text: &str) -> Option<Self> {
+        if split_statements(text).len() != 1 || leading_keyword(text).as_deref() != Some("copy") {
+            return None;
+        }
+
+        let tokens = tokenize(text);
+        let word = |idx: usize| match tokens.get(idx) {
+            Some(Token::Word(word)) => word.as_str(),
+            _ => "",
+        };
+
+        // the relation or the parenthesized query comes first
+        let mut depth = 0;
+        let mut direction = None;
+        let mut idx = 0;
+        while idx < tokens.len() && direction.is_none() {
+            match &tokens[idx] {
+                Token::Symbol('(') => depth += 1,
+                Token::Symbol(')') => depth -= 1,
+                _ if depth > 0 => {}
+                _ => {
+                    direction = match (word(idx), word(idx + 1)) {
+                        ("to", "stdout") => Some(CopyDirection::ToStdout),
+                        ("from", "stdin") => Some(CopyDirection::FromStdin),
+                        ("to", _) | ("from", _) => return None,
+                        _ => None,
+                    }
+                }
+            }
+            idx += 1;
+        }
+
+        let mut copy = Self {
+            direction: direction?,
+            format: CopyFormat::Text,
+            header: false,
+            delimiter: b'\t',
+            quote: b'"',
+            null: "\\N".into(),
+        };
+        let mut delimiter = None;
+        let mut null = None;
+
+        // options, both `WITH (FORMAT csv, HEADER)` and the older `WITH CSV HEADER`
+        let string = |idx: usize| match tokens.get(idx) {
+            Some(Token::Str(text)) => Some(text.clone()),
+            _ => None,
+        };
+        idx += 1;
+        while idx < tokens.len() {
+            match word(idx) {
+                "text" => copy.format = CopyFormat::Text,
+                "csv" => copy.format = CopyFormat::Csv,
+                "binary" => copy.format = CopyFormat::Binary,
+                "header" => {
+                    copy.header = !matches!(word(idx + 1), "false" | "off" | "0");
+                    if matches!(word(idx + 1), "true" | "on" | "1" | "false" | "off" | "0") {
+                        idx += 1;
+                    }
+                }
+                option @ "delimiter" | option @ "null" | option @ "quote" => {
+                    if word(idx + 1) == "as" {
+                        idx += 1;
+                    }
+                    let value = string(idx + 1);
+                    match option {
+                        "delimiter" => delimiter = value.and_then(|v| v.bytes().next()),
+                        "null" => null = value,
+                        _ => copy.quote = value.and_then(|v| v.bytes().next()).unwrap_or(b'"'),
+                    }
+                    idx += 1;
+                }
+                _ => {}
+            }
+            idx += 1;
+        }
+
+        if copy.format == CopyFormat::Csv {
+            copy.delimiter = b',';
+            copy.null = String::new();
+        }
+        copy.delimiter = delimiter.unwrap_or(copy.delimiter);
+        copy.null = null.unwrap_or(copy.null);
+
+        Some(copy)
+    }
+
+    /// Rows in the output of `messages` data messages, the server sends one per row, one for
+    /// the header line and one for the binary trailer
+    pub fn output_rows(&self, messages: u64) -> u64 {
+        let extra = match self.format {
+            CopyFormat::Binary => 1,
+            _ if self.header => 1,
+            _ => 0,
+        };
+
+        messages.saturating_sub(extra)
+    }
+
+    /// Reads the output of the statement as a table of text values, columns are named by the
+    /// header if there is one
+    pub fn decode(&self, data: &[u8]) -> Result<Table> {
+        let mut records = match self.format {
+            CopyFormat::Binary => bail!("Output in the binary format can only be saved to a file"),
+            CopyFormat::Csv => self.csv_records(data)?,
+            CopyFormat::Text => String::from_utf8_lossy(data)
+                .lines()
+                .map(|line| {
+                    line.split(char::from(self.delimiter))
+                        .map(|f| self.text_field(f))
+                        .collect()
+                })
+                .collect(),
+        };
+
+        let names = if self.header && !records.is_empty() {
+            records
+                .remove(0)
+                .into_iter()
+                .map(Option::unwrap_or_default)
+                .collect()
+        } else {
+            let width = records.iter().map(Vec::len).max().unwrap_or(0);
+            (1..=width)
+                .map(|n| format!("column_{}", n))
+                .collect::<Vec<_>>()
+        };
+
+        let mut header = Header::default();
+        for name in &names {
+            header.push(name.as_str());
+        }
+
+        let rows = records
+            .into_iter()
+            .map(|fields| {
+                let mut values = fields
+                    .into_iter()
+                    .map(|field| field.map_or(Value::Null, Value::Text))
+                    .collect::<Vec<_>>();
+                values.resize(names.len(), Value::Null);
+                Row::new(values)
+            })
+            .collect();
+
+        Ok(Table::new(header, rows))
+    }
+
+    /// CSV fields equal to the null text are nulls, so with the default an empty quoted string
+    /// is taken as a null as well
+    fn csv_records(&self, data: &[u8]) -> Result<Vec<Vec<Option<String>>>> {
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(data);
+
+        let mut records = vec![];
+        let mut record = csv::StringRecord::new();
+        while reader.read_record(&mut record)? {
+            let fields = record
+                .iter()
+                .map(|field| Some(field.to_string()).filter(|field| *field != self.null))
+                .collect();
+            records.push(fields);
+        }
+
+        Ok(records)
+    }
+
+    /// Field of the text format with its backslash escapes resolved
+    fn text_field(&self, field: &str) -> Option<String> {
+        if field == self.null {
+            return None;
+        }
+
+        let mut text = String::with_capacity(field.len());
+        let mut chars = field.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                text.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('r') => text.push('\r'),
+                Some('t') => text.push('\t'),
+                Some('b') => text.push('\u{8}'),
+                Some('f') => text.push('\u{c}'),
+                Some('v') => text.push('\u{b}'),
+                Some(c) => text.push(c),
+                None => text.push('\\'),
+            }
+        }
+
+        Some(text)
+    }
+}
+
+/// Splits the statement into words, quoted names, strings and symbols, skipping comments
+fn tokenize(text: &str) -> Vec<Token> {
+    sql_splitter::tokenize(text)
+        .into_iter()
+        .filter_map(|token| match token.kind {
+            TokenKind::Word | TokenKind::Number | TokenKind::Param => {
+                Some(Token::Word(token.value()))
+            }
+            TokenKind::QuotedName => Some(Token::Ident),
+            TokenKind::String => Some(Token::Str(token.value())),
+            TokenKind::Symbol => token.text.chars().next().map(Token::Symbol),
+            TokenKind::Comment => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn copy_directions() {
+        let copy = CopyStatement::parse("copy orders to stdout;").unwrap();
+        assert_eq!(copy.direction, CopyDirection::ToStdout);
+        assert_eq!(copy.format, CopyFormat::Text);
+        assert_eq!((copy.delimiter, copy.null.as_str()), (b'\t', "\\N"));
+
+        let copy = CopyStatement::parse("COPY orders (id, note) FROM STDIN").unwrap();
+        assert_eq!(copy.direction, CopyDirection::FromStdin);
+
+        // the words inside the query do not count
+        let copy = CopyStatement::parse("COPY (SELECT * FROM t) TO STDOUT").unwrap();
+        assert_eq!(copy.direction, CopyDirection::ToStdout);
+
+        assert_eq!(CopyStatement::parse("COPY t TO '/tmp/t.csv'"), None);
+        assert_eq!(CopyStatement::parse("COPY t FROM PROGRAM 'cat'"), None);
+        assert_eq!(CopyStatement::parse("SELECT 1"), None);
+        assert_eq!(CopyStatement::parse("COPY t TO STDOUT; SELECT 1"), None);
+    }
+
+    #[test]
+    fn copy_options() {
+        let copy = CopyStatement::parse(
+            "COPY t TO STDOUT WITH (FORMAT csv, HEADER true, DELIMITER ';', NULL 'NULL')",
+        )
+        .unwrap();
+        assert_eq!(copy.format, CopyFormat::Csv);
+        assert!(copy.header);
+        assert_eq!(copy.delimiter, b';');
+        assert_eq!(copy.null, "NULL");
+
+        let copy = CopyStatement::parse("copy t to stdout with csv header quote as ''''").unwrap();
+        assert_eq!(copy.format, CopyFormat::Csv);
+        assert!(copy.header);
+        assert_eq!(copy.quote, b'\'');
+        assert_eq!(copy.delimiter, b',');
+
+        let copy =
+            CopyStatement::parse("COPY t TO STDOUT /* (FORMAT csv) */ (DELIMITER E'\\t')").unwrap();
+        assert_eq!(copy.format, CopyFormat::Text);
+        assert_eq!(copy.delimiter, b'\t');
+
+        let copy = CopyStatement::parse("COPY t TO STDOUT (FORMAT binary)").unwrap();
+        assert_eq!(copy.format, CopyFormat::Binary);
+        assert_eq!(copy.output_rows(3), 2);
+    }
+
+    #[test]
+    fn decode_text() {
+        let copy = CopyStatement::parse("COPY t TO STDOUT").unwrap();
+        let table = copy.decode(b"1\ta\\tb\\\\c\n2\t\\N\n").unwrap();
+
+        assert_eq!(
+            table.header.names().collect::<Vec<_>>(),
+            ["column_1", "column_2"]
+        );
+        assert_eq!(
+            table.rows,
+            vec![
+                Row::new(vec![Value::Text("1".into()), Value::Text("a\tb\\c".into())]),
+                Row::new(vec![Value::Text("2".into()), Value::Null]),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_csv() {
+        let copy = CopyStatement::parse("COPY t TO STDOUT (FORMAT csv, HEADER)").unwrap();
+        let table = copy.decode(b"id,note\n1,\"a,\"\"b\"\"\"\n2,\n").unwrap();
+
+        assert_eq!(table.header.names().collect::<Vec<_>>(), ["id", "note"]);
+        assert_eq!(
+            table.rows,
+            vec![
+                Row::new(vec![Value::Text("1".into()), Value::Text("a,\"b\"".into())]),
+                Row::new(vec![Value::Text("2".into()), Value::Null]),
+            ]
+        );
+        assert_eq!(copy.output_rows(3), 2);
+    }
+}
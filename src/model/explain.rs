@@ -0,0 +1,377 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::{Map, Value as JsonValue};
+
+use super::{pg_response::PgResponse, value::Value};
+
+/// Column name of `EXPLAIN` results
+const PLAN_COLUMN: &str = "QUERY PLAN";
+
+/// How much the statement reveals about its execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplainMode {
+    /// Estimates of the planner only, the statement is not executed
+    Plan,
+    /// Statement is executed and its changes rolled back, the plan carries actual times, rows
+    /// and buffers
+    Analyze,
+}
+
+/// Savepoint undoing an analyzed statement inside an open transaction
+const ANALYZE_SAVEPOINT: &str = "slonik_explain";
+
+impl ExplainMode {
+    /// Wraps `statement` so that it returns its plan in JSON.
+    ///
+    /// An analyzed statement runs in a transaction that is rolled back, or up to a savepoint
+    /// when `in_transaction`, so that data it modifies stays as it was.
+    pub fn wrap(self, statement: &str, in_transaction: bool) -> String {
+        match self {
+            ExplainMode::Plan => format!("EXPLAIN (FORMAT JSON) {}", statement.trim()),
+            ExplainMode::Analyze => {
+                let explain = format!(
+                    "EXPLAIN (FORMAT JSON, ANALYZE, BUFFERS) {};",
+                    statement.trim().trim_end_matches(';').trim_end()
+                );
+
+                if in_transaction {
+                    format!(
+                        "SAVEPOINT {0};\n{1}\nROLLBACK TO SAVEPOINT {0};",
+                        ANALYZE_SAVEPOINT, explain
+                    )
+                } else {
+                    format!("BEGIN;\n{}\nROLLBACK;", explain)
+                }
+            }
+        }
+    }
+}
+
+/// Execution plan of a statement as returned by `EXPLAIN (FORMAT JSON)`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Plan {
+    #[serde(rename = "Plan")]
+    pub root: PlanNode,
+    /// Milliseconds
+    #[serde(rename = "Planning Time")]
+    pub planning_time: Option<f64>,
+    /// Milliseconds, only with `ANALYZE`
+    #[serde(rename = "Execution Time")]
+    pub execution_time: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PlanNode {
+    #[serde(rename = "Node Type")]
+    pub node_type: String,
+    #[serde(rename = "Relation Name")]
+    pub relation: Option<String>,
+    #[serde(rename = "Alias")]
+    pub alias: Option<String>,
+    #[serde(rename = "Index Name")]
+    pub index: Option<String>,
+    #[serde(rename = "Join Type")]
+    pub join_type: Option<String>,
+    #[serde(rename = "Startup Cost")]
+    pub startup_cost: f64,
+    #[serde(rename = "Total Cost")]
+    pub total_cost: f64,
+    /// Estimated rows per loop
+    #[serde(rename = "Plan Rows")]
+    pub plan_rows: f64,
+    /// Milliseconds per loop
+    #[serde(rename = "Actual Startup Time")]
+    pub actual_startup_time: Option<f64>,
+    /// Milliseconds per loop
+    #[serde(rename = "Actual Total Time")]
+    pub actual_total_time: Option<f64>,
+    /// Rows per loop
+    #[serde(rename = "Actual Rows")]
+    pub actual_rows: Option<f64>,
+    #[serde(rename = "Actual Loops")]
+    pub actual_loops: Option<f64>,
+    #[serde(rename = "Shared Hit Blocks")]
+    pub shared_hit_blocks: Option<u64>,
+    #[serde(rename = "Shared Read Blocks")]
+    pub shared_read_blocks: Option<u64>,
+    #[serde(rename = "Shared Dirtied Blocks")]
+    pub shared_dirtied_blocks: Option<u64>,
+    #[serde(rename = "Shared Written Blocks")]
+    pub shared_written_blocks: Option<u64>,
+    #[serde(rename = "Temp Read Blocks")]
+    pub temp_read_blocks: Option<u64>,
+    #[serde(rename = "Temp Written Blocks")]
+    pub temp_written_blocks: Option<u64>,
+    #[serde(rename = "Plans", default)]
+    pub children: Vec<PlanNode>,
+    /// Remaining properties, e.g. conditions and sort keys
+    #[serde(flatten)]
+    pub details: Map<String, JsonValue>,
+}
+
+impl Plan {
+    /// Finds the plan in the result of `EXPLAIN (FORMAT JSON)`, which may be surrounded by
+    /// the transaction control of [`ExplainMode::wrap`]
+    pub fn from_responses(responses: &[PgResponse]) -> Option<Result<Self>> {
+        let mut tables = responses.iter().filter_map(|response| match response {
+            PgResponse::Table(table) => Some(table),
+            PgResponse::CommandComplete(_) => None,
+        });
+        let table = tables.next()?;
+        if tables.next().is_some() {
+            return None;
+        }
+
+        if table.header.names().collect::<Vec<_>>() != [PLAN_COLUMN] || table.rows.len() != 1 {
+            return None;
+        }
+
+        let plan = match table.rows[0].values.first()? {
+            Value::Json(json) => Self::from_json(json.clone()),
+            // through the simple query protocol, plans in other formats are not JSON
+            Value::Text(text) => Self::from_json(serde_json::from_str(text).ok()?),
+            _ => return None,
+        };
+
+        Some(plan)
+    }
+
+    /// Reads the plan from the single element array returned by `EXPLAIN`
+    pub fn from_json(json: JsonValue) -> Result<Self> {
+        let mut plans: Vec<Plan> = serde_json::from_value(json)?;
+
+        match plans.len() {
+            1 => Ok(plans.remove(0)),
+            len => Err(anyhow!("Expected a single plan, got {}", len)),
+        }
+    }
+
+    pub fn is_analyzed(&self) -> bool {
+        self.root.actual_total_time.is_some()
+    }
+
+    /// Share of the whole plan spent in `node` itself, from 0 to 1: by actual time if the plan
+    /// was analyzed, by estimated cost otherwise
+    pub fn weight(&self, node: &PlanNode) -> f64 {
+        let (own, total) = match (node.exclusive_time(), self.root.total_time()) {
+            (Some(own), Some(total)) => (own, total),
+            _ => (node.exclusive_cost(), self.root.total_cost),
+        };
+
+        if total > 0.0 {
+            (own / total).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl PlanNode {
+    /// Short description, e.g. `Index Scan using orders_pkey on orders o`
+    pub fn title(&self) -> String {
+        let mut title = self.node_type.clone();
+
+        if let Some(join_type) = &self.join_type {
+            if join_type != "Inner" {
+                title = format!("{} {}", join_type, title);
+            }
+        }
+        if let Some(index) = &self.index {
+            title.push_str(&format!(" using {}", index));
+        }
+        if let Some(relation) = &self.relation {
+            title.push_str(&format!(" on {}", relation));
+
+            if let Some(alias) = self.alias.as_ref().filter(|alias| *alias != relation) {
+                title.push_str(&format!(" {}", alias));
+            }
+        }
+
+        title
+    }
+
+    /// Milliseconds spent in the node and its children over all loops
+    pub fn total_time(&self) -> Option<f64> {
+        Some(self.actual_total_time? * self.actual_loops.unwrap_or(1.0))
+    }
+
+    /// Milliseconds spent in the node, not counting its children
+    pub fn exclusive_time(&self) -> Option<f64> {
+        let children = self
+            .children
+            .iter()
+            .filter_map(PlanNode::total_time)
+            .sum::<f64>();
+
+        Some((self.total_time()? - children).max(0.0))
+    }
+
+    /// Estimated cost of the node, not counting its children
+    pub fn exclusive_cost(&self) -> f64 {
+        let children = self
+            .children
+            .iter()
+            .map(|child| child.total_cost)
+            .sum::<f64>();
+
+        (self.total_cost - children).max(0.0)
+    }
+
+    /// How many times the actual rows differ from the estimate, in either direction
+    pub fn misestimate(&self) -> Option<f64> {
+        let actual = self.actual_rows?.max(1.0);
+        let planned = self.plan_rows.max(1.0);
+
+        Some((actual / planned).max(planned / actual))
+    }
+
+    /// Buffer usage, e.g. `hit 120, read 4`, empty without `BUFFERS`
+    pub fn buffers(&self) -> String {
+        let counts = [
+            ("hit", self.shared_hit_blocks),
+            ("read", self.shared_read_blocks),
+            ("dirtied", self.shared_dirtied_blocks),
+            ("written", self.shared_written_blocks),
+            ("temp read", self.temp_read_blocks),
+            ("temp written", self.temp_written_blocks),
+        ];
+
+        counts
+            .iter()
+            .filter_map(|(label, count)| match count {
+                Some(count) if *count > 0 => Some(format!("{} {}", label, count)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Nodes of the subtree, depth first
+    pub fn descendants(&self) -> Vec<&PlanNode> {
+        let mut nodes = vec![self];
+        for child in &self.children {
+            nodes.extend(child.descendants());
+        }
+
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn analyzed_plan() -> Plan {
+        Plan::from_json(json!([{
+            "Plan": {
+                "Node Type": "Hash Join",
+                "Join Type": "Inner",
+                "Startup Cost": 10.0,
+                "Total Cost": 100.0,
+                "Plan Rows": 50,
+                "Actual Startup Time": 1.0,
+                "Actual Total Time": 20.0,
+                "Actual Rows": 500,
+                "Actual Loops": 1,
+                "Hash Cond": "(o.customer_id = c.id)",
+                "Plans": [
+                    {
+                        "Node Type": "Seq Scan",
+                        "Relation Name": "orders",
+                        "Alias": "o",
+                        "Startup Cost": 0.0,
+                        "Total Cost": 60.0,
+                        "Plan Rows": 1000,
+                        "Actual Startup Time": 0.1,
+                        "Actual Total Time": 12.0,
+                        "Actual Rows": 1000,
+                        "Actual Loops": 1,
+                        "Shared Hit Blocks": 30,
+                        "Shared Read Blocks": 2
+                    },
+                    {
+                        "Node Type": "Index Scan",
+                        "Index Name": "customers_pkey",
+                        "Relation Name": "customers",
+                        "Alias": "customers",
+                        "Startup Cost": 0.0,
+                        "Total Cost": 20.0,
+                        "Plan Rows": 1,
+                        "Actual Startup Time": 0.01,
+                        "Actual Total Time": 0.002,
+                        "Actual Rows": 1,
+                        "Actual Loops": 1000
+                    }
+                ]
+            },
+            "Planning Time": 0.2,
+            "Execution Time": 20.5
+        }]))
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_plan() {
+        let plan = analyzed_plan();
+
+        assert!(plan.is_analyzed());
+        assert_eq!(plan.execution_time, Some(20.5));
+        assert_eq!(plan.root.children.len(), 2);
+        assert_eq!(
+            plan.root.details.get("Hash Cond"),
+            Some(&json!("(o.customer_id = c.id)"))
+        );
+        assert_eq!(plan.root.descendants().len(), 3);
+
+        let titles = plan
+            .root
+            .descendants()
+            .iter()
+            .map(|node| node.title())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            titles,
+            vec![
+                "Hash Join",
+                "Seq Scan on orders o",
+                "Index Scan using customers_pkey on customers"
+            ]
+        );
+    }
+
+    #[test]
+    fn exclusive_figures() {
+        let plan = analyzed_plan();
+        let root = &plan.root;
+        let scan = &root.children[0];
+        let index_scan = &root.children[1];
+
+        // 20 ms in total, 12 ms in the seq scan, 2 ms in 1000 loops of the index scan
+        assert!((root.exclusive_time().unwrap() - 6.0).abs() < 1e-9);
+        assert!((index_scan.total_time().unwrap() - 2.0).abs() < 1e-9);
+        assert!((plan.weight(scan) - 0.6).abs() < 1e-9);
+
+        assert_eq!(root.exclusive_cost(), 20.0);
+        assert_eq!(root.misestimate(), Some(10.0));
+        assert_eq!(scan.buffers(), "hit 30, read 2");
+    }
+
+    #[test]
+    fn wrap_statement() {
+        assert_eq!(
+            ExplainMode::Analyze.wrap("delete from t ;\n", false),
+            "BEGIN;\nEXPLAIN (FORMAT JSON, ANALYZE, BUFFERS) delete from t;\nROLLBACK;"
+        );
+        assert_eq!(
+            ExplainMode::Analyze.wrap("delete from t", true),
+            "SAVEPOINT slonik_explain;\nEXPLAIN (FORMAT JSON, ANALYZE, BUFFERS) delete from t;\n\
+             ROLLBACK TO SAVEPOINT slonik_explain;"
+        );
+        assert_eq!(
+            ExplainMode::Plan.wrap("select 1", false),
+            "EXPLAIN (FORMAT JSON) select 1"
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use std::io::Write;
+
+use anyhow::Result;
+use serde_json::Value as JsonValue;
+
+use super::{
+    catalog::{quote_ident, quote_literal, quote_qualified},
+    pg_response::Table,
+    value::Value,
+};
+
+/// Rows written between progress reports
+pub const PROGRESS_STEP: usize = 1000;
+
+/// Options of the CSV format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub quote: u8,
+    /// Whether the first record names the columns
+    pub header: bool,
+    /// Written in place of nulls
+    pub null: String,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            header: true,
+            null: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv(CsvOptions),
+    /// Tab separated values, without quoting; tabs, newlines and backslashes are escaped
+    Tsv,
+    /// Array of objects keyed by column names
+    Json,
+    /// One object per line
+    JsonLines,
+    Markdown,
+    Html,
+    /// `INSERT` statement per row into the table
+    SqlInsert {
+        table: String,
+    },
+}
+
+impl ExportFormat {
+    /// Extension of files in the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv(_) => "csv",
+            ExportFormat::Tsv => "tsv",
+            ExportFormat::Json => "json",
+            ExportFormat::JsonLines => "jsonl",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::SqlInsert { .. } => "sql",
+        }
+    }
+}
+
+/// Writes `table` to `out` in `format`, `progress` is told the number of rows written so far
+/// every `PROGRESS_STEP` rows and once all are written
+pub fn export<W: Write>(
+    table: &Table,
+    format: &ExportFormat,
+    mut out: W,
+    progress: &mut dyn FnMut(usize),
+) -> Result<()> {
+    let names = table.header.names().collect::<Vec<_>>();
+
+    match format {
+        ExportFormat::Csv(options) => {
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(options.delimiter)
+                .quote(options.quote)
+                .from_writer(&mut out);
+
+            if options.header {
+                writer.write_record(&names)?;
+            }
+
+            for_each_row(table, progress, |values| {
+                let record = values.iter().map(|value| match value {
+                    Value::Null => options.null.clone(),
+                    value => value.to_string(),
+                });
+                Ok(writer.write_record(record)?)
+            })?;
+
+            writer.flush()?;
+        }
+        ExportFormat::Tsv => {
+            let header = names
+                .iter()
+                .map(|name| escape_tsv(name))
+                .collect::<Vec<_>>();
+            writeln!(out, "{}", header.join("\t"))?;
+
+            for_each_row(table, progress, |values| {
+                let fields = values
+                    .iter()
+                    .map(|value| match value {
+                        Value::Null => "\\N".to_string(),
+                        value => escape_tsv(&value.to_string()),
+                    })
+                    .collect::<Vec<_>>();
+                Ok(writeln!(out, "{}", fields.join("\t"))?)
+            })?;
+        }
+        ExportFormat::Json => {
+            out.write_all(b"[")?;
+
+            let mut first = true;
+            for_each_row(table, progress, |values| {
+                out.write_all(if first { b"\n  " } else { b",\n  " })?;
+                first = false;
+                Ok(out.write_all(json_object(&names, values).as_bytes())?)
+            })?;
+
+            out.write_all(b"\n]\n")?;
+        }
+        ExportFormat::JsonLines => {
+            for_each_row(table, progress, |values| {
+                Ok(writeln!(out, "{}", json_object(&names, values))?)
+            })?;
+        }
+        ExportFormat::Markdown => {
+            let header = names
+                .iter()
+                .map(|name| escape_markdown(name))
+                .collect::<Vec<_>>();
+            writeln!(out, "| {} |", header.join(" | "))?;
+            writeln!(out, "|{}", " --- |".repeat(names.len()))?;
+
+            for_each_row(table, progress, |values| {
+                let cells = values
+                    .iter()
+                    .map(|value| match value {
+                        Value::Null => String::new(),
+                        value => escape_markdown(&value.to_string()),
+                    })
+                    .collect::<Vec<_>>();
+                Ok(writeln!(out, "| {} |", cells.join(" | "))?)
+            })?;
+        }
+        ExportFormat::Html => {
+            writeln!(out, "<table>\n  <thead>\n    <tr>")?;
+            for name in &names {
+                writeln!(out, "      <th>{}</th>", escape_html(name))?;
+            }
+            writeln!(out, "    </tr>\n  </thead>\n  <tbody>")?;
+
+            for_each_row(table, progress, |values| {
+                writeln!(out, "    <tr>")?;
+                for value in values {
+                    match value {
+                        Value::Null => writeln!(out, "      <td></td>")?,
+                        value => {
+                            writeln!(out, "      <td>{}</td>", escape_html(&value.to_string()))?
+                        }
+                    }
+                }
+                Ok(writeln!(out, "    </tr>")?)
+            })?;
+
+            writeln!(out, "  </tbody>\n</table>")?;
+        }
+        ExportFormat::SqlInsert { table: target } => {
+            let target = quote_qualified(target);
+            let columns = names
+                .iter()
+                .map(|name| quote_ident(name))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            for_each_row(table, progress, |values| {
+                let literals = values.iter().map(sql_literal).collect::<Vec<_>>();
+                Ok(writeln!(
+                    out,
+                    "INSERT INTO {} ({}) VALUES ({});",
+                    target,
+                    columns,
+                    literals.join(", ")
+                )?)
+            })?;
+        }
+    }
+
+    out.flush()?;
+
+    Ok(())
+}
+
+fn for_each_row(
+    table: &Table,
+    progress: &mut dyn FnMut(usize),
+    mut write: impl FnMut(&[Value]) -> Result<()>,
+) -> Result<()> {
+    for (idx, row) in table.rows.iter().enumerate() {
+        write(&row.values)?;
+
+        if (idx + 1) % PROGRESS_STEP == 0 {
+            progress(idx + 1);
+        }
+    }
+
+    progress(table.rows.len());
+
+    Ok(())
+}
+
+/// Object of the row with keys in the order of columns
+fn json_object(names: &[&str], values: &[Value]) -> String {
+    let members = names
+        .iter()
+        .zip(values)
+        .map(|(name, value)| format!("{}:{}", JsonValue::from(*name), json_value(value)))
+        .collect::<Vec<_>>();
+
+    format!("{{{}}}", members.join(","))
+}
+
+/// Numbers which JSON cannot represent exactly, like `numeric` or infinite floats, become strings
+fn json_value(value: &Value) -> JsonValue {
+    match value {
+        Value::Null => JsonValue::Null,
+        Value::Bool(b) => JsonValue::Bool(*b),
+        Value::Int(i) => JsonValue::from(*i),
+        Value::Float(f) if f.is_finite() => JsonValue::from(*f),
+        Value::Json(json) => json.clone(),
+        Value::Array(values) => JsonValue::Array(values.iter().map(json_value).collect()),
+        value => JsonValue::String(value.to_string()),
+    }
+}
+
+fn sql_literal(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".into(),
+        Value::Bool(true) => "TRUE".into(),
+        Value::Bool(false) => "FALSE".into(),
+        Value::Int(i) => i.to_string(),
+        Value::Float(f) if f.is_finite() => f.to_string(),
+        Value::Numeric(n) if matches!(n.parse::<f64>(), Ok(n) if n.is_finite()) => n.clone(),
+        value => quote_literal(&value.to_string()),
+    }
+}
+
+fn escape_tsv(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::pg_response::{Header, Row};
+
+    fn table() -> Table {
+        let mut header = Header::default();
+        header.push("id");
+        header.push("Name");
+        header.push("note");
+
+        let rows = vec![
+            Row::new(vec![
+                Value::Int(1),
+                Value::Text("O'Brien".into()),
+                Value::Text("a|b\nc".into()),
+            ]),
+            Row::new(vec![
+                Value::Int(2),
+                Value::Null,
+                Value::Numeric("1.50".into()),
+            ]),
+        ];
+
+        Table::new(header, rows)
+    }
+
+    fn exported(format: ExportFormat) -> String {
+        let mut out = vec![];
+        let mut reported = vec![];
+        export(&table(), &format, &mut out, &mut |rows| reported.push(rows)).unwrap();
+
+        assert_eq!(reported, vec![2]);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_options() {
+        let options = CsvOptions {
+            delimiter: b';',
+            quote: b'\'',
+            header: false,
+            null: "NULL".into(),
+        };
+
+        assert_eq!(
+            exported(ExportFormat::Csv(options)),
+            "1;'O''Brien';'a|b\nc'\n2;NULL;1.50\n"
+        );
+    }
+
+    #[test]
+    fn tsv_escapes() {
+        assert_eq!(
+            exported(ExportFormat::Tsv),
+            "id\tName\tnote\n1\tO'Brien\ta|b\\nc\n2\t\\N\t1.50\n"
+        );
+    }
+
+    #[test]
+    fn json_objects() {
+        assert_eq!(
+            exported(ExportFormat::JsonLines),
+            "{\"id\":1,\"Name\":\"O'Brien\",\"note\":\"a|b\\nc\"}\n{\"id\":2,\"Name\":null,\"note\":\"1.50\"}\n"
+        );
+
+        let json: JsonValue = serde_json::from_str(&exported(ExportFormat::Json)).unwrap();
+        assert_eq!(json[1]["note"], JsonValue::from("1.50"));
+        assert_eq!(json.as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn markdown_table() {
+        assert_eq!(
+            exported(ExportFormat::Markdown),
+            "| id | Name | note |\n| --- | --- | --- |\n| 1 | O'Brien | a\\|b<br>c |\n| 2 |  | 1.50 |\n"
+        );
+    }
+
+    #[test]
+    fn insert_statements() {
+        let format = ExportFormat::SqlInsert {
+            table: "public.People".into(),
+        };
+
+        assert_eq!(
+            exported(format),
+            "INSERT INTO public.\"People\" (id, \"Name\", note) VALUES (1, 'O''Brien', 'a|b\nc');\n\
+             INSERT INTO public.\"People\" (id, \"Name\", note) VALUES (2, NULL, 1.50);\n"
+        );
+    }
+}
@@ -0,0 +1,661 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde_json::{Map, Value as JsonValue};
+
+use super::catalog::{quote_ident, quote_qualified};
+
+/// Records read for the preview and the type inference
+pub const PREVIEW_ROWS: usize = 100;
+/// Rows sent to the server in a single message, progress is reported after each
+pub const CHUNK_ROWS: usize = 1000;
+/// Rejected rows after which reading the file stops
+pub const MAX_ROW_ERRORS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// Delimited values, TSV being CSV delimited by tabs
+    Csv { delimiter: u8, header: bool },
+    /// JSON object per line, its keys name the columns
+    JsonLines,
+}
+
+impl ImportFormat {
+    /// Format suggested by the extension of `path`
+    pub fn guess(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+
+        match extension.as_deref() {
+            Some("tsv") | Some("tab") => ImportFormat::Csv {
+                delimiter: b'\t',
+                header: true,
+            },
+            Some("jsonl") | Some("ndjson") | Some("json") => ImportFormat::JsonLines,
+            _ => ImportFormat::Csv {
+                delimiter: b',',
+                header: true,
+            },
+        }
+    }
+}
+
+/// Record of a file, empty fields and JSON nulls are `None`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Line of the file the record starts at, from 1
+    pub line: u64,
+    pub values: Vec<Option<String>>,
+}
+
+/// Row which could not be read or was rejected by the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    pub line: u64,
+    pub message: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+enum Source {
+    Csv(csv::Reader<File>),
+    JsonLines {
+        reader: BufReader<File>,
+        /// Lines and bytes read so far
+        line: u64,
+        bytes: u64,
+    },
+}
+
+/// Reads the records of a file one by one.
+///
+/// Keys of JSON Lines files become columns as the records having them are read.
+pub struct RecordReader {
+    source: Source,
+    columns: Vec<String>,
+}
+
+impl RecordReader {
+    pub fn open(path: &Path, format: ImportFormat) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+
+        let reader = match format {
+            ImportFormat::Csv { delimiter, header } => {
+                let mut reader = csv::ReaderBuilder::new()
+                    .delimiter(delimiter)
+                    .has_headers(header)
+                    .flexible(true)
+                    .from_reader(file);
+
+                // without a header, the first record only tells the number of columns
+                let first = reader.headers()?;
+                let columns = if header {
+                    first.iter().map(str::to_string).collect()
+                } else {
+                    (1..=first.len()).map(|n| format!("column_{}", n)).collect()
+                };
+
+                Self {
+                    source: Source::Csv(reader),
+                    columns,
+                }
+            }
+            ImportFormat::JsonLines => {
+                let mut reader = BufReader::new(file);
+                let (line, bytes) = skip_blank(&mut reader)?;
+
+                // a `.json` file is just as likely to hold a single document
+                if reader.fill_buf()?.first() == Some(&b'[') {
+                    bail!(
+                        "{} holds a JSON array, only JSON Lines files with an object per line \
+                         can be imported",
+                        path.display()
+                    );
+                }
+
+                Self {
+                    source: Source::JsonLines {
+                        reader,
+                        line,
+                        bytes,
+                    },
+                    columns: vec![],
+                }
+            }
+        };
+
+        Ok(reader)
+    }
+
+    /// Names of the columns, generated for CSV files without a header
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Bytes of the file read so far
+    pub fn bytes_read(&self) -> u64 {
+        match &self.source {
+            Source::Csv(reader) => reader.position().byte(),
+            Source::JsonLines { bytes, .. } => *bytes,
+        }
+    }
+}
+
+impl Iterator for RecordReader {
+    type Item = Result<Record, RowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.source {
+            Source::Csv(reader) => {
+                let line = reader.position().line();
+                let mut record = csv::StringRecord::new();
+
+                match reader.read_record(&mut record) {
+                    Ok(false) => None,
+                    Ok(true) if record.len() != self.columns.len() => Some(Err(RowError {
+                        line: record.position().map_or(line, csv::Position::line),
+                        message: format!(
+                            "expected {} fields, found {}",
+                            self.columns.len(),
+                            record.len()
+                        ),
+                    })),
+                    Ok(true) => Some(Ok(Record {
+                        line: record.position().map_or(line, csv::Position::line),
+                        values: record
+                            .iter()
+                            .map(|field| Some(field.to_string()).filter(|f| !f.is_empty()))
+                            .collect(),
+                    })),
+                    Err(err) => Some(Err(RowError {
+                        line: err.position().map_or(line, csv::Position::line),
+                        message: err.to_string(),
+                    })),
+                }
+            }
+            Source::JsonLines {
+                reader,
+                line,
+                bytes,
+            } => loop {
+                let mut text = String::new();
+                let read = reader.read_line(&mut text);
+                *line += 1;
+
+                match read {
+                    Ok(0) => return None,
+                    Ok(read) => *bytes += read as u64,
+                    Err(err) => {
+                        return Some(Err(RowError {
+                            line: *line,
+                            message: err.to_string(),
+                        }))
+                    }
+                }
+
+                if text.trim().is_empty() {
+                    continue;
+                }
+
+                let record = match serde_json::from_str(&text) {
+                    Ok(JsonValue::Object(object)) => Ok(Record {
+                        line: *line,
+                        values: json_values(&mut self.columns, object),
+                    }),
+                    Ok(_) => Err(RowError {
+                        line: *line,
+                        message: "expected a JSON object".into(),
+                    }),
+                    Err(err) => Err(RowError {
+                        line: *line,
+                        message: err.to_string(),
+                    }),
+                };
+
+                return Some(record);
+            },
+        }
+    }
+}
+
+/// Skips the whitespace the file starts with, returns the lines and bytes skipped
+fn skip_blank(reader: &mut BufReader<File>) -> Result<(u64, u64)> {
+    let mut lines = 0;
+    let mut bytes = 0;
+
+    loop {
+        let buffer = reader.fill_buf()?;
+        let blank = buffer
+            .iter()
+            .take_while(|byte| byte.is_ascii_whitespace())
+            .count();
+        if blank == 0 {
+            return Ok((lines, bytes));
+        }
+
+        lines += buffer[..blank]
+            .iter()
+            .filter(|byte| **byte == b'\n')
+            .count() as u64;
+        bytes += blank as u64;
+        reader.consume(blank);
+    }
+}
+
+/// Values of `object` in the order of `columns`, keys met for the first time become new columns
+fn json_values(
+    columns: &mut Vec<String>,
+    mut object: Map<String, JsonValue>,
+) -> Vec<Option<String>> {
+    let mut values = columns
+        .iter()
+        .map(|column| object.remove(column).and_then(json_text))
+        .collect::<Vec<_>>();
+
+    for (key, value) in object {
+        columns.push(key);
+        values.push(json_text(value));
+    }
+
+    values
+}
+
+/// Strings are taken as they are, nested objects and arrays as JSON text
+fn json_text(value: JsonValue) -> Option<String> {
+    match value {
+        JsonValue::Null => None,
+        JsonValue::String(text) => Some(text),
+        value => Some(value.to_string()),
+    }
+}
+
+/// Beginning of a file, read to set up its import
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preview {
+    pub columns: Vec<String>,
+    /// Types inferred from the previewed records, one per column
+    pub types: Vec<&'static str>,
+    pub records: Vec<Record>,
+    /// Rows of the preview which could not be read
+    pub errors: Vec<RowError>,
+}
+
+/// Reads up to `PREVIEW_ROWS` rows of `path`
+pub fn preview(path: &Path, format: ImportFormat) -> Result<Preview> {
+    let mut reader = RecordReader::open(path, format)?;
+
+    let mut records = vec![];
+    let mut errors = vec![];
+    for result in reader.by_ref().take(PREVIEW_ROWS) {
+        match result {
+            Ok(record) => records.push(record),
+            Err(err) => errors.push(err),
+        }
+    }
+
+    let columns = reader.columns().to_vec();
+    if columns.is_empty() {
+        bail!("{} holds no columns", path.display());
+    }
+
+    let types = (0..columns.len())
+        .map(|idx| {
+            let values = records
+                .iter()
+                .filter_map(|record| record.values.get(idx)?.as_deref())
+                .collect::<Vec<_>>();
+            infer_type(&values)
+        })
+        .collect();
+
+    Ok(Preview {
+        columns,
+        types,
+        records,
+        errors,
+    })
+}
+
+/// Narrowest of the common types accepting all of `values`, `text` if there are none
+pub fn infer_type(values: &[&str]) -> &'static str {
+    const CANDIDATES: [&str; 6] = [
+        "boolean",
+        "bigint",
+        "numeric",
+        "date",
+        "timestamp",
+        "timestamptz",
+    ];
+
+    if values.is_empty() {
+        return "text";
+    }
+
+    CANDIDATES
+        .iter()
+        .find(|data_type| values.iter().all(|value| accepts(data_type, value)))
+        .unwrap_or(&"text")
+}
+
+/// Whether `value` is a literal of `data_type`, as far as the inference goes
+fn accepts(data_type: &str, value: &str) -> bool {
+    match data_type {
+        "boolean" => ["true", "false", "t", "f"]
+            .iter()
+            .any(|literal| value.eq_ignore_ascii_case(literal)),
+        "bigint" => value.parse::<i64>().is_ok(),
+        "numeric" => matches!(value.parse::<f64>(), Ok(n) if n.is_finite()),
+        "date" => NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+        "timestamp" => ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+            .iter()
+            .any(|format| NaiveDateTime::parse_from_str(value, format).is_ok()),
+        // with an offset, as in RFC 3339 or in the output of the server
+        "timestamptz" => {
+            DateTime::parse_from_rfc3339(value).is_ok()
+                || DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f%#z").is_ok()
+        }
+        _ => true,
+    }
+}
+
+/// Column of the file copied into a column of the target table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMapping {
+    /// Index of the file column
+    pub source: usize,
+    pub target: String,
+    /// Type of the column when the table is created
+    pub data_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportRequest {
+    pub path: PathBuf,
+    pub format: ImportFormat,
+    /// Possibly schema qualified name of the target table
+    pub table: String,
+    pub columns: Vec<ColumnMapping>,
+    /// Whether the table is created before copying into it
+    pub create_table: bool,
+    /// Rows are copied and checked by the server, then rolled back
+    pub dry_run: bool,
+}
+
+impl ImportRequest {
+    pub fn create_table_sql(&self) -> String {
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| format!("{} {}", quote_ident(&column.target), column.data_type))
+            .collect::<Vec<_>>();
+
+        format!(
+            "CREATE TABLE {} (\n    {}\n)",
+            quote_qualified(&self.table),
+            columns.join(",\n    ")
+        )
+    }
+
+    pub fn copy_sql(&self) -> String {
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| quote_ident(&column.target))
+            .collect::<Vec<_>>();
+
+        format!(
+            "COPY {} ({}) FROM STDIN WITH (FORMAT csv)",
+            quote_qualified(&self.table),
+            columns.join(", ")
+        )
+    }
+
+    /// Appends the mapped values of `record` to `out` as a line of the CSV read by `COPY`.
+    ///
+    /// Values are always quoted, so that only missing ones are taken as nulls.
+    pub fn encode(&self, record: &Record, out: &mut Vec<u8>) {
+        for (idx, column) in self.columns.iter().enumerate() {
+            if idx > 0 {
+                out.push(b',');
+            }
+
+            if let Some(value) = record.values.get(column.source).and_then(Option::as_deref) {
+                out.push(b'"');
+                out.extend_from_slice(value.replace('"', "\"\"").as_bytes());
+                out.push(b'"');
+            }
+        }
+
+        out.push(b'\n');
+    }
+}
+
+/// Line of the data sent to `COPY` and the column its error context names, e.g.
+/// `COPY people, line 3, column age: "x"`
+pub fn copy_error_position(context: &str) -> Option<(u64, Option<&str>)> {
+    const LINE: &str = ", line ";
+    const COLUMN: &str = ", column ";
+
+    let rest = &context[context.find(LINE)? + LINE.len()..];
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let line = rest[..digits].parse().ok()?;
+
+    let column = rest[digits..]
+        .strip_prefix(COLUMN)
+        .and_then(|rest| rest.split(':').next());
+
+    Some((line, column))
+}
+
+/// Outcome of an import which reached the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Rows copied, or which would have been copied by a dry run
+    pub rows: u64,
+    pub dry_run: bool,
+    /// Rejected rows, nothing is committed when there are any
+    pub errors: Vec<RowError>,
+}
+
+impl ImportReport {
+    pub fn committed(&self) -> bool {
+        !self.dry_run && self.errors.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+    use crate::util::TempDir;
+
+    fn file(dir: &TempDir, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        File::create(&path)
+            .unwrap()
+            .write_all(content.as_bytes())
+            .unwrap();
+        path
+    }
+
+    fn values(values: &[Option<&str>]) -> Vec<Option<String>> {
+        values.iter().map(|value| value.map(Into::into)).collect()
+    }
+
+    #[test]
+    fn csv_records() {
+        let dir = TempDir::new("import");
+        let path = file(
+            &dir,
+            "people.csv",
+            "id,name,born\n1,\"Smith, \"\"J\"\"\",1990-01-02\n2,,1985-12-31\n3,short\n",
+        );
+        let preview = preview(&path, ImportFormat::guess(&path)).unwrap();
+
+        assert_eq!(preview.columns, vec!["id", "name", "born"]);
+        assert_eq!(preview.types, vec!["bigint", "text", "date"]);
+        assert_eq!(
+            preview.records,
+            vec![
+                Record {
+                    line: 2,
+                    values: values(&[Some("1"), Some("Smith, \"J\""), Some("1990-01-02")]),
+                },
+                Record {
+                    line: 3,
+                    values: values(&[Some("2"), None, Some("1985-12-31")]),
+                },
+            ]
+        );
+        assert_eq!(
+            preview.errors,
+            vec![RowError {
+                line: 4,
+                message: "expected 3 fields, found 2".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn headerless_tsv() {
+        let dir = TempDir::new("import");
+        let path = file(&dir, "values.tsv", "1\t2.5\n2\t3\n");
+        let format = ImportFormat::Csv {
+            delimiter: b'\t',
+            header: false,
+        };
+        let preview = preview(&path, format).unwrap();
+
+        assert_eq!(preview.columns, vec!["column_1", "column_2"]);
+        assert_eq!(preview.types, vec!["bigint", "numeric"]);
+        assert_eq!(preview.records.len(), 2);
+    }
+
+    #[test]
+    fn json_lines_records() {
+        let dir = TempDir::new("import");
+        let path = file(
+            &dir,
+            "events.jsonl",
+            "\n {\"id\": 1, \"ok\": true}\n\n{\"ok\": false, \"tags\": [\"a\"], \"id\": null}\n[1]\n",
+        );
+        let preview = preview(&path, ImportFormat::guess(&path)).unwrap();
+
+        assert_eq!(preview.columns, vec!["id", "ok", "tags"]);
+        assert_eq!(preview.types, vec!["bigint", "boolean", "text"]);
+        assert_eq!(
+            preview.records[1],
+            Record {
+                line: 4,
+                values: values(&[None, Some("false"), Some("[\"a\"]")]),
+            }
+        );
+        assert_eq!(preview.errors[0].line, 5);
+    }
+
+    #[test]
+    fn json_array_rejected() {
+        let dir = TempDir::new("import");
+        let path = file(&dir, "events.json", "\n[{\"id\": 1},\n{\"id\": 2}]\n");
+        let err = preview(&path, ImportFormat::guess(&path)).unwrap_err();
+
+        assert!(err.to_string().contains("holds a JSON array"));
+    }
+
+    #[test]
+    fn inferred_types() {
+        assert_eq!(infer_type(&["t", "FALSE"]), "boolean");
+        assert_eq!(infer_type(&["1", "-20"]), "bigint");
+        assert_eq!(infer_type(&["1", "2.5e3"]), "numeric");
+        assert_eq!(infer_type(&["1", "NaN"]), "text");
+        assert_eq!(
+            infer_type(&["2021-03-04 05:06:07", "2021-03-04T05:06:07.123"]),
+            "timestamp"
+        );
+        assert_eq!(
+            infer_type(&["2021-03-04T05:06:07Z", "2021-03-04 05:06:07+02"]),
+            "timestamptz"
+        );
+        assert_eq!(infer_type(&[]), "text");
+    }
+
+    #[test]
+    fn request_statements() {
+        let request = ImportRequest {
+            path: PathBuf::from("people.csv"),
+            format: ImportFormat::JsonLines,
+            table: "crm.People".into(),
+            columns: vec![
+                ColumnMapping {
+                    source: 2,
+                    target: "name".into(),
+                    data_type: "text".into(),
+                },
+                ColumnMapping {
+                    source: 0,
+                    target: "Id".into(),
+                    data_type: "bigint".into(),
+                },
+            ],
+            create_table: true,
+            dry_run: false,
+        };
+
+        assert_eq!(
+            request.create_table_sql(),
+            "CREATE TABLE crm.\"People\" (\n    name text,\n    \"Id\" bigint\n)"
+        );
+        assert_eq!(
+            request.copy_sql(),
+            "COPY crm.\"People\" (name, \"Id\") FROM STDIN WITH (FORMAT csv)"
+        );
+
+        let mut out = vec![];
+        let record = Record {
+            line: 1,
+            values: values(&[Some("7"), Some("x"), Some("a \"b\",\nc")]),
+        };
+        request.encode(&record, &mut out);
+        request.encode(
+            &Record {
+                line: 2,
+                values: values(&[None]),
+            },
+            &mut out,
+        );
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\"a \"\"b\"\",\nc\",\"7\"\n,\n"
+        );
+    }
+
+    #[test]
+    fn copy_error_context() {
+        assert_eq!(
+            copy_error_position("COPY people, line 3, column age: \"x\""),
+            Some((3, Some("age")))
+        );
+        assert_eq!(
+            copy_error_position("COPY people, line 12: \"1,2\""),
+            Some((12, None))
+        );
+        assert_eq!(copy_error_position("SQL function \"f\""), None);
+    }
+}
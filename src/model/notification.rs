@@ -0,0 +1,99 @@
+use chrono::{DateTime, Local};
+
+use super::catalog::quote_ident;
+
+/// Message received on a `LISTEN`ed channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub received_at: DateTime<Local>,
+    /// Backend which sent the message
+    pub process_id: i32,
+    pub channel: String,
+    pub payload: String,
+}
+
+impl Notification {
+    pub fn new(notification: &tokio_postgres::Notification) -> Self {
+        Self {
+            received_at: Local::now(),
+            process_id: notification.process_id(),
+            channel: notification.channel().to_string(),
+            payload: notification.payload().to_string(),
+        }
+    }
+
+    /// Log line of the message, JSON payloads are indented on the lines that follow
+    pub fn format(&self) -> String {
+        let header = format!(
+            "{} [{}] pid {}",
+            self.received_at.format("%H:%M:%S%.3f"),
+            self.channel,
+            self.process_id
+        );
+
+        match pretty_json(&self.payload) {
+            Some(json) => format!("{}\n{}\n", header, json),
+            None if self.payload.is_empty() => format!("{}\n", header),
+            None => format!("{} {}\n", header, self.payload),
+        }
+    }
+}
+
+/// Indented form of JSON objects and arrays, other payloads are shown as they are
+fn pretty_json(payload: &str) -> Option<String> {
+    if !payload.trim_start().starts_with(['{', '[']) {
+        return None;
+    }
+
+    let value = serde_json::from_str::<serde_json::Value>(payload).ok()?;
+    serde_json::to_string_pretty(&value).ok()
+}
+
+/// Channel names are taken as they are typed, so they are quoted unless already lower case
+pub fn listen_sql(channel: &str) -> String {
+    format!("LISTEN {}", quote_ident(channel))
+}
+
+pub fn unlisten_sql(channel: &str) -> String {
+    format!("UNLISTEN {}", quote_ident(channel))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn notification(payload: &str) -> Notification {
+        Notification {
+            received_at: Local::now(),
+            process_id: 42,
+            channel: "orders".into(),
+            payload: payload.into(),
+        }
+    }
+
+    #[test]
+    fn formats_payloads() {
+        assert!(notification("shipped")
+            .format()
+            .ends_with("[orders] pid 42 shipped\n"));
+
+        assert!(notification("").format().ends_with("pid 42\n"));
+
+        assert!(notification(r#"{"id":1,"tags":["a"]}"#)
+            .format()
+            .ends_with("pid 42\n{\n  \"id\": 1,\n  \"tags\": [\n    \"a\"\n  ]\n}\n"));
+
+        // only objects and arrays are expanded, broken JSON is shown as sent
+        assert!(notification("12").format().ends_with("pid 42 12\n"));
+        assert!(notification("{broken")
+            .format()
+            .ends_with("pid 42 {broken\n"));
+    }
+
+    #[test]
+    fn quotes_channels() {
+        assert_eq!(listen_sql("orders"), "LISTEN orders");
+        assert_eq!(listen_sql("Orders"), "LISTEN \"Orders\"");
+        assert_eq!(unlisten_sql("order events"), "UNLISTEN \"order events\"");
+    }
+}
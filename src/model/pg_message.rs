@@ -0,0 +1,209 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Local};
+use tokio_postgres::error::{self, DbError};
+
+/// Level of a message sent by the server outside of an error, ordered by importance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Debug,
+    Log,
+    Info,
+    Notice,
+    Warning,
+}
+
+impl Severity {
+    /// Level of `notice`, servers before 9.6 send the severity in the language of
+    /// `lc_messages` only
+    pub fn of(notice: &DbError) -> Self {
+        match notice.parsed_severity() {
+            Some(severity) => severity.into(),
+            None => Self::parse(notice.severity()),
+        }
+    }
+
+    /// Reads the severity as the server names it in English, other names are taken for notices
+    pub fn parse(severity: &str) -> Self {
+        match severity {
+            "WARNING" => Severity::Warning,
+            "INFO" => Severity::Info,
+            "LOG" => Severity::Log,
+            debug if debug.starts_with("DEBUG") => Severity::Debug,
+            _ => Severity::Notice,
+        }
+    }
+}
+
+impl From<error::Severity> for Severity {
+    fn from(severity: error::Severity) -> Self {
+        match severity {
+            error::Severity::Warning => Severity::Warning,
+            error::Severity::Info => Severity::Info,
+            error::Severity::Log => Severity::Log,
+            error::Severity::Debug => Severity::Debug,
+            // notices, errors are not sent as messages
+            _ => Severity::Notice,
+        }
+    }
+}
+
+/// Notice, warning or other message the server sent a session, e.g. by `RAISE NOTICE`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerMessage {
+    pub received_at: DateTime<Local>,
+    /// Session the message was sent to, as shown in the log
+    pub session: String,
+    /// Statement the session ran last, which most likely produced the message
+    pub query: Option<String>,
+    pub level: Severity,
+    /// Severity as reported by the server
+    pub severity: String,
+    /// SQLSTATE code
+    pub code: String,
+    pub message: String,
+    pub detail: Option<String>,
+    pub hint: Option<String>,
+}
+
+impl ServerMessage {
+    pub fn new(session: String, query: Option<String>, notice: &DbError) -> Self {
+        Self {
+            received_at: Local::now(),
+            session,
+            query,
+            level: Severity::of(notice),
+            severity: notice.severity().to_string(),
+            code: notice.code().code().to_string(),
+            message: notice.message().to_string(),
+            detail: notice.detail().map(String::from),
+            hint: notice.hint().map(String::from),
+        }
+    }
+
+    /// Log entry of the message, optional fields and the statement follow on indented lines
+    pub fn format(&self) -> String {
+        let mut text = format!(
+            "{} {} {} [{}] {}\n",
+            self.received_at.format("%H:%M:%S%.3f"),
+            self.severity,
+            self.code,
+            self.session,
+            self.message
+        );
+
+        let query = self
+            .query
+            .as_deref()
+            .and_then(|query| query.trim().lines().next());
+        let fields = [
+            ("DETAIL", self.detail.as_deref()),
+            ("HINT", self.hint.as_deref()),
+            ("QUERY", query),
+        ];
+        for (label, value) in fields.iter() {
+            if let Some(value) = value {
+                text.push_str(&format!("    {}: {}\n", label, value));
+            }
+        }
+
+        text
+    }
+}
+
+/// Messages of the log, the oldest ones are dropped once `capacity` is reached
+#[derive(Debug)]
+pub struct MessageLog {
+    messages: VecDeque<ServerMessage>,
+    capacity: usize,
+}
+
+impl MessageLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            messages: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Appends `message`, returning the message dropped to make room for it
+    pub fn push(&mut self, message: ServerMessage) -> Option<ServerMessage> {
+        let dropped = if self.messages.len() >= self.capacity {
+            self.messages.pop_front()
+        } else {
+            None
+        };
+        self.messages.push_back(message);
+
+        dropped
+    }
+
+    pub fn clear(&mut self) {
+        self.messages.clear();
+    }
+
+    pub fn messages(&self) -> impl Iterator<Item = &ServerMessage> {
+        self.messages.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn message(text: &str) -> ServerMessage {
+        ServerMessage {
+            received_at: Local::now(),
+            session: "session 1".into(),
+            query: None,
+            level: Severity::Notice,
+            severity: "NOTICE".into(),
+            code: "00000".into(),
+            message: text.into(),
+            detail: None,
+            hint: None,
+        }
+    }
+
+    #[test]
+    fn parses_severities() {
+        assert_eq!(Severity::parse("WARNING"), Severity::Warning);
+        assert_eq!(Severity::parse("DEBUG2"), Severity::Debug);
+        assert_eq!(Severity::parse("NOTICE"), Severity::Notice);
+        // the non-localized severity does not depend on `lc_messages`
+        assert_eq!(Severity::from(error::Severity::Warning), Severity::Warning);
+        assert_eq!(Severity::from(error::Severity::Log), Severity::Log);
+        assert_eq!(Severity::from(error::Severity::Notice), Severity::Notice);
+        assert!(Severity::Warning > Severity::Notice);
+    }
+
+    #[test]
+    fn formats_fields() {
+        assert!(message("done")
+            .format()
+            .ends_with(" NOTICE 00000 [session 1] done\n"));
+
+        let message = ServerMessage {
+            hint: Some("Try again.".into()),
+            query: Some("\n  DO $$ BEGIN\n RAISE NOTICE 'done'; END $$".into()),
+            ..message("done")
+        };
+        assert!(message
+            .format()
+            .ends_with("done\n    HINT: Try again.\n    QUERY: DO $$ BEGIN\n"));
+    }
+
+    #[test]
+    fn drops_oldest_messages() {
+        let mut log = MessageLog::new(2);
+        assert_eq!(log.push(message("1")), None);
+        assert_eq!(log.push(message("2")), None);
+        assert_eq!(log.push(message("3")).map(|m| m.message), Some("1".into()));
+
+        let texts = log
+            .messages()
+            .map(|m| m.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["2", "3"]);
+    }
+}
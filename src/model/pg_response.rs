@@ -1,43 +1,136 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use tokio_postgres::SimpleQueryMessage;
+use tokio_postgres::{types::Type, Column, SimpleQueryMessage};
+
+use super::value::Value;
+
+/// Timing of a batch of responses, those of a query or a page of rows fetched later, as seen by
+/// the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QueryStats {
+    /// Until the first row arrived, or the whole request if it returned none: planning and
+    /// execution on the server together with the round trip
+    pub first_row: Duration,
+    /// Receiving and decoding rows after the first one arrived, `None` when all rows arrived at
+    /// once, as with the simple query protocol
+    pub transfer: Option<Duration>,
+    /// Rows returned or affected by the last statement of the batch
+    pub rows: Option<u64>,
+}
+
+/// Result column description
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ColumnMeta {
+    pub name: String,
+    pub type_name: String,
+    pub type_oid: u32,
+    /// Type specific modifier, e.g. length of `varchar(n)`, `-1` when not applicable
+    pub type_modifier: i32,
+    /// Table the column originates from, `None` for computed columns
+    pub table_oid: Option<u32>,
+    /// Attribute number of the column within `table_oid`
+    pub column_id: Option<i16>,
+}
+
+impl ColumnMeta {
+    /// Column without type information, as reported by the simple query protocol
+    pub fn untyped<T: Into<String>>(name: T) -> Self {
+        Self {
+            name: name.into(),
+            type_name: Type::TEXT.name().to_string(),
+            type_oid: Type::TEXT.oid(),
+            type_modifier: -1,
+            table_oid: None,
+            column_id: None,
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            Type::from_oid(self.type_oid),
+            Some(Type::INT2)
+                | Some(Type::INT4)
+                | Some(Type::INT8)
+                | Some(Type::OID)
+                | Some(Type::FLOAT4)
+                | Some(Type::FLOAT8)
+                | Some(Type::NUMERIC)
+        )
+    }
+
+    /// Type name including its modifier, e.g. `varchar(32)` or `numeric(10,2)`
+    pub fn type_display(&self) -> String {
+        // typmods of these types carry the 4 byte varlena header
+        let modifier = self.type_modifier - 4;
+
+        match Type::from_oid(self.type_oid) {
+            Some(Type::VARCHAR) | Some(Type::BPCHAR) if modifier >= 0 => {
+                format!("{}({})", self.type_name, modifier)
+            }
+            Some(Type::NUMERIC) if modifier >= 0 => format!(
+                "{}({},{})",
+                self.type_name,
+                (modifier >> 16) & 0xffff,
+                modifier & 0xffff
+            ),
+            _ => self.type_name.clone(),
+        }
+    }
+}
+
+impl From<&Column> for ColumnMeta {
+    fn from(column: &Column) -> Self {
+        Self {
+            name: column.name().to_string(),
+            type_name: column.type_().name().to_string(),
+            type_oid: column.type_().oid(),
+            type_modifier: column.type_modifier(),
+            table_oid: column.table_oid(),
+            column_id: column.column_id(),
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Header {
-    pub columns: Vec<String>,
+    pub columns: Vec<ColumnMeta>,
 }
 
 impl Header {
-    pub fn new(columns: Vec<String>) -> Self {
+    pub fn new(columns: Vec<ColumnMeta>) -> Self {
         Self { columns }
     }
 
     pub fn push<T: Into<String>>(&mut self, col: T) {
-        self.columns.push(col.into());
+        self.columns.push(ColumnMeta::untyped(col));
     }
 
     /// Set the header's columns.
-    pub fn set_columns(&mut self, columns: Vec<String>) {
+    pub fn set_columns(&mut self, columns: Vec<ColumnMeta>) {
         self.columns = columns;
     }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.columns.iter().map(|col| col.name.as_str())
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Row {
-    pub values: Vec<Option<String>>,
+    pub values: Vec<Value>,
 }
 
 impl Row {
-    pub fn new(values: Vec<Option<String>>) -> Self {
+    pub fn new(values: Vec<Value>) -> Self {
         Self { values }
     }
 
-    pub fn push<T: Into<String>>(&mut self, value: Option<T>) {
-        self.values.push(value.map(|x| x.into()));
+    pub fn push<T: Into<Value>>(&mut self, value: T) {
+        self.values.push(value.into());
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Table {
     pub header: Header,
     pub rows: Vec<Row>,
@@ -49,66 +142,125 @@ impl Table {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum PgResponse {
     Table(Table),
     CommandComplete(u64),
 }
 
+/// Decodes rows received with the extended query protocol
+pub fn decode_rows(rows: &[tokio_postgres::Row]) -> Result<Vec<Row>, tokio_postgres::Error> {
+    rows.iter()
+        .map(|row| {
+            (0..row.len())
+                .map(|idx| row.try_get::<_, Value>(idx))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Row::new)
+        })
+        .collect()
+}
+
+/// Decodes rows of a statement described by `columns` which were received in text format,
+/// with the simple query protocol
+pub fn decode_text_rows(columns: &[Column], messages: &[SimpleQueryMessage]) -> Vec<Row> {
+    messages
+        .iter()
+        .filter_map(|message| match message {
+            SimpleQueryMessage::Row(row) => Some(row),
+            _ => None,
+        })
+        .map(|row| {
+            let values = columns
+                .iter()
+                .enumerate()
+                .map(|(idx, column)| match row.get(idx) {
+                    Some(text) => Value::from_text(column.type_(), text),
+                    None => Value::Null,
+                })
+                .collect();
+
+            Row::new(values)
+        })
+        .collect()
+}
+
+/// Whether all values of `columns` can be decoded from the binary format, see
+/// `Value::decodes_binary`
+pub fn decodes_binary(columns: &[Column]) -> bool {
+    columns
+        .iter()
+        .all(|column| Value::decodes_binary(column.type_()))
+}
+
 impl PgResponse {
+    /// Builds responses of a single statement from its decoded rows
+    pub fn from_rows(
+        columns: &[Column],
+        rows: Vec<Row>,
+        rows_affected: Option<u64>,
+    ) -> Vec<PgResponse> {
+        let mut responses = vec![];
+
+        if !columns.is_empty() {
+            let header = Header::new(columns.iter().map(ColumnMeta::from).collect());
+            responses.push(PgResponse::Table(Table::new(header, rows)));
+        }
+
+        if let Some(rows_affected) = rows_affected {
+            responses.push(PgResponse::CommandComplete(rows_affected));
+        }
+
+        responses
+    }
+
+    /// Builds responses of a script executed with the simple query protocol, values are
+    /// reported as text
     pub fn process_batches(batches: Vec<SimpleQueryMessage>) -> Vec<PgResponse> {
         let instant = Instant::now();
         let mut responses = vec![];
 
         let mut table: Option<Table> = None;
-        let mut prev_columns: Vec<String> = vec![];
         for batch in batches {
             match batch {
+                SimpleQueryMessage::RowDescription(columns) => {
+                    if let Some(ready_table) = table.take() {
+                        responses.push(PgResponse::Table(ready_table));
+                    }
+
+                    let header = Header::new(
+                        columns
+                            .iter()
+                            .map(|col| ColumnMeta::untyped(col.name()))
+                            .collect(),
+                    );
+                    table = Some(Table::new(header, vec![]));
+                }
                 SimpleQueryMessage::Row(row) => {
                     let current_table = table.get_or_insert_with(|| {
-                        let mut table = Table::default();
-                        let column_names = row
-                            .columns()
-                            .iter()
-                            .map(|col| col.name().to_string())
-                            .collect::<Vec<String>>();
-
-                        prev_columns = column_names.clone();
-                        table.header.columns = column_names;
+                        let header = Header::new(
+                            row.columns()
+                                .iter()
+                                .map(|col| ColumnMeta::untyped(col.name()))
+                                .collect(),
+                        );
 
-                        table
+                        Table::new(header, vec![])
                     });
 
-                    let current_columns = row
-                        .columns()
-                        .iter()
-                        .map(|c| c.name().to_string())
+                    let row_values = (0..row.len())
+                        .map(|idx| Value::from(row.get(idx)))
                         .collect::<Vec<_>>();
 
-                    let row_values = (0..current_columns.len())
-                        .map(|idx| row.get(idx).map(|x| x.to_string()))
-                        .collect::<Vec<_>>();
-
-                    if current_columns == prev_columns {
-                        current_table.rows.push(Row::new(row_values));
-                    } else {
-                        let ready_table = table.take().unwrap();
-                        responses.push(PgResponse::Table(ready_table));
-
-                        let header = Header::new(current_columns.clone());
-                        table = Some(Table::new(header, vec![Row::new(row_values)]));
-                        prev_columns = current_columns;
-                    }
+                    current_table.rows.push(Row::new(row_values));
                 }
                 SimpleQueryMessage::CommandComplete(rows_affected) => {
-                    if table.is_some() {
-                        let ready_table = table.take().unwrap();
-                        prev_columns = vec![];
+                    if let Some(ready_table) = table.take() {
                         responses.push(PgResponse::Table(ready_table));
                     }
                     responses.push(PgResponse::CommandComplete(rows_affected))
                 }
-                _ => unreachable!(),
+                // messages added to the protocol later carry nothing to show
+                _ => {}
             }
         }
 
@@ -120,6 +272,23 @@ impl PgResponse {
 
         responses
     }
+
+    /// Last table among `responses`
+    pub fn last_table(responses: &[PgResponse]) -> Option<&Table> {
+        responses.iter().rev().find_map(|response| match response {
+            PgResponse::Table(table) => Some(table),
+            PgResponse::CommandComplete(_) => None,
+        })
+    }
+
+    /// Rows returned or affected by the last statement of `responses`, a table still being
+    /// fetched counts the rows received so far
+    pub fn row_count(responses: &[PgResponse]) -> Option<u64> {
+        match responses.last()? {
+            PgResponse::CommandComplete(rows) => Some(*rows),
+            PgResponse::Table(table) => Some(table.rows.len() as u64),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -163,4 +332,23 @@ mod test {
         //let pretty = tableas_pretty();
         //pretty.printstd();
     }
+
+    #[test]
+    fn column_type_display() {
+        let column = |ty: Type, type_modifier: i32| ColumnMeta {
+            type_name: ty.name().to_string(),
+            type_oid: ty.oid(),
+            type_modifier,
+            ..ColumnMeta::untyped("col")
+        };
+
+        assert_eq!(column(Type::VARCHAR, 36).type_display(), "varchar(32)");
+        assert_eq!(column(Type::VARCHAR, -1).type_display(), "varchar");
+        assert_eq!(
+            column(Type::NUMERIC, (10 << 16 | 2) + 4).type_display(),
+            "numeric(10,2)"
+        );
+        assert_eq!(column(Type::INT4, -1).type_display(), "int4");
+        assert!(column(Type::NUMERIC, -1).is_numeric());
+    }
 }
@@ -0,0 +1,133 @@
+use std::ops::Range;
+
+use tokio_postgres::error::{DbError, ErrorPosition};
+
+/// Error reported by the server for a query, with everything `DbError` tells about it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryError {
+    pub severity: String,
+    /// SQLSTATE code
+    pub code: String,
+    pub message: String,
+    pub detail: Option<String>,
+    pub hint: Option<String>,
+    /// Call stack of the error, e.g. within a function
+    pub where_: Option<String>,
+    pub schema: Option<String>,
+    pub table: Option<String>,
+    pub column: Option<String>,
+    pub datatype: Option<String>,
+    pub constraint: Option<String>,
+    /// Position in the query as sent, counted in characters starting at 1
+    pub position: Option<u32>,
+    /// Query generated by the server, e.g. by a function, with the position of the error in it
+    pub internal_query: Option<(u32, String)>,
+}
+
+impl From<&DbError> for QueryError {
+    fn from(err: &DbError) -> Self {
+        let (position, internal_query) = match err.position() {
+            Some(ErrorPosition::Original(position)) => (Some(*position), None),
+            Some(ErrorPosition::Internal { position, query }) => {
+                (None, Some((*position, query.clone())))
+            }
+            None => (None, None),
+        };
+
+        Self {
+            severity: err.severity().to_string(),
+            code: err.code().code().to_string(),
+            message: err.message().to_string(),
+            detail: err.detail().map(String::from),
+            hint: err.hint().map(String::from),
+            where_: err.where_().map(String::from),
+            schema: err.schema().map(String::from),
+            table: err.table().map(String::from),
+            column: err.column().map(String::from),
+            datatype: err.datatype().map(String::from),
+            constraint: err.constraint().map(String::from),
+            position,
+            internal_query,
+        }
+    }
+}
+
+impl QueryError {
+    /// Optional fields which are present, labeled, in the order they are shown
+    pub fn fields(&self) -> Vec<(&'static str, &str)> {
+        let fields = [
+            ("Detail", &self.detail),
+            ("Hint", &self.hint),
+            ("Where", &self.where_),
+            ("Schema", &self.schema),
+            ("Table", &self.table),
+            ("Column", &self.column),
+            ("Data type", &self.datatype),
+            ("Constraint", &self.constraint),
+        ];
+
+        let mut fields = fields
+            .iter()
+            .filter_map(|(label, value)| Some((*label, value.as_deref()?)))
+            .collect::<Vec<_>>();
+
+        if let Some((_, query)) = &self.internal_query {
+            fields.push(("Internal query", query));
+        }
+
+        fields
+    }
+}
+
+/// Characters of `query` an error points at: the token starting at character `position`,
+/// counted from 1
+pub fn token_range(query: &str, position: u32) -> Option<Range<usize>> {
+    let start = (position as usize).checked_sub(1)?;
+    let mut chars = query.chars().skip(start).peekable();
+
+    let first = *chars.peek()?;
+    let len = if is_word_char(first) {
+        chars.take_while(|c| is_word_char(*c)).count()
+    } else {
+        1
+    };
+
+    Some(start..start + len)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn token_at_position() {
+        let query = "select * from ördrs where id = 1";
+        assert_eq!(token_range(query, 15), Some(14..19));
+        assert_eq!(token_range(query, 30), Some(29..30));
+        assert_eq!(token_range(query, 40), None);
+        assert_eq!(token_range(query, 0), None);
+    }
+
+    #[test]
+    fn present_fields_in_order() {
+        let err = QueryError {
+            hint: Some("Check the spelling.".into()),
+            table: Some("orders".into()),
+            detail: Some("Key (id)=(1) already exists.".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            err.fields(),
+            vec![
+                ("Detail", "Key (id)=(1) already exists."),
+                ("Hint", "Check the spelling."),
+                ("Table", "orders"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,491 @@
+use std::{error::Error, ops::Range};
+
+use bytes::{BufMut, BytesMut};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
+use postgres_protocol::types as protocol;
+use tokio_postgres::types::{to_sql_checked, IsNull, Kind, ToSql, Type};
+
+use super::sql_splitter::{tokenize, Token, TokenKind};
+
+/// Types offered for explicit parameter declaration
+pub const PARAM_TYPES: &[Type] = &[
+    Type::TEXT,
+    Type::INT4,
+    Type::INT8,
+    Type::NUMERIC,
+    Type::FLOAT8,
+    Type::BOOL,
+    Type::DATE,
+    Type::TIME,
+    Type::TIMESTAMP,
+    Type::TIMESTAMPTZ,
+    Type::JSON,
+    Type::JSONB,
+    Type::UUID,
+    Type::BYTEA,
+];
+
+/// Statement with its placeholders rewritten to positional ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedQuery {
+    /// Statement text, `:name` placeholders are replaced with `$n`
+    pub text: String,
+    /// Parameter names in positional order, `$1`-like for positional and `:name`-like for named
+    /// placeholders
+    pub params: Vec<String>,
+    /// Byte ranges of every placeholder in the statement as written and in `text`
+    pub rewrites: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl ParsedQuery {
+    /// Maps a character position in `text`, counted from 1 as the server reports it, to the
+    /// statement as written, `original`. A position within a placeholder maps to its start.
+    pub fn original_position(&self, original: &str, position: u32) -> u32 {
+        let idx = (position as usize).saturating_sub(1);
+        let offset = self
+            .text
+            .char_indices()
+            .nth(idx)
+            .map_or(self.text.len(), |(offset, _)| offset);
+
+        let original_offset = match self
+            .rewrites
+            .iter()
+            .rev()
+            .find(|(_, to)| to.start <= offset)
+        {
+            Some((from, to)) if offset < to.end => from.start,
+            Some((from, to)) => from.end + (offset - to.end),
+            None => offset,
+        };
+
+        original[..original_offset.min(original.len())]
+            .chars()
+            .count() as u32
+            + 1
+    }
+}
+
+#[derive(Debug)]
+enum Placeholder<'a> {
+    Positional(usize),
+    Named(&'a str),
+}
+
+/// Finds `$1` and `:name` placeholders outside of literals, quoted identifiers and comments.
+///
+/// Named placeholders are numbered in order of appearance, after the highest positional one.
+pub fn parse_placeholders(text: &str) -> ParsedQuery {
+    let placeholders = placeholders(text);
+
+    let positional = placeholders
+        .iter()
+        .filter_map(|(_, placeholder)| match placeholder {
+            Placeholder::Positional(n) => Some(*n),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+
+    let mut params = (1..=positional)
+        .map(|n| format!("${}", n))
+        .collect::<Vec<_>>();
+    let mut rewritten = String::with_capacity(text.len());
+    let mut rewrites = vec![];
+    let mut copied = 0;
+
+    for (range, placeholder) in placeholders {
+        rewritten.push_str(&text[copied..range.start]);
+        copied = range.end;
+
+        let n = match placeholder {
+            Placeholder::Positional(n) => n,
+            Placeholder::Named(name) => {
+                let name = format!(":{}", name);
+                match params.iter().position(|p| *p == name) {
+                    Some(idx) => idx + 1,
+                    None => {
+                        params.push(name);
+                        params.len()
+                    }
+                }
+            }
+        };
+        let start = rewritten.len();
+        rewritten.push_str(&format!("${}", n));
+        rewrites.push((range, start..rewritten.len()));
+    }
+    rewritten.push_str(&text[copied..]);
+
+    ParsedQuery {
+        text: rewritten,
+        params,
+        rewrites,
+    }
+}
+
+/// Placeholders of `text` with their byte ranges. A colon in an array subscript separates the
+/// bounds of a slice, like in `arr[lo:hi]`, and starts no placeholder.
+fn placeholders(text: &str) -> Vec<(Range<usize>, Placeholder<'_>)> {
+    let tokens = tokenize(text);
+    let mut placeholders = vec![];
+    // whether each open bracket is a subscript rather than an `ARRAY[...]` constructor
+    let mut subscripts = vec![];
+
+    for (idx, token) in tokens.iter().enumerate() {
+        let previous = idx.checked_sub(1).map(|idx| &tokens[idx]);
+
+        match token.kind {
+            TokenKind::Param => {
+                if let Ok(n) = token.text[1..].parse() {
+                    placeholders.push((token.start..token.end(), Placeholder::Positional(n)));
+                }
+            }
+            TokenKind::Symbol if token.text == "[" => {
+                let subscript = matches!(
+                    previous,
+                    Some(previous) if is_operand(previous) && previous.value() != "array"
+                );
+                subscripts.push(subscript);
+            }
+            TokenKind::Symbol if token.text == "]" => {
+                subscripts.pop();
+            }
+            TokenKind::Symbol if token.text == ":" => {
+                let name = match tokens.get(idx + 1) {
+                    Some(name) if name.kind == TokenKind::Word && name.start == token.end() => name,
+                    _ => continue,
+                };
+                let slice = subscripts.last() == Some(&true)
+                    && matches!(previous, Some(previous) if previous.is("[") || is_operand(previous));
+
+                if !slice {
+                    placeholders.push((token.start..name.end(), Placeholder::Named(name.text)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    placeholders
+}
+
+/// Tokens a subscript or a slice bound can follow
+fn is_operand(token: &Token) -> bool {
+    matches!(
+        token.kind,
+        TokenKind::Word | TokenKind::QuotedName | TokenKind::Number | TokenKind::Param
+    ) || token.is(")")
+        || token.is("]")
+}
+
+/// Value bound to a statement parameter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryParam {
+    pub name: String,
+    /// Declared type, `None` lets the server infer it from the statement
+    pub ty: Option<Type>,
+    /// Text representation of the value, `None` binds `NULL`
+    pub value: Option<String>,
+}
+
+impl QueryParam {
+    /// Type sent with the statement, `unknown` leaves the resolution to the server
+    pub fn declared_type(&self) -> Type {
+        self.ty.clone().unwrap_or(Type::UNKNOWN)
+    }
+}
+
+impl ToSql for QueryParam {
+    /// Converts the text value into the binary representation of the resolved parameter type
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        let text = match &self.value {
+            Some(text) => text.as_str(),
+            None => return Ok(IsNull::Yes),
+        };
+
+        let invalid = |err: &dyn std::fmt::Display| -> Box<dyn Error + Sync + Send> {
+            format!("invalid value of {} for type {}: {}", self.name, ty, err).into()
+        };
+
+        match *ty {
+            Type::BOOL => match text.trim().to_lowercase().as_str() {
+                "t" | "true" | "y" | "yes" | "on" | "1" => true.to_sql(ty, out),
+                "f" | "false" | "n" | "no" | "off" | "0" => false.to_sql(ty, out),
+                _ => Err(invalid(&"expected true or false")),
+            },
+            Type::INT2 => text
+                .trim()
+                .parse::<i16>()
+                .map_err(|e| invalid(&e))?
+                .to_sql(ty, out),
+            Type::INT4 => text
+                .trim()
+                .parse::<i32>()
+                .map_err(|e| invalid(&e))?
+                .to_sql(ty, out),
+            Type::INT8 => text
+                .trim()
+                .parse::<i64>()
+                .map_err(|e| invalid(&e))?
+                .to_sql(ty, out),
+            Type::OID => text
+                .trim()
+                .parse::<u32>()
+                .map_err(|e| invalid(&e))?
+                .to_sql(ty, out),
+            Type::FLOAT4 => text
+                .trim()
+                .parse::<f32>()
+                .map_err(|e| invalid(&e))?
+                .to_sql(ty, out),
+            Type::FLOAT8 => text
+                .trim()
+                .parse::<f64>()
+                .map_err(|e| invalid(&e))?
+                .to_sql(ty, out),
+            Type::NUMERIC => {
+                out.put_slice(&encode_numeric(text.trim()).map_err(|e| invalid(&e))?);
+                Ok(IsNull::No)
+            }
+            Type::DATE => NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+                .map_err(|e| invalid(&e))?
+                .to_sql(ty, out),
+            Type::TIME => text
+                .trim()
+                .parse::<NaiveTime>()
+                .map_err(|e| invalid(&e))?
+                .to_sql(ty, out),
+            Type::TIMESTAMP => parse_timestamp(text.trim())
+                .map_err(|e| invalid(&e))?
+                .to_sql(ty, out),
+            Type::TIMESTAMPTZ => DateTime::<FixedOffset>::parse_from_rfc3339(text.trim())
+                .or_else(|_| DateTime::parse_from_str(text.trim(), "%Y-%m-%d %H:%M:%S%.f%#z"))
+                .map_err(|e| invalid(&e))?
+                .to_sql(ty, out),
+            Type::JSON | Type::JSONB => serde_json::from_str::<serde_json::Value>(text)
+                .map_err(|e| invalid(&e))?
+                .to_sql(ty, out),
+            Type::UUID => {
+                protocol::uuid_to_sql(parse_uuid(text.trim()).map_err(|e| invalid(&e))?, out);
+                Ok(IsNull::No)
+            }
+            Type::BYTEA => {
+                protocol::bytea_to_sql(&parse_bytea(text.trim()).map_err(|e| invalid(&e))?, out);
+                Ok(IsNull::No)
+            }
+            Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => {
+                protocol::text_to_sql(text, out);
+                Ok(IsNull::No)
+            }
+            _ if matches!(ty.kind(), Kind::Enum(_)) => {
+                protocol::text_to_sql(text, out);
+                Ok(IsNull::No)
+            }
+            _ => Err(format!("parameters of type {} are not supported", ty).into()),
+        }
+    }
+
+    fn accepts(_: &Type) -> bool {
+        true
+    }
+
+    to_sql_checked!();
+}
+
+fn parse_timestamp(text: &str) -> Result<NaiveDateTime, chrono::ParseError> {
+    NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f"))
+}
+
+fn parse_uuid(text: &str) -> Result<[u8; 16], String> {
+    let hex = text.replace('-', "");
+    let bytes = parse_hex(&hex)?;
+
+    let mut uuid = [0; 16];
+    if bytes.len() != uuid.len() {
+        return Err("expected 32 hexadecimal digits".into());
+    }
+    uuid.copy_from_slice(&bytes);
+
+    Ok(uuid)
+}
+
+/// Parses bytea in hex format, other input is taken as is
+fn parse_bytea(text: &str) -> Result<Vec<u8>, String> {
+    match text.strip_prefix("\\x") {
+        Some(hex) => parse_hex(hex),
+        None => Ok(text.as_bytes().to_vec()),
+    }
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>, String> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| match std::str::from_utf8(pair) {
+            Ok(pair) if pair.len() == 2 => u8::from_str_radix(pair, 16).map_err(|e| e.to_string()),
+            _ => Err("invalid hexadecimal digits".into()),
+        })
+        .collect()
+}
+
+/// Encodes a decimal number into the binary `numeric` format
+fn encode_numeric(text: &str) -> Result<Vec<u8>, String> {
+    const NUMERIC_POS: u16 = 0x0000;
+    const NUMERIC_NEG: u16 = 0x4000;
+    const NUMERIC_NAN: u16 = 0xC000;
+
+    let header = |ndigits: u16, weight: i16, sign: u16, dscale: u16| {
+        let mut raw = vec![];
+        raw.extend_from_slice(&ndigits.to_be_bytes());
+        raw.extend_from_slice(&weight.to_be_bytes());
+        raw.extend_from_slice(&sign.to_be_bytes());
+        raw.extend_from_slice(&dscale.to_be_bytes());
+        raw
+    };
+
+    if text.eq_ignore_ascii_case("nan") {
+        return Ok(header(0, 0, NUMERIC_NAN, 0));
+    }
+
+    let (sign, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (NUMERIC_NEG, rest),
+        None => (NUMERIC_POS, text.strip_prefix('+').unwrap_or(text)),
+    };
+
+    let (integer, fraction) = match unsigned.find('.') {
+        Some(pos) => (&unsigned[..pos], &unsigned[pos + 1..]),
+        None => (unsigned, ""),
+    };
+
+    let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if (integer.is_empty() && fraction.is_empty()) || !is_digits(integer) || !is_digits(fraction) {
+        return Err(format!("'{}' is not a number", text));
+    }
+
+    // base 10000 digits, integer part padded on the left and fraction on the right
+    let integer = integer.trim_start_matches('0');
+    let int_pad = (4 - integer.len() % 4) % 4;
+    let frac_pad = (4 - fraction.len() % 4) % 4;
+    let decimal = format!(
+        "{}{}{}{}",
+        "0".repeat(int_pad),
+        integer,
+        fraction,
+        "0".repeat(frac_pad)
+    );
+
+    let mut digits = (0..decimal.len())
+        .step_by(4)
+        .map(|idx| decimal[idx..idx + 4].parse::<u16>().unwrap())
+        .collect::<Vec<_>>();
+    let mut weight = ((integer.len() + int_pad) / 4) as i16 - 1;
+
+    while digits.first() == Some(&0) {
+        digits.remove(0);
+        weight -= 1;
+    }
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    if digits.is_empty() {
+        weight = 0;
+    }
+
+    let sign = if digits.is_empty() { NUMERIC_POS } else { sign };
+    let mut raw = header(digits.len() as u16, weight, sign, fraction.len() as u16);
+    digits
+        .iter()
+        .for_each(|d| raw.extend_from_slice(&d.to_be_bytes()));
+
+    Ok(raw)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::model::value::test::numeric;
+
+    #[test]
+    fn placeholders_outside_literals() {
+        let parsed = parse_placeholders(
+            "select $2, :name::text, ':skip', \"col:skip\", $$ :skip $1 $$ -- :skip\n\
+             from t where a = :name and b = :other /* $9 */",
+        );
+
+        assert_eq!(parsed.params, vec!["$1", "$2", ":name", ":other"]);
+        assert_eq!(
+            parsed.text,
+            "select $2, $3::text, ':skip', \"col:skip\", $$ :skip $1 $$ -- :skip\n\
+             from t where a = $3 and b = $4 /* $9 */"
+        );
+    }
+
+    #[test]
+    fn placeholders_outside_escaped_strings_and_nested_comments() {
+        let parsed =
+            parse_placeholders("select E'it\\'s :skip', :a /* outer /* :skip */ :skip */ , :b");
+
+        assert_eq!(parsed.params, vec![":a", ":b"]);
+        assert_eq!(
+            parsed.text,
+            "select E'it\\'s :skip', $1 /* outer /* :skip */ :skip */ , $2"
+        );
+    }
+
+    #[test]
+    fn slices_are_not_placeholders() {
+        let parsed = parse_placeholders(
+            "select arr[lo:hi], arr[:n], arr[1:2], m[1][2:3], arr[1 + :i], array[:x], f(:y)",
+        );
+
+        assert_eq!(parsed.params, vec![":i", ":x", ":y"]);
+        assert_eq!(
+            parsed.text,
+            "select arr[lo:hi], arr[:n], arr[1:2], m[1][2:3], arr[1 + $1], array[$2], f($3)"
+        );
+    }
+
+    #[test]
+    fn no_placeholders() {
+        let parsed = parse_placeholders("select now()::date, 'a:b', $tag$ $1 $tag$");
+
+        assert!(parsed.params.is_empty());
+        assert_eq!(parsed.text, "select now()::date, 'a:b', $tag$ $1 $tag$");
+    }
+
+    #[test]
+    fn error_positions_map_to_placeholders() {
+        let original = "select :first_name, 'ü', :x + bad";
+        let parsed = parse_placeholders(original);
+        assert_eq!(parsed.text, "select $1, 'ü', $2 + bad");
+
+        let position = |rewritten: &str| rewritten.chars().count() as u32 + 1;
+        assert_eq!(parsed.original_position(original, position("select ")), 8);
+        assert_eq!(parsed.original_position(original, position("select $")), 8);
+        assert_eq!(
+            parsed.original_position(original, position("select $1, 'ü', $2 + ")),
+            position("select :first_name, 'ü', :x + ")
+        );
+        assert_eq!(parsed.original_position(original, 3), 3);
+    }
+
+    #[test]
+    fn numeric_encoding() {
+        assert_eq!(encode_numeric("0").unwrap(), numeric(0, 0, 0, &[]));
+        assert_eq!(
+            encode_numeric("12345.678").unwrap(),
+            numeric(1, 0, 3, &[1, 2345, 6780])
+        );
+        assert_eq!(encode_numeric("0.0001").unwrap(), numeric(-1, 0, 4, &[1]));
+        assert_eq!(
+            encode_numeric("-4200000000").unwrap(),
+            numeric(2, 0x4000, 0, &[42])
+        );
+        assert!(encode_numeric("1e5").is_err());
+    }
+}
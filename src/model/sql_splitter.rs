@@ -0,0 +1,448 @@
+use std::ops::Range;
+
+/// Kind of a lexical token of SQL text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Keyword or unquoted name, which may contain `$`
+    Word,
+    /// Name in double quotes
+    QuotedName,
+    /// String constant, `'...'`, `E'...'` with backslash escapes or dollar quoted
+    String,
+    Number,
+    /// Positional parameter like `$1`
+    Param,
+    Comment,
+    /// Any other character, `::` is a single symbol
+    Symbol,
+}
+
+/// Token of SQL text, whitespace between tokens is skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    /// Text of the token as written, including quotes and comment markers
+    pub text: &'a str,
+    /// Byte offset of the token in the lexed text
+    pub start: usize,
+}
+
+impl Token<'_> {
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    /// Whether the token is the symbol `symbol`
+    pub fn is(&self, symbol: &str) -> bool {
+        self.kind == TokenKind::Symbol && self.text == symbol
+    }
+
+    /// Word folded to lowercase, quoted names and strings without their quotes, other tokens
+    /// as written
+    pub fn value(&self) -> String {
+        match self.kind {
+            TokenKind::Word => self.text.to_lowercase(),
+            TokenKind::QuotedName => unquote(self.text, '"'),
+            TokenKind::String => string_value(self.text),
+            _ => self.text.to_string(),
+        }
+    }
+}
+
+/// Splits `text` into tokens. Unterminated quotes and comments extend to the end of the text.
+pub fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let bytes = text.as_bytes();
+    let mut tokens = vec![];
+    let mut idx = 0;
+
+    let skip_until = |from: usize, pattern: &str| match text[from..].find(pattern) {
+        Some(pos) => from + pos + pattern.len(),
+        None => text.len(),
+    };
+    let skip_digits = |from: usize| {
+        (from..bytes.len())
+            .find(|&i| !bytes[i].is_ascii_digit())
+            .unwrap_or(bytes.len())
+    };
+    let is_name_start = |b: u8| b.is_ascii_alphabetic() || b == b'_' || b >= 0x80;
+    let is_name = |b: u8| is_name_start(b) || b.is_ascii_digit();
+
+    while idx < bytes.len() {
+        let next = bytes.get(idx + 1).copied();
+        let start = idx;
+
+        let kind = match bytes[idx] {
+            b if b.is_ascii_whitespace() => {
+                idx += 1;
+                continue;
+            }
+            b'-' if next == Some(b'-') => {
+                idx = text[idx..].find('\n').map_or(text.len(), |pos| idx + pos);
+                TokenKind::Comment
+            }
+            b'/' if next == Some(b'*') => {
+                idx = skip_block_comment(bytes, idx);
+                TokenKind::Comment
+            }
+            // backslash escapes are only recognized in E'...' strings
+            b'e' | b'E' if next == Some(b'\'') => {
+                idx = skip_quoted(bytes, idx + 1, b'\'', true);
+                TokenKind::String
+            }
+            b'\'' => {
+                idx = skip_quoted(bytes, idx, b'\'', false);
+                TokenKind::String
+            }
+            b'"' => {
+                idx = skip_quoted(bytes, idx, b'"', false);
+                TokenKind::QuotedName
+            }
+            b'$' if matches!(next, Some(b) if b.is_ascii_digit()) => {
+                idx = skip_digits(idx + 1);
+                TokenKind::Param
+            }
+            b'$' => {
+                // `$$...$$` or `$tag$...$tag$`
+                let tag_end = (idx + 1..bytes.len()).find(|&i| !is_name(bytes[i]));
+                match tag_end {
+                    Some(tag_end) if bytes[tag_end] == b'$' => {
+                        idx = skip_until(tag_end + 1, &text[idx..=tag_end]);
+                        TokenKind::String
+                    }
+                    _ => {
+                        idx += 1;
+                        TokenKind::Symbol
+                    }
+                }
+            }
+            b if is_name_start(b) => {
+                idx = (idx..bytes.len())
+                    .find(|&i| !is_name(bytes[i]) && bytes[i] != b'$')
+                    .unwrap_or(bytes.len());
+                TokenKind::Word
+            }
+            b if b.is_ascii_digit()
+                || (b == b'.' && matches!(next, Some(b) if b.is_ascii_digit())) =>
+            {
+                idx = skip_digits(idx);
+                if bytes.get(idx) == Some(&b'.') && bytes.get(idx + 1) != Some(&b'.') {
+                    idx = skip_digits(idx + 1);
+                }
+                if matches!(bytes.get(idx), Some(b'e') | Some(b'E')) {
+                    let exponent = match bytes.get(idx + 1) {
+                        Some(b'+') | Some(b'-') => idx + 2,
+                        _ => idx + 1,
+                    };
+                    if matches!(bytes.get(exponent), Some(b) if b.is_ascii_digit()) {
+                        idx = skip_digits(exponent);
+                    }
+                }
+                TokenKind::Number
+            }
+            b':' if next == Some(b':') => {
+                idx += 2;
+                TokenKind::Symbol
+            }
+            _ => {
+                idx += text[idx..].chars().next().map_or(1, char::len_utf8);
+                TokenKind::Symbol
+            }
+        };
+
+        tokens.push(Token {
+            kind,
+            text: &text[start..idx],
+            start,
+        });
+    }
+
+    tokens
+}
+
+/// Splits `text` into statements separated by semicolons.
+///
+/// Semicolons inside string literals, quoted identifiers, comments and dollar quoted bodies do not
+/// end a statement. Returned byte ranges start at the first token which is not a comment and
+/// include the terminating semicolon; chunks made of comments only are left out.
+pub fn split_statements(text: &str) -> Vec<Range<usize>> {
+    let mut statements = vec![];
+    let mut start: Option<usize> = None;
+
+    for token in tokenize(text) {
+        match token.kind {
+            TokenKind::Comment => {}
+            TokenKind::Symbol if token.text == ";" => {
+                if let Some(start) = start.take() {
+                    statements.push(start..token.end());
+                }
+            }
+            _ => {
+                start.get_or_insert(token.start);
+            }
+        }
+    }
+
+    if let Some(start) = start {
+        // unterminated last statement, its trailing comments are kept
+        let end = start + text[start..].trim_end().len();
+        statements.push(start..end);
+    }
+
+    statements
+}
+
+/// Statement containing the byte `offset`. A cursor placed between statements belongs to the
+/// following one, a cursor past the last statement to the last one.
+pub fn statement_at(text: &str, offset: usize) -> Option<Range<usize>> {
+    let statements = split_statements(text);
+
+    statements
+        .iter()
+        .find(|range| offset <= range.end)
+        .or_else(|| statements.last())
+        .cloned()
+}
+
+/// First keyword of a statement in lowercase, leading comments and parentheses are skipped
+pub fn leading_keyword(text: &str) -> Option<String> {
+    tokenize(text)
+        .into_iter()
+        .find(|token| token.kind != TokenKind::Comment && !token.is("("))
+        .filter(|token| token.kind == TokenKind::Word)
+        .map(|token| token.value())
+}
+
+/// Possibly qualified and quoted name around the byte `offset`, like `public."Orders"`
+pub fn identifier_at(text: &str, offset: usize) -> Option<Range<usize>> {
+    let is_name = |c: char| c.is_alphanumeric() || matches!(c, '_' | '$' | '.' | '"');
+    let offset = offset.min(text.len());
+
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_name(*c))
+        .last()
+        .map_or(offset, |(idx, _)| idx);
+    let end = text[offset..]
+        .char_indices()
+        .find(|(_, c)| !is_name(*c))
+        .map_or(text.len(), |(idx, _)| offset + idx);
+
+    let name = text[start..end].trim_matches('.');
+    if name.is_empty() {
+        return None;
+    }
+
+    let start = start + text[start..end].find(name)?;
+    Some(start..start + name.len())
+}
+
+fn skip_quoted(bytes: &[u8], from: usize, quote: u8, escapes: bool) -> usize {
+    let mut idx = from + 1;
+
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'\\' if escapes => idx += 2,
+            // doubled quote is an escaped one
+            b if b == quote && bytes.get(idx + 1) == Some(&quote) => idx += 2,
+            b if b == quote => return idx + 1,
+            _ => idx += 1,
+        }
+    }
+
+    bytes.len()
+}
+
+/// Content of a quoted name or string, doubled quotes stand for the quote itself
+fn unquote(text: &str, quote: char) -> String {
+    let doubled = format!("{}{}", quote, quote);
+    let inner = text.strip_prefix(quote).unwrap_or(text);
+    let inner = inner.strip_suffix(quote).unwrap_or(inner);
+
+    inner.replace(&doubled, &quote.to_string())
+}
+
+fn string_value(text: &str) -> String {
+    if let Some(tag_end) = text.strip_prefix('$').and_then(|rest| rest.find('$')) {
+        // `$tag$` opens and closes the string
+        let tag = &text[..tag_end + 2];
+        let body = &text[tag.len()..];
+        return body.strip_suffix(tag).unwrap_or(body).to_string();
+    }
+
+    let escaped = match text.strip_prefix(|c| c == 'e' || c == 'E') {
+        Some(escaped) => escaped,
+        None => return unquote(text, '\''),
+    };
+
+    let inner = escaped.strip_prefix('\'').unwrap_or(escaped);
+    let inner = inner.strip_suffix('\'').unwrap_or(inner);
+    let mut value = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some('b') => '\u{8}',
+                Some('f') => '\u{c}',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some(c) => c,
+                None => break,
+            },
+            '\'' => {
+                // doubled quote
+                chars.next();
+                '\''
+            }
+            c => c,
+        };
+        value.push(c);
+    }
+
+    value
+}
+
+/// Block comments nest in PostgreSQL
+fn skip_block_comment(bytes: &[u8], from: usize) -> usize {
+    let mut depth = 0;
+    let mut idx = from;
+
+    while idx < bytes.len() {
+        match (bytes[idx], bytes.get(idx + 1)) {
+            (b'/', Some(b'*')) => {
+                depth += 1;
+                idx += 2;
+            }
+            (b'*', Some(b'/')) => {
+                depth -= 1;
+                idx += 2;
+                if depth == 0 {
+                    return idx;
+                }
+            }
+            _ => idx += 1,
+        }
+    }
+
+    bytes.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn split(text: &str) -> Vec<&str> {
+        split_statements(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn splits_on_semicolons_outside_quotes() {
+        let text = r#"
+            select 'a;b', "c;d", E'it\'s;' from t; -- trailing; comment
+            /* outer /* nested; */ still comment; */ select 2;
+            create function f() returns int as $body$ begin return 1; end $body$ language plpgsql;
+            do $$ begin perform 1; end $$
+        "#;
+
+        assert_eq!(
+            split(text),
+            vec![
+                r#"select 'a;b', "c;d", E'it\'s;' from t;"#,
+                "select 2;",
+                "create function f() returns int as $body$ begin return 1; end $body$ language plpgsql;",
+                "do $$ begin perform 1; end $$",
+            ]
+        );
+    }
+
+    #[test]
+    fn tokens() {
+        let text =
+            "select e'a\\'b', $q$ x $q$, \"Na\"\"me\" /* a /* b */ */ from t1::text[1:$2] -- end";
+        let tokens = tokenize(text)
+            .into_iter()
+            .map(|token| (token.kind, token.value()))
+            .collect::<Vec<_>>();
+
+        use TokenKind::*;
+        let expected = [
+            (Word, "select"),
+            (String, "a'b"),
+            (Symbol, ","),
+            (String, " x "),
+            (Symbol, ","),
+            (QuotedName, "Na\"me"),
+            (Comment, "/* a /* b */ */"),
+            (Word, "from"),
+            (Word, "t1"),
+            (Symbol, "::"),
+            (Word, "text"),
+            (Symbol, "["),
+            (Number, "1"),
+            (Symbol, ":"),
+            (Param, "$2"),
+            (Symbol, "]"),
+            (Comment, "-- end"),
+        ];
+        assert_eq!(
+            tokens,
+            expected
+                .iter()
+                .map(|(kind, value)| (*kind, value.to_string()))
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            tokenize("1.5e-3 .5 col$1 'it''s'")
+                .iter()
+                .map(Token::value)
+                .collect::<Vec<_>>(),
+            ["1.5e-3", ".5", "col$1", "it's"]
+        );
+    }
+
+    #[test]
+    fn skips_comment_only_chunks() {
+        assert_eq!(split("select 1; -- done\n"), vec!["select 1;"]);
+        assert_eq!(split(";;  ; select $1"), vec!["select $1"]);
+    }
+
+    #[test]
+    fn statement_keyword() {
+        assert_eq!(
+            leading_keyword("-- list\n/* all */ (SELECT 1)").as_deref(),
+            Some("select")
+        );
+        assert_eq!(
+            leading_keyword("  with x as (select 1) table x").as_deref(),
+            Some("with")
+        );
+        assert_eq!(leading_keyword("-- nothing"), None);
+    }
+
+    #[test]
+    fn identifier_under_cursor() {
+        let text = "select * from public.\"Orders\" o;";
+        let at = |offset| identifier_at(text, offset).map(|range| &text[range]);
+
+        assert_eq!(at(16), Some("public.\"Orders\""));
+        assert_eq!(at(29), Some("public.\"Orders\""));
+        assert_eq!(at(2), Some("select"));
+        assert_eq!(at(8), None);
+    }
+
+    #[test]
+    fn statement_under_cursor() {
+        let text = "select 1;\n\nselect 2;\n";
+
+        assert_eq!(statement_at(text, 0), Some(0..9));
+        assert_eq!(statement_at(text, 9), Some(0..9));
+        assert_eq!(statement_at(text, 10), Some(11..20));
+        assert_eq!(statement_at(text, text.len()), Some(11..20));
+        assert_eq!(statement_at("", 0), None);
+    }
+}
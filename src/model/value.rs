@@ -0,0 +1,565 @@
+use std::{cmp::Ordering, convert::TryInto, error::Error, fmt};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset, Utc};
+use fallible_iterator::FallibleIterator;
+use postgres_protocol::types as protocol;
+use tokio_postgres::types::{FromSql, Kind, Type};
+
+/// Typed value of a single result cell
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    /// Arbitrary precision number kept in its exact decimal representation
+    Numeric(String),
+    Text(String),
+    Date(NaiveDate),
+    Time(NaiveTime),
+    Timestamp(NaiveDateTime),
+    /// Offset of the session time zone when read in text format, UTC when read in binary
+    TimestampTz(DateTime<FixedOffset>),
+    Json(serde_json::Value),
+    Bytea(Vec<u8>),
+    /// Elements of a one-dimensional array, or the nested arrays of the next dimension
+    Array(Vec<Value>),
+    /// Binary value of a type without dedicated decoding, rendered as its raw bytes. Such types
+    /// are read in text format instead where the statement allows it, see `decodes_binary`.
+    Unknown(Vec<u8>),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Value::Int(_) | Value::Float(_) | Value::Numeric(_))
+    }
+
+    /// Numeric value used for sorting, `None` for non-numeric values
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            Value::Numeric(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Ordering used for sorting result columns, nulls go first
+    pub fn sort_cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Null, _) => Ordering::Less,
+            (_, Value::Null) => Ordering::Greater,
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Text(a), Value::Text(b)) => a.cmp(b),
+            (Value::Date(a), Value::Date(b)) => a.cmp(b),
+            (Value::Time(a), Value::Time(b)) => a.cmp(b),
+            (Value::Timestamp(a), Value::Timestamp(b)) => a.cmp(b),
+            (Value::TimestampTz(a), Value::TimestampTz(b)) => a.cmp(b),
+            (a, b) => match (a.as_f64(), b.as_f64()) {
+                (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                _ => a.to_string().cmp(&b.to_string()),
+            },
+        }
+    }
+
+    fn decode(ty: &Type, raw: &[u8]) -> Result<Value, Box<dyn Error + Sync + Send>> {
+        let value = match *ty {
+            Type::BOOL => Value::Bool(protocol::bool_from_sql(raw)?),
+            // single byte "char" of catalog columns like `relkind`
+            Type::CHAR => Value::Text(char::from(protocol::char_from_sql(raw)? as u8).to_string()),
+            Type::INT2 => Value::Int(protocol::int2_from_sql(raw)? as i64),
+            Type::INT4 => Value::Int(protocol::int4_from_sql(raw)? as i64),
+            Type::INT8 => Value::Int(protocol::int8_from_sql(raw)?),
+            Type::OID => Value::Int(protocol::oid_from_sql(raw)? as i64),
+            // going through text keeps the shortest representation, e.g. 0.1 instead of
+            // 0.10000000149011612
+            Type::FLOAT4 => Value::Float(protocol::float4_from_sql(raw)?.to_string().parse()?),
+            Type::FLOAT8 => Value::Float(protocol::float8_from_sql(raw)?),
+            Type::NUMERIC => Value::Numeric(decode_numeric(raw)?),
+            Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => {
+                Value::Text(protocol::text_from_sql(raw)?.to_string())
+            }
+            Type::DATE | Type::TIME | Type::TIMESTAMP | Type::TIMESTAMPTZ => {
+                decode_datetime(ty, raw)?
+            }
+            Type::INTERVAL => Value::Text(decode_interval(raw)?),
+            Type::UUID => Value::Text(format_uuid(protocol::uuid_from_sql(raw)?)),
+            Type::JSON | Type::JSONB => Value::Json(serde_json::Value::from_sql(ty, raw)?),
+            Type::BYTEA => Value::Bytea(protocol::bytea_from_sql(raw).to_vec()),
+            _ => match ty.kind() {
+                Kind::Array(member) => {
+                    let array = protocol::array_from_sql(raw)?;
+                    let dimensions = array
+                        .dimensions()
+                        .map(|dimension| Ok(dimension.len.max(0) as usize))
+                        .collect::<Vec<_>>()?;
+
+                    let mut values = vec![];
+                    let mut elements = array.values();
+                    while let Some(element) = elements.next()? {
+                        values.push(match element {
+                            Some(raw) => Value::decode(member, raw)?,
+                            None => Value::Null,
+                        });
+                    }
+
+                    Value::Array(nest(values, &dimensions))
+                }
+                Kind::Domain(base) => Value::decode(base, raw)?,
+                // enum labels are sent as plain text
+                Kind::Enum(_) => Value::Text(protocol::text_from_sql(raw)?.to_string()),
+                _ => Value::Unknown(raw.to_vec()),
+            },
+        };
+
+        Ok(value)
+    }
+
+    /// Whether values of `ty` are decoded from the binary format, other types like `inet`,
+    /// `regclass` or records have to be read in text format to be shown as the server prints them.
+    /// So does `timestamptz`, which is sent in UTC and printed by the server in the session time
+    /// zone.
+    pub fn decodes_binary(ty: &Type) -> bool {
+        match *ty {
+            Type::BOOL
+            | Type::CHAR
+            | Type::INT2
+            | Type::INT4
+            | Type::INT8
+            | Type::OID
+            | Type::FLOAT4
+            | Type::FLOAT8
+            | Type::NUMERIC
+            | Type::TEXT
+            | Type::VARCHAR
+            | Type::BPCHAR
+            | Type::NAME
+            | Type::UNKNOWN
+            | Type::DATE
+            | Type::TIME
+            | Type::TIMESTAMP
+            | Type::INTERVAL
+            | Type::UUID
+            | Type::JSON
+            | Type::JSONB
+            | Type::BYTEA => true,
+            _ => match ty.kind() {
+                Kind::Array(member) => Value::decodes_binary(member),
+                Kind::Domain(base) => Value::decodes_binary(base),
+                Kind::Enum(_) => true,
+                _ => false,
+            },
+        }
+    }
+
+    /// Reads a value sent in text format, numbers, booleans and JSON keep their type while
+    /// everything else is shown as sent
+    pub fn from_text(ty: &Type, text: &str) -> Value {
+        let typed = match *ty {
+            Type::BOOL => match text {
+                "t" => Some(Value::Bool(true)),
+                "f" => Some(Value::Bool(false)),
+                _ => None,
+            },
+            Type::INT2 | Type::INT4 | Type::INT8 | Type::OID => text.parse().ok().map(Value::Int),
+            Type::FLOAT4 | Type::FLOAT8 => text.parse().ok().map(Value::Float),
+            Type::NUMERIC => Some(Value::Numeric(text.to_string())),
+            Type::JSON | Type::JSONB => serde_json::from_str(text).ok().map(Value::Json),
+            // other date styles than ISO are kept as text
+            Type::TIMESTAMPTZ => DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z")
+                .ok()
+                .map(Value::TimestampTz),
+            _ => match ty.kind() {
+                Kind::Domain(base) => Some(Value::from_text(base, text)),
+                _ => None,
+            },
+        };
+
+        typed.unwrap_or_else(|| Value::Text(text.to_string()))
+    }
+}
+
+impl<'a> FromSql<'a> for Value {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        Value::decode(ty, raw)
+    }
+
+    fn from_sql_null(_: &Type) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        Ok(Value::Null)
+    }
+
+    fn accepts(_: &Type) -> bool {
+        true
+    }
+}
+
+impl<T: Into<String>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, |v| Value::Text(v.into()))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("[null]"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(v) if v.is_nan() => f.write_str("NaN"),
+            Value::Float(v) if v.is_infinite() && *v > 0.0 => f.write_str("Infinity"),
+            Value::Float(v) if v.is_infinite() => f.write_str("-Infinity"),
+            Value::Float(v) => write!(f, "{}", v),
+            Value::Numeric(n) | Value::Text(n) => f.write_str(n),
+            Value::Date(d) => write!(f, "{}", d),
+            Value::Time(t) => write!(f, "{}", t),
+            Value::Timestamp(ts) => write!(f, "{}", ts),
+            Value::TimestampTz(ts) => {
+                write!(f, "{}", ts.format("%Y-%m-%d %H:%M:%S%.f"))?;
+                f.write_str(&format_offset(ts.offset()))
+            }
+            Value::Json(json) => write!(f, "{}", json),
+            Value::Bytea(bytes) | Value::Unknown(bytes) => {
+                f.write_str("\\x")?;
+                bytes.iter().try_for_each(|b| write!(f, "{:02x}", b))
+            }
+            Value::Array(values) => {
+                f.write_str("{")?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(",")?;
+                    }
+                    match value {
+                        Value::Null => f.write_str("NULL")?,
+                        value => write!(f, "{}", value)?,
+                    }
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/// Decodes binary `numeric` into its decimal text representation
+fn decode_numeric(raw: &[u8]) -> Result<String, Box<dyn Error + Sync + Send>> {
+    const NUMERIC_NEG: u16 = 0x4000;
+    const NUMERIC_NAN: u16 = 0xC000;
+    const NUMERIC_PINF: u16 = 0xD000;
+    const NUMERIC_NINF: u16 = 0xF000;
+
+    let read_u16 = |idx: usize| -> Result<u16, Box<dyn Error + Sync + Send>> {
+        match raw.get(idx..idx + 2) {
+            Some(bytes) => Ok(u16::from_be_bytes([bytes[0], bytes[1]])),
+            None => Err("invalid numeric length".into()),
+        }
+    };
+
+    let ndigits = read_u16(0)? as usize;
+    let weight = read_u16(2)? as i16 as i32;
+    let sign = read_u16(4)?;
+    let dscale = read_u16(6)? as usize;
+    let digits = (0..ndigits)
+        .map(|idx| read_u16(8 + idx * 2))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match sign {
+        NUMERIC_NAN => return Ok("NaN".into()),
+        NUMERIC_PINF => return Ok("Infinity".into()),
+        NUMERIC_NINF => return Ok("-Infinity".into()),
+        _ => {}
+    }
+
+    // digits are base 10000, `weight` is the exponent of the first one
+    let digit = |idx: i32| -> u16 {
+        if idx >= 0 {
+            digits.get(idx as usize).copied().unwrap_or(0)
+        } else {
+            0
+        }
+    };
+
+    let mut text = String::new();
+    if sign == NUMERIC_NEG {
+        text.push('-');
+    }
+
+    if weight < 0 {
+        text.push('0');
+    } else {
+        text.push_str(&digit(0).to_string());
+        (1..=weight).for_each(|idx| text.push_str(&format!("{:04}", digit(idx))));
+    }
+
+    if dscale > 0 {
+        let mut fraction = String::new();
+        let mut idx = weight + 1;
+        while fraction.len() < dscale {
+            fraction.push_str(&format!("{:04}", digit(idx)));
+            idx += 1;
+        }
+        fraction.truncate(dscale);
+
+        text.push('.');
+        text.push_str(&fraction);
+    }
+
+    Ok(text)
+}
+
+fn decode_datetime(ty: &Type, raw: &[u8]) -> Result<Value, Box<dyn Error + Sync + Send>> {
+    let value = match *ty {
+        Type::DATE => match protocol::date_from_sql(raw)? {
+            i32::MAX => Value::Text("infinity".into()),
+            i32::MIN => Value::Text("-infinity".into()),
+            _ => Value::Date(NaiveDate::from_sql(ty, raw)?),
+        },
+        Type::TIME => Value::Time(NaiveTime::from_sql(ty, raw)?),
+        _ => match protocol::timestamp_from_sql(raw)? {
+            i64::MAX => Value::Text("infinity".into()),
+            i64::MIN => Value::Text("-infinity".into()),
+            _ if *ty == Type::TIMESTAMPTZ => {
+                let ts = DateTime::<Utc>::from_sql(ty, raw)?;
+                Value::TimestampTz(ts.with_timezone(&ts.offset().fix()))
+            }
+            _ => Value::Timestamp(NaiveDateTime::from_sql(ty, raw)?),
+        },
+    };
+
+    Ok(value)
+}
+
+/// Formats binary `interval` similarly to the server's `postgres` interval style
+fn decode_interval(raw: &[u8]) -> Result<String, Box<dyn Error + Sync + Send>> {
+    if raw.len() != 16 {
+        return Err("invalid interval length".into());
+    }
+
+    let micros = i64::from_be_bytes(raw[0..8].try_into()?);
+    let days = i32::from_be_bytes(raw[8..12].try_into()?);
+    let months = i32::from_be_bytes(raw[12..16].try_into()?);
+
+    let mut parts = vec![];
+    let (years, months) = (months / 12, months % 12);
+    for (amount, unit) in [(years, "year"), (months, "mon"), (days, "day")].iter() {
+        if *amount != 0 {
+            let plural = if amount.abs() == 1 { "" } else { "s" };
+            parts.push(format!("{} {}{}", amount, unit, plural));
+        }
+    }
+
+    if micros != 0 || parts.is_empty() {
+        let sign = if micros < 0 { "-" } else { "" };
+        let micros = micros.unsigned_abs();
+        let secs = micros / 1_000_000;
+        let mut time = format!(
+            "{}{:02}:{:02}:{:02}",
+            sign,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        );
+        if micros % 1_000_000 != 0 {
+            let fraction = format!("{:06}", micros % 1_000_000);
+            time.push('.');
+            time.push_str(fraction.trim_end_matches('0'));
+        }
+        parts.push(time);
+    }
+
+    Ok(parts.join(" "))
+}
+
+/// Elements of a multi-dimensional array grouped into nested arrays, `dimensions` being the
+/// lengths from the outermost one
+fn nest(values: Vec<Value>, dimensions: &[usize]) -> Vec<Value> {
+    let mut values = values;
+    for &len in dimensions.iter().skip(1).rev() {
+        let mut elements = values.into_iter();
+        let count = elements.len() / len.max(1);
+        values = (0..count)
+            .map(|_| Value::Array(elements.by_ref().take(len).collect()))
+            .collect();
+    }
+
+    values
+}
+
+/// UTC offset as the server prints it, e.g. `+02`, `-03:30` or `+00`
+fn format_offset(offset: &FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let (hours, minutes) = (seconds.abs() / 3600, seconds.abs() / 60 % 60);
+
+    match minutes {
+        0 => format!("{}{:02}", sign, hours),
+        _ => format!("{}{:02}:{:02}", sign, hours, minutes),
+    }
+}
+
+fn format_uuid(bytes: [u8; 16]) -> String {
+    let hex = bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// Binary `numeric` of base 10000 `digits`
+    pub(crate) fn numeric(weight: i16, sign: u16, dscale: u16, digits: &[u16]) -> Vec<u8> {
+        let mut raw = vec![];
+        raw.extend_from_slice(&(digits.len() as u16).to_be_bytes());
+        raw.extend_from_slice(&weight.to_be_bytes());
+        raw.extend_from_slice(&sign.to_be_bytes());
+        raw.extend_from_slice(&dscale.to_be_bytes());
+        digits
+            .iter()
+            .for_each(|d| raw.extend_from_slice(&d.to_be_bytes()));
+        raw
+    }
+
+    #[test]
+    fn numeric_decoding() {
+        let cases = [
+            (numeric(0, 0, 0, &[]), "0"),
+            (numeric(1, 0, 3, &[1, 2345, 6780]), "12345.678"),
+            (numeric(-1, 0, 4, &[1]), "0.0001"),
+            (numeric(-2, 0, 5, &[1000]), "0.00001"),
+            (numeric(2, 0x4000, 0, &[42]), "-4200000000"),
+            (numeric(0, 0, 2, &[7]), "7.00"),
+            (numeric(0, 0xC000, 0, &[]), "NaN"),
+        ];
+
+        for (raw, expected) in cases.iter() {
+            assert_eq!(decode_numeric(raw).unwrap(), *expected);
+        }
+    }
+
+    #[test]
+    fn interval_decoding() {
+        let interval = |micros: i64, days: i32, months: i32| {
+            let mut raw = vec![];
+            raw.extend_from_slice(&micros.to_be_bytes());
+            raw.extend_from_slice(&days.to_be_bytes());
+            raw.extend_from_slice(&months.to_be_bytes());
+            decode_interval(&raw).unwrap()
+        };
+
+        assert_eq!(interval(0, 0, 0), "00:00:00");
+        assert_eq!(interval(0, 3, 14), "1 year 2 mons 3 days");
+        assert_eq!(interval(14_706_500_000, 1, 0), "1 day 04:05:06.5");
+        assert_eq!(interval(-90_000_000, 0, 0), "-00:01:30");
+    }
+
+    #[test]
+    fn sort_order() {
+        let mut values = vec![
+            Value::Numeric("10.5".into()),
+            Value::Null,
+            Value::Int(2),
+            Value::Float(-1.0),
+        ];
+        values.sort_by(Value::sort_cmp);
+
+        assert_eq!(
+            values,
+            vec![
+                Value::Null,
+                Value::Float(-1.0),
+                Value::Int(2),
+                Value::Numeric("10.5".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn char_and_text_values() {
+        assert_eq!(
+            Value::decode(&Type::CHAR, b"r").unwrap(),
+            Value::Text("r".into())
+        );
+
+        assert!(Value::decodes_binary(&Type::INT4_ARRAY));
+        for ty in [Type::INET, Type::REGCLASS, Type::MONEY, Type::RECORD].iter() {
+            assert!(!Value::decodes_binary(ty));
+        }
+
+        assert_eq!(Value::from_text(&Type::INT8, "42"), Value::Int(42));
+        assert_eq!(Value::from_text(&Type::BOOL, "t"), Value::Bool(true));
+        assert_eq!(
+            Value::from_text(&Type::INET, "10.0.0.1/8"),
+            Value::Text("10.0.0.1/8".into())
+        );
+        assert_eq!(
+            Value::from_text(&Type::REGCLASS, "orders"),
+            Value::Text("orders".into())
+        );
+    }
+
+    #[test]
+    fn array_display() {
+        let array = Value::Array(vec![Value::Int(1), Value::Null, Value::Int(3)]);
+        assert_eq!(array.to_string(), "{1,NULL,3}");
+    }
+
+    #[test]
+    fn two_dimensional_array() {
+        // '{{1,2},{3,NULL}}'::int4[]: dimensions, null flag, element type, length and lower
+        // bound of each dimension, then the elements
+        let mut raw = vec![];
+        for word in [2, 1, Type::INT4.oid() as i32, 2, 1, 2, 1].iter() {
+            raw.extend_from_slice(&word.to_be_bytes());
+        }
+        for element in [1i32, 2, 3].iter() {
+            raw.extend_from_slice(&4i32.to_be_bytes());
+            raw.extend_from_slice(&element.to_be_bytes());
+        }
+        raw.extend_from_slice(&(-1i32).to_be_bytes());
+
+        let array = Value::decode(&Type::INT4_ARRAY, &raw).unwrap();
+        assert_eq!(
+            array,
+            Value::Array(vec![
+                Value::Array(vec![Value::Int(1), Value::Int(2)]),
+                Value::Array(vec![Value::Int(3), Value::Null]),
+            ])
+        );
+        assert_eq!(array.to_string(), "{{1,2},{3,NULL}}");
+    }
+
+    #[test]
+    fn timestamptz_in_session_time_zone() {
+        assert!(!Value::decodes_binary(&Type::TIMESTAMPTZ));
+
+        let ts = Value::from_text(&Type::TIMESTAMPTZ, "2021-08-01 12:30:00.25+02");
+        assert_eq!(ts.to_string(), "2021-08-01 12:30:00.250+02");
+        assert_eq!(
+            Value::from_text(&Type::TIMESTAMPTZ, "2021-08-01 12:30:00-03:30").to_string(),
+            "2021-08-01 12:30:00-03:30"
+        );
+        assert_eq!(
+            Value::from_text(&Type::TIMESTAMPTZ, "infinity"),
+            Value::Text("infinity".into())
+        );
+
+        // sent in binary when parameters require the extended protocol
+        let micros: i64 = 681_136_200_000_000;
+        let ts = Value::decode(&Type::TIMESTAMPTZ, &micros.to_be_bytes()).unwrap();
+        assert_eq!(ts.to_string(), "2021-08-01 12:30:00+00");
+    }
+}
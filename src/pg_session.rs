@@ -1,106 +1,1545 @@
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-use std::env;
-use std::sync::Arc;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use bytes::Bytes;
 use futures::channel::mpsc::unbounded;
 use futures::channel::mpsc::UnboundedReceiver;
 use futures::channel::mpsc::UnboundedSender;
-use futures::{stream, StreamExt, TryStreamExt};
-use tokio_postgres::SimpleQueryMessage;
-use tokio_postgres::{AsyncMessage, Client, Config, NoTls};
+use futures::{pin_mut, stream, SinkExt, StreamExt, TryStreamExt};
+use postgres_native_tls::MakeTlsConnector;
+use tokio::runtime::Handle;
+use tokio::task::block_in_place;
+use tokio_postgres::error::{DbError, SqlState};
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{AsyncMessage, CancelToken, Client};
+use tokio_postgres::{Row, RowStream, SimpleQueryMessage, Statement};
 
-use crate::event::PgRequest;
-use crate::event::{AppEvent, Emitter, EventListener};
-use crate::model::pg_response::PgResponse;
+use crate::config::Settings;
+use crate::ddl;
+use crate::event::{AppEvent, ConnectionState, DdlTarget, Emitter, EventListener};
+use crate::event::{FetchCount, NotifyCommand, PgRequest, TransactionControl, TransactionStatus};
+use crate::history::{self, HistoryEntry};
+use crate::import;
+use crate::model::catalog::{CatalogObject, CatalogQuery};
+use crate::model::completion::{self, CatalogCache};
+use crate::model::connection_profile::ConnectionProfile;
+use crate::model::copy::{CopyDirection, CopyStatement};
+use crate::model::import::ImportRequest;
+use crate::model::notification::{self, Notification};
+use crate::model::pg_message::ServerMessage;
+use crate::model::pg_response::{self, PgResponse, QueryStats};
+use crate::model::query_error::QueryError;
+use crate::model::query_params::QueryParam;
+use crate::model::sql_splitter::{leading_keyword, split_statements};
+use crate::tls::make_tls_connector;
+use crate::util::PartialFile;
+
+/// Commands handled by `pg_event_loop`
+#[derive(Debug)]
+pub enum PgCommand {
+    Request(PgRequest),
+    TransactionControl {
+        id: usize,
+        control: TransactionControl,
+    },
+    Fetch {
+        id: usize,
+        count: FetchCount,
+    },
+    Catalog(CatalogQuery),
+    Ddl(DdlTarget),
+    Import(Arc<ImportRequest>),
+    Notify(NotifyCommand),
+    Connect {
+        id: usize,
+    },
+    UseProfile(Arc<ConnectionProfile>),
+    Close {
+        id: usize,
+    },
+}
+
+/// Commands queued for a single session task
+#[derive(Debug)]
+enum SessionCommand {
+    Query {
+        text: String,
+        params: Vec<QueryParam>,
+        copy_file: Option<PathBuf>,
+    },
+    TransactionControl(TransactionControl),
+    Fetch(FetchCount),
+    Catalog(CatalogQuery),
+    Ddl(DdlTarget),
+    Import(Arc<ImportRequest>),
+    Notify(NotifyCommand),
+    /// Opens the connection ahead of the first statement
+    Connect,
+}
+
+/// Name of the cursor holding a paged result, a session has at most one
+const CURSOR_NAME: &str = "slonik_cursor";
+
+/// Session used by the schema explorer and the DDL generator, so that browsing does not wait for
+/// running queries
+const CATALOG_SESSION: usize = usize::MAX;
+
+/// Session loading files into tables, an import runs in a transaction of its own
+pub const IMPORT_SESSION: usize = usize::MAX - 1;
+
+/// Session of the notifications panel, idle apart from `LISTEN` and `NOTIFY` so that messages
+/// arrive as soon as they are sent
+const NOTIFY_SESSION: usize = usize::MAX - 2;
+
+/// Longest wait between tries to replace a lost connection
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Last statement run by a session, shared with the task forwarding its server messages
+type LastQuery = Arc<Mutex<Option<String>>>;
+
+/// Cancel handle of a session task, filled once the task is connected
+type CancelSlot = Arc<Mutex<Option<QueryCanceller>>>;
+
+/// Cancel slots of running session tasks, shared with the proxy so that cancellation does not
+/// have to go through the (possibly busy) session queue
+type Cancellers = Arc<Mutex<HashMap<usize, CancelSlot>>>;
 
 pub struct PgEventLoopProxy {
-    event_loop_tx: UnboundedSender<PgRequest>,
+    event_loop_tx: UnboundedSender<PgCommand>,
+    cancellers: Cancellers,
+    emitter: Emitter,
+    /// Listeners run on the GTK main loop, outside of the tokio runtime context
+    runtime: Handle,
 }
 
 impl PgEventLoopProxy {
     pub fn initialize(emitter: Emitter) -> Self {
         let (event_loop_tx, event_loop_rx) = unbounded();
+        let cancellers = Cancellers::default();
+
+        let settings = Settings::load_default().unwrap_or_else(|err| {
+            emitter.emit(AppEvent::Err(err));
+            Settings::default()
+        });
+
+        tokio::spawn(pg_event_loop(
+            emitter.clone(),
+            event_loop_rx,
+            Arc::clone(&cancellers),
+            Arc::new(settings),
+        ));
 
-        tokio::spawn(async move { pg_event_loop(emitter, event_loop_rx).await.unwrap() });
+        Self {
+            event_loop_tx,
+            cancellers,
+            emitter,
+            runtime: Handle::current(),
+        }
+    }
+
+    fn cancel(&self, id: usize) {
+        let canceller = match self.cancellers.lock().unwrap().get(&id) {
+            Some(slot) => slot.lock().unwrap().clone(),
+            None => None,
+        };
+
+        let canceller = match canceller {
+            Some(canceller) => canceller,
+            None => return,
+        };
 
-        Self { event_loop_tx }
+        let emitter = self.emitter.clone();
+        self.runtime.spawn(async move {
+            if let Err(err) = canceller.cancel().await {
+                emitter.emit(AppEvent::Err(err));
+            }
+        });
     }
 }
 
 impl EventListener for PgEventLoopProxy {
     fn on_event(&mut self, event: &AppEvent) {
-        if let AppEvent::PgRequest(req) = event {
-            self.event_loop_tx.unbounded_send(req.clone()).unwrap();
-        }
+        let command = match event {
+            AppEvent::PgRequest(req) => PgCommand::Request(req.clone()),
+            AppEvent::PgTransactionControl { id, control } => PgCommand::TransactionControl {
+                id: *id,
+                control: *control,
+            },
+            AppEvent::PgFetch { id, count } => PgCommand::Fetch {
+                id: *id,
+                count: *count,
+            },
+            AppEvent::CatalogRequest(query) => PgCommand::Catalog(query.clone()),
+            AppEvent::DdlRequest(target) => PgCommand::Ddl(target.clone()),
+            AppEvent::ImportRequest(request) => PgCommand::Import(Arc::clone(request)),
+            AppEvent::NotifyRequest(command) => PgCommand::Notify(command.clone()),
+            AppEvent::PgConnect { id } => PgCommand::Connect { id: *id },
+            AppEvent::ProfileSelected(profile) => PgCommand::UseProfile(Arc::clone(profile)),
+            AppEvent::PgCloseSession { id } => PgCommand::Close { id: *id },
+            AppEvent::PgCancel { id } => return self.cancel(*id),
+            _ => return,
+        };
+
+        self.event_loop_tx.unbounded_send(command).unwrap();
     }
 }
 
+/// Routes requests to per-session tasks, spawning them on first use.
+///
+/// Every session has its own queue, so a slow query blocks only the session it was sent to.
 pub async fn pg_event_loop(
     emitter: Emitter,
-    mut receiver: UnboundedReceiver<PgRequest>,
+    mut receiver: UnboundedReceiver<PgCommand>,
+    cancellers: Cancellers,
+    settings: Arc<Settings>,
+) {
+    let mut sessions: HashMap<usize, UnboundedSender<SessionCommand>> = HashMap::new();
+    let mut profile = Arc::new(ConnectionProfile::from_env());
+
+    while let Some(command) = receiver.next().await {
+        let (id, command) = match command {
+            PgCommand::Request(PgRequest {
+                id,
+                text,
+                params,
+                copy_file,
+            }) => (
+                id,
+                SessionCommand::Query {
+                    text,
+                    params,
+                    copy_file,
+                },
+            ),
+            PgCommand::TransactionControl { id, control } => {
+                (id, SessionCommand::TransactionControl(control))
+            }
+            PgCommand::Fetch { id, count } => (id, SessionCommand::Fetch(count)),
+            PgCommand::Catalog(query) => (CATALOG_SESSION, SessionCommand::Catalog(query)),
+            PgCommand::Ddl(target) => (CATALOG_SESSION, SessionCommand::Ddl(target)),
+            PgCommand::Import(request) => (IMPORT_SESSION, SessionCommand::Import(request)),
+            PgCommand::Notify(command) => (NOTIFY_SESSION, SessionCommand::Notify(command)),
+            PgCommand::Connect { id } => (id, SessionCommand::Connect),
+            PgCommand::UseProfile(new_profile) => {
+                // dropping the senders lets running tasks finish their queues and exit,
+                // new sessions are opened lazily against the new profile
+                sessions.clear();
+                profile = new_profile;
+                continue;
+            }
+            PgCommand::Close { id } => {
+                sessions.remove(&id);
+                cancellers.lock().unwrap().remove(&id);
+                continue;
+            }
+        };
+
+        let spawn = || spawn_session(id, &profile, emitter.clone(), &cancellers, &settings);
+        let session = sessions.entry(id).or_insert_with(spawn);
+
+        if let Err(err) = session.unbounded_send(command) {
+            // session task is gone, start over with a fresh one
+            let session = spawn();
+            session.unbounded_send(err.into_inner()).unwrap();
+            sessions.insert(id, session);
+        }
+    }
+}
+
+fn spawn_session(
+    id: usize,
+    profile: &Arc<ConnectionProfile>,
+    emitter: Emitter,
+    cancellers: &Cancellers,
+    settings: &Arc<Settings>,
+) -> UnboundedSender<SessionCommand> {
+    let (tx, rx) = unbounded();
+    let cancel_slot = CancelSlot::default();
+
+    cancellers
+        .lock()
+        .unwrap()
+        .insert(id, Arc::clone(&cancel_slot));
+
+    tokio::spawn(session_loop(
+        id,
+        Arc::clone(profile),
+        emitter,
+        rx,
+        cancel_slot,
+        Arc::clone(settings),
+    ));
+
+    tx
+}
+
+/// Executes commands of a single session one after another
+async fn session_loop(
+    id: usize,
+    profile: Arc<ConnectionProfile>,
+    emitter: Emitter,
+    mut commands: UnboundedReceiver<SessionCommand>,
+    cancel_slot: CancelSlot,
+    settings: Arc<Settings>,
+) {
+    let fetch_size = settings.fetch_size.max(1) as usize;
+    let (lost_tx, mut lost) = unbounded();
+    let mut connector = Connector {
+        id,
+        profile: Arc::clone(&profile),
+        settings: Arc::clone(&settings),
+        emitter: emitter.clone(),
+        cancel_slot,
+        lost: lost_tx,
+        opened: 0,
+    };
+
+    let mut session: Option<PgSession> = None;
+    let mut auto_commit = true;
+    // the explorer session does not serve an editor
+    let mut catalog_stale = id != CATALOG_SESSION;
+
+    loop {
+        let command = tokio::select! {
+            // a lost connection is handled before the commands queued meanwhile
+            biased;
+
+            Some(connection) = lost.next() => {
+                // a connection replaced in the meantime is of no concern
+                if matches!(&session, Some(session) if session.connection == connection) {
+                    on_connection_lost(&mut session, &mut connector, auto_commit).await;
+                }
+                continue;
+            }
+            command = commands.next() => match command {
+                Some(command) => command,
+                None => break,
+            },
+        };
+
+        let (text, params, copy_file) = match command {
+            SessionCommand::Query {
+                text,
+                params,
+                copy_file,
+            } => (text, params, copy_file),
+            SessionCommand::TransactionControl(TransactionControl::SetAutoCommit(enabled)) => {
+                // takes effect with the next transaction, an open one has to be ended manually
+                auto_commit = enabled;
+                let status = session
+                    .as_ref()
+                    .map_or(TransactionStatus::Idle, |s| s.transaction_status);
+                emitter.emit(AppEvent::PgTransactionStatus {
+                    id,
+                    status,
+                    auto_commit,
+                });
+                continue;
+            }
+            SessionCommand::TransactionControl(control) => {
+                let session = match session.as_mut() {
+                    Some(session) => session,
+                    None => continue,
+                };
+
+                let commit = control == TransactionControl::Commit;
+                if let Err(err) = session.end_transaction(commit).await {
+                    emitter.emit(AppEvent::Err(err));
+                }
+
+                emitter.emit(AppEvent::PgTransactionStatus {
+                    id,
+                    status: session.transaction_status,
+                    auto_commit,
+                });
+                emitter.emit(AppEvent::PgCursorState { id, more: false });
+                continue;
+            }
+            SessionCommand::Fetch(count) => {
+                let session = match session.as_mut() {
+                    Some(session) if session.cursor.is_some() => session,
+                    _ => continue,
+                };
+
+                emitter.emit(AppEvent::PgSessionBusy { id, busy: true });
+
+                let result = fetch_rows(id, session, count, &emitter).await;
+                emit_query_result(id, result, &emitter);
+
+                emitter.emit(AppEvent::PgCursorState {
+                    id,
+                    more: session.cursor.is_some(),
+                });
+                emitter.emit(AppEvent::PgTransactionStatus {
+                    id,
+                    status: session.transaction_status,
+                    auto_commit,
+                });
+                emitter.emit(AppEvent::PgSessionBusy { id, busy: false });
+                continue;
+            }
+            SessionCommand::Catalog(query) => {
+                let result = match connected(&mut session, &mut connector, auto_commit).await {
+                    Ok(session) => session.load_catalog(&query).await,
+                    Err(err) => Err(err),
+                };
+
+                let objects = match result {
+                    Ok(objects) => Some(Arc::new(objects)),
+                    Err(err) => {
+                        emitter.emit(AppEvent::Err(err));
+                        None
+                    }
+                };
+
+                emitter.emit(AppEvent::CatalogLoaded { query, objects });
+                continue;
+            }
+            SessionCommand::Connect => {
+                // failures are reported once a statement needs the connection
+                match connected(&mut session, &mut connector, auto_commit).await {
+                    Ok(session) => {
+                        refresh_catalog_cache(id, session, &mut catalog_stale, &emitter).await
+                    }
+                    Err(err) => log::warn!("Connecting session {} failed: {}", id, err),
+                }
+                continue;
+            }
+            SessionCommand::Ddl(target) => {
+                let result = match connected(&mut session, &mut connector, auto_commit).await {
+                    Ok(session) => session.generate_ddl(&target).await,
+                    Err(err) => Err(err),
+                };
+
+                match result {
+                    Ok((title, text)) => emitter.emit(AppEvent::OpenScript { title, text }),
+                    Err(err) => emitter.emit(AppEvent::Err(err)),
+                }
+                continue;
+            }
+            SessionCommand::Import(request) => {
+                let result = match connected(&mut session, &mut connector, auto_commit).await {
+                    Ok(session) => import::run(&session.client, &request, &emitter).await,
+                    Err(err) => Err(err),
+                };
+
+                let report = match result {
+                    Ok(report) => Some(Arc::new(report)),
+                    Err(err) => {
+                        emitter.emit(AppEvent::Err(err));
+                        None
+                    }
+                };
+
+                emitter.emit(AppEvent::ImportFinished(report));
+                continue;
+            }
+            SessionCommand::Notify(command) => {
+                let session = match connected(&mut session, &mut connector, auto_commit).await {
+                    Ok(session) => session,
+                    Err(err) => {
+                        emitter.emit(AppEvent::Err(err));
+                        continue;
+                    }
+                };
+
+                if let Err(err) = session.notify(&command).await {
+                    emitter.emit(AppEvent::Err(err));
+                }
+
+                let channels = session.channels.iter().cloned().collect();
+                emitter.emit(AppEvent::NotifyChannels(Arc::new(channels)));
+                continue;
+            }
+        };
+
+        emitter.emit(AppEvent::PgSessionBusy { id, busy: true });
+
+        let executed_at = history::now_millis();
+        let started = Instant::now();
+
+        let result = match connected(&mut session, &mut connector, auto_commit).await {
+            Ok(session) => {
+                session
+                    .execute(
+                        &text,
+                        &params,
+                        copy_file.as_deref(),
+                        auto_commit,
+                        fetch_size,
+                    )
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+
+        let entry = HistoryEntry {
+            executed_at,
+            profile: profile.name.clone(),
+            database: profile.dbname.clone(),
+            query: text.clone(),
+            duration_ms: started.elapsed().as_millis() as u64,
+            rows: result.as_ref().ok().and_then(|(_, stats)| stats.rows),
+            error: result.as_ref().err().map(error_message),
+        };
+        emitter.emit(AppEvent::PgQueryFinished {
+            id,
+            entry: Arc::new(entry),
+        });
+
+        let result = result.map(|(responses, stats)| {
+            emitter.emit(AppEvent::PgResponses {
+                id,
+                responses: Arc::new(responses),
+                stats,
+            })
+        });
+        emit_query_result(id, result, &emitter);
+
+        catalog_stale |= changes_catalog(&text);
+
+        if let Some(session) = &session {
+            emitter.emit(AppEvent::PgCursorState {
+                id,
+                more: session.cursor.is_some(),
+            });
+            emitter.emit(AppEvent::PgTransactionStatus {
+                id,
+                status: session.transaction_status,
+                auto_commit,
+            });
+        }
+
+        // the editor is usable again while names are reloaded, statements queue up behind them
+        emitter.emit(AppEvent::PgSessionBusy { id, busy: false });
+
+        if let Some(session) = &session {
+            refresh_catalog_cache(id, session, &mut catalog_stale, &emitter).await;
+        }
+    }
+
+    log::info!("Session {} closed", id);
+}
+
+/// Name of session `id` in the message log
+fn session_name(id: usize) -> String {
+    match id {
+        CATALOG_SESSION => "explorer".into(),
+        IMPORT_SESSION => "import".into(),
+        NOTIFY_SESSION => "notifications".into(),
+        id => format!("session {}", id + 1),
+    }
+}
+
+/// Reports a failed query, successful results are emitted by the caller
+fn emit_query_result(id: usize, result: Result<()>, emitter: &Emitter) {
+    match result {
+        Ok(()) => {}
+        Err(err) if is_query_canceled(&err) => emitter.emit(AppEvent::PgQueryCanceled { id }),
+        Err(err) => match db_error(&err) {
+            Some(db_err) => emitter.emit(AppEvent::PgQueryFailed {
+                id,
+                error: Arc::new(QueryError::from(db_err)),
+            }),
+            None => emitter.emit(AppEvent::Err(err)),
+        },
+    }
+}
+
+/// Fetches further rows of the open cursor, every page is emitted as soon as it arrives
+async fn fetch_rows(
+    id: usize,
+    session: &mut PgSession,
+    count: FetchCount,
+    emitter: &Emitter,
 ) -> Result<()> {
-    let mut sessions: HashMap<usize, PgSession> = HashMap::new();
+    while session.cursor.is_some() {
+        let mut timer = Timer::start();
+        let rows = session.fetch_page(&mut timer).await?;
+        let stats = timer.stats(Some(rows.len() as u64));
+
+        emitter.emit(AppEvent::PgRowsFetched {
+            id,
+            rows: Arc::new(rows),
+            stats,
+        });
 
-    while let Some(PgRequest { id, text }) = receiver.next().await {
-        let session = match sessions.entry(id) {
-            Entry::Occupied(o) => o.into_mut(),
-            Entry::Vacant(v) => {
-                let session = PgSession::initialize(emitter.clone()).await?;
-                v.insert(session)
+        if count == FetchCount::Page {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Opens the connections of a session and reports their state
+struct Connector {
+    id: usize,
+    profile: Arc<ConnectionProfile>,
+    settings: Arc<Settings>,
+    emitter: Emitter,
+    cancel_slot: CancelSlot,
+    /// Receives the number of a connection which broke
+    lost: UnboundedSender<u64>,
+    /// Connections opened so far, numbering them
+    opened: u64,
+}
+
+impl Connector {
+    async fn connect(&mut self, attempt: u32) -> Result<PgSession> {
+        self.emit_state(ConnectionState::Connecting { attempt });
+
+        self.opened += 1;
+        let session = match PgSession::initialize(self).await {
+            Ok(session) => session,
+            Err(err) => {
+                self.emit_state(ConnectionState::Lost);
+                return Err(err);
             }
         };
+        *self.cancel_slot.lock().unwrap() = Some(session.canceller.clone());
 
-        emitter.emit(match session.exec_simple_query(&text).await {
-            Ok(batches) => AppEvent::PgResponses {
+        self.emit_state(ConnectionState::Connected);
+        self.emitter.emit(AppEvent::PgConnected {
+            id: self.id,
+            profile: Arc::clone(&self.profile),
+        });
+
+        Ok(session)
+    }
+
+    fn emit_state(&self, state: ConnectionState) {
+        self.emitter
+            .emit(AppEvent::PgConnectionState { id: self.id, state });
+    }
+}
+
+/// Sends the names known to the completion of session `id` if they are missing or outdated
+async fn refresh_catalog_cache(
+    id: usize,
+    session: &PgSession,
+    catalog_stale: &mut bool,
+    emitter: &Emitter,
+) {
+    if !*catalog_stale || id == CATALOG_SESSION {
+        return;
+    }
+
+    match session.load_catalog_cache().await {
+        Ok(cache) => {
+            *catalog_stale = false;
+            emitter.emit(AppEvent::PgCatalogCache {
                 id,
-                responses: Arc::new(PgResponse::process_batches(batches)),
-            },
-            Err(err) => AppEvent::Err(err),
-        })
+                cache: Arc::new(cache),
+            });
+        }
+        // e.g. within a failed transaction, retried after the next statement
+        Err(err) => log::warn!("Loading catalog of session {} failed: {}", id, err),
+    }
+}
+
+/// Session ready to run a command, opened first or, when its connection broke unnoticed, reported
+/// lost and replaced
+async fn connected<'a>(
+    session: &'a mut Option<PgSession>,
+    connector: &mut Connector,
+    auto_commit: bool,
+) -> Result<&'a mut PgSession> {
+    match session {
+        Some(open) if open.client.is_closed() => {
+            // the notification of the lost connection may still be on its way
+            on_connection_lost(session, connector, auto_commit).await;
+            if !matches!(session, Some(open) if !open.client.is_closed()) {
+                reconnect(session, connector, 1).await?;
+            }
+        }
+        Some(_) => {}
+        None => *session = Some(connector.connect(0).await?),
+    }
+
+    Ok(session.as_mut().unwrap())
+}
+
+/// Replaces the broken connection of `session`, waiting longer after every failed try.
+///
+/// The session keeps its id, and the channels it listened to are listened to again.
+async fn reconnect(
+    session: &mut Option<PgSession>,
+    connector: &mut Connector,
+    attempts: u32,
+) -> Result<()> {
+    let channels = session
+        .take()
+        .map(|session| session.channels)
+        .unwrap_or_default();
+
+    let mut attempt = 1;
+    let new_session = loop {
+        match connector.connect(attempt).await {
+            Ok(new_session) => break new_session,
+            Err(err) if attempt >= attempts || !is_transient(&err) => return Err(err),
+            Err(err) => {
+                let delay = reconnect_delay(attempt);
+                log::info!(
+                    "Reconnecting session {} failed, next try in {:?}: {}",
+                    connector.id,
+                    delay,
+                    err
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    };
+
+    let session = session.insert(new_session);
+    for channel in channels {
+        session.notify(&NotifyCommand::Listen(channel)).await?;
     }
 
     Ok(())
 }
 
+/// Reports the broken connection of `session` and replaces it unless reconnecting is disabled
+async fn on_connection_lost(
+    session: &mut Option<PgSession>,
+    connector: &mut Connector,
+    auto_commit: bool,
+) {
+    let id = connector.id;
+    let emitter = connector.emitter.clone();
+    log::warn!("Connection of session {} was lost", id);
+
+    connector.emit_state(ConnectionState::Lost);
+    if matches!(session, Some(open) if open.transaction_status != TransactionStatus::Idle) {
+        emitter.emit(AppEvent::Err(anyhow!(
+            "Connection of {} was lost, its open transaction was rolled back",
+            session_name(id)
+        )));
+    }
+    emitter.emit(AppEvent::PgTransactionStatus {
+        id,
+        status: TransactionStatus::Idle,
+        auto_commit,
+    });
+    emitter.emit(AppEvent::PgCursorState { id, more: false });
+
+    let attempts = connector.settings.reconnect_attempts;
+    if attempts == 0 {
+        return;
+    }
+
+    if let Err(err) = reconnect(session, connector, attempts).await {
+        log::warn!("Giving up reconnecting session {}: {}", id, err);
+        emitter.emit(AppEvent::Err(err));
+    }
+
+    if id == NOTIFY_SESSION {
+        let channels = session
+            .iter()
+            .flat_map(|session| session.channels.iter().cloned())
+            .collect();
+        emitter.emit(AppEvent::NotifyChannels(Arc::new(channels)));
+    }
+}
+
+/// Failures to connect which may go away, unlike e.g. a changed password
+fn is_transient(err: &anyhow::Error) -> bool {
+    is_transient_state(db_error(err).map(DbError::code))
+}
+
+/// Connection refused for the time being by the server, or not established at all without a
+/// server error
+fn is_transient_state(code: Option<&SqlState>) -> bool {
+    match code {
+        Some(code) => matches!(
+            code,
+            &SqlState::CANNOT_CONNECT_NOW | &SqlState::TOO_MANY_CONNECTIONS
+        ),
+        None => true,
+    }
+}
+
+/// Wait before the next try to reconnect, doubled after every try
+fn reconnect_delay(attempt: u32) -> Duration {
+    let delay = Duration::from_secs(1) * 2u32.saturating_pow(attempt.saturating_sub(1));
+    delay.min(MAX_RECONNECT_DELAY)
+}
+
+fn db_error(err: &anyhow::Error) -> Option<&DbError> {
+    err.downcast_ref::<tokio_postgres::Error>()
+        .and_then(tokio_postgres::Error::as_db_error)
+}
+
+/// Message of a server error without its severity, or the whole error otherwise
+fn error_message(err: &anyhow::Error) -> String {
+    match db_error(err) {
+        Some(db_err) => db_err.message().to_string(),
+        None => err.to_string(),
+    }
+}
+
+fn is_query_canceled(err: &anyhow::Error) -> bool {
+    let code = err
+        .downcast_ref::<tokio_postgres::Error>()
+        .and_then(|err| err.code());
+
+    code == Some(&SqlState::QUERY_CANCELED)
+}
+
+/// Statements after which names known to the completion have to be reloaded
+fn changes_catalog(text: &str) -> bool {
+    split_statements(text).into_iter().any(|range| {
+        matches!(
+            leading_keyword(&text[range]).as_deref(),
+            Some("create") | Some("alter") | Some("drop")
+        )
+    })
+}
+
+/// Transaction status after running `text` from `status`, told from the transaction control
+/// statements in it.
+///
+/// `None` when `text` failed and controls transactions itself, as it is not known which of its
+/// statements failed.
+fn status_after(status: TransactionStatus, text: &str, failed: bool) -> Option<TransactionStatus> {
+    let mut controls = false;
+    let mut after = status;
+
+    for range in split_statements(text) {
+        let statement = &text[range];
+        let words = statement
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|word| !word.is_empty())
+            .map(str::to_ascii_lowercase)
+            .collect::<Vec<_>>();
+        // `AND CHAIN` starts a new transaction, `ROLLBACK TO` keeps the current one
+        let chains = words.iter().any(|word| word == "chain") && !words.iter().any(|w| w == "no");
+
+        after = match words.first().map(String::as_str) {
+            Some("begin") | Some("start") => TransactionStatus::InTransaction,
+            Some("commit") | Some("end") | Some("abort") if chains => {
+                TransactionStatus::InTransaction
+            }
+            Some("rollback") if chains || words.iter().any(|word| word == "to") => {
+                TransactionStatus::InTransaction
+            }
+            Some("commit") | Some("end") | Some("abort") | Some("rollback") => {
+                TransactionStatus::Idle
+            }
+            Some("prepare") if words.get(1).map(String::as_str) == Some("transaction") => {
+                TransactionStatus::Idle
+            }
+            _ => continue,
+        };
+        controls = true;
+    }
+
+    match (failed, status) {
+        (false, _) => Some(after),
+        (true, _) if controls => None,
+        // the implicit transaction of the statement was rolled back
+        (true, TransactionStatus::Idle) => Some(TransactionStatus::Idle),
+        (true, _) => Some(TransactionStatus::Failed),
+    }
+}
+
+/// Statements which can be run through a cursor and fetched in pages
+fn is_cursor_query(text: &str) -> bool {
+    match leading_keyword(text).as_deref() {
+        Some("select") | Some("values") | Some("table") => true,
+        // cursors reject data modifying statements in WITH
+        Some("with") => !text
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .any(|word| {
+                ["insert", "update", "delete"]
+                    .iter()
+                    .any(|keyword| word.eq_ignore_ascii_case(keyword))
+            }),
+        _ => false,
+    }
+}
+
+fn is_multiple_commands(err: &tokio_postgres::Error) -> bool {
+    matches!(
+        err.as_db_error(),
+        Some(err) if err.code() == &SqlState::SYNTAX_ERROR && err.message().contains("multiple commands")
+    )
+}
+
+/// Sends cancel requests for the query currently running in a session
+#[derive(Clone)]
+pub struct QueryCanceller {
+    token: CancelToken,
+    tls: MakeTlsConnector,
+}
+
+impl QueryCanceller {
+    pub async fn cancel(self) -> Result<()> {
+        self.token.cancel_query(self.tls).await?;
+
+        Ok(())
+    }
+}
+
+/// Server side cursor over the result of the last query
+struct Cursor {
+    fetch: Statement,
+    fetch_size: usize,
+    /// Transaction opened only to hold the cursor in auto-commit mode
+    implicit_transaction: bool,
+    /// Pages are fetched in text format, as some column has no binary decoding
+    text_format: bool,
+}
+
+/// Collects rows of `stream`, noting the arrival of the first one in `timer`
+async fn collect_rows(
+    stream: RowStream,
+    timer: &mut Timer,
+) -> Result<Vec<Row>, tokio_postgres::Error> {
+    pin_mut!(stream);
+
+    let mut rows = vec![];
+    while let Some(row) = stream.try_next().await? {
+        timer.row_streamed();
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+/// Measures a request or a fetched page, see `QueryStats`
+struct Timer {
+    started: Instant,
+    first_row: Option<Instant>,
+    /// Rows arrived one by one rather than all at once
+    streamed: bool,
+}
+
+impl Timer {
+    fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            first_row: None,
+            streamed: false,
+        }
+    }
+
+    fn row_streamed(&mut self) {
+        self.first_row.get_or_insert_with(Instant::now);
+        self.streamed = true;
+    }
+
+    /// All rows arrived at once, as with the simple query protocol
+    fn rows_received(&mut self) {
+        self.first_row.get_or_insert_with(Instant::now);
+    }
+
+    fn stats(&self, rows: Option<u64>) -> QueryStats {
+        let finished = Instant::now();
+        let first_row = self.first_row.unwrap_or(finished);
+
+        QueryStats {
+            first_row: first_row - self.started,
+            transfer: if self.streamed {
+                Some(finished - first_row)
+            } else {
+                None
+            },
+            rows,
+        }
+    }
+}
+
 pub struct PgSession {
     client: Client,
+    canceller: QueryCanceller,
+    /// Number of the connection within its session, see `Connector`
+    connection: u64,
+    transaction_status: TransactionStatus,
+    cursor: Option<Cursor>,
+    /// Channels listened to through the notifications panel
+    channels: BTreeSet<String>,
+    last_query: LastQuery,
 }
 
 impl PgSession {
-    async fn initialize(emitter: Emitter) -> Result<Self> {
-        let mut cfg = Config::new();
-        cfg.host(env::var("PG_HOST").as_deref().unwrap_or("localhost"));
-        cfg.port(env::var("PG_PORT").as_deref().unwrap_or("5432").parse()?);
-        cfg.user(env::var("PG_USER").as_deref().unwrap_or("postgres"));
-        cfg.dbname(env::var("PG_DBNAME").as_deref().unwrap_or("disputandum"));
-        if let Ok(pg_pass) = env::var("PG_PASS") {
-            cfg.password(&pg_pass);
-        }
+    /// Opens connection number `connector.opened`, whose task drives it and forwards the
+    /// messages the server sends. The number is sent to `connector.lost` if it breaks.
+    async fn initialize(connector: &Connector) -> Result<Self> {
+        let profile = &connector.profile;
+        log::info!("Opening session for profile '{}'", profile.name);
 
-        let (client, mut conn) = cfg.connect(NoTls).await?;
-        let (tx, mut rx) = unbounded::<AsyncMessage>();
+        let tls = make_tls_connector(profile)?;
+        let mut config = profile.pg_config();
+        connector.settings.configure(&mut config);
+        let (client, mut conn) = config.connect(tls.clone()).await?;
+        let canceller = QueryCanceller {
+            token: client.cancel_token(),
+            tls,
+        };
 
-        let stream = stream::poll_fn(move |cx| conn.poll_message(cx)).map_err(|e| panic!("{}", e));
-        let connection = stream.forward(tx);
+        let last_query = LastQuery::default();
+        let query = Arc::clone(&last_query);
+        let id = connector.id;
+        let connection = connector.opened;
+        let emitter = connector.emitter.clone();
+        let lost = connector.lost.clone();
 
-        tokio::spawn(connection);
         tokio::spawn(async move {
-            while let Some(async_msg) = rx.next().await {
-                emitter.emit(AppEvent::PgMessage(Box::new(async_msg)))
+            let messages = stream::poll_fn(move |cx| conn.poll_message(cx));
+            pin_mut!(messages);
+
+            // the stream ends without an error once the client is dropped
+            while let Some(async_msg) = messages.next().await {
+                match async_msg {
+                    Ok(AsyncMessage::Notice(notice)) => {
+                        let query = query.lock().unwrap().clone();
+                        let message = ServerMessage::new(session_name(id), query, &notice);
+                        emitter.emit(AppEvent::PgMessage(Arc::new(message)))
+                    }
+                    Ok(AsyncMessage::Notification(message)) => emitter.emit(
+                        AppEvent::PgNotification(Arc::new(Notification::new(&message))),
+                    ),
+                    Ok(_) => {}
+                    Err(err) => {
+                        log::warn!("Connection of session {} broke: {}", id, err);
+                        lost.unbounded_send(connection).ok();
+                        break;
+                    }
+                }
             }
         });
 
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            canceller,
+            connection,
+            transaction_status: TransactionStatus::Idle,
+            cursor: None,
+            channels: BTreeSet::new(),
+            last_query,
+        })
+    }
+
+    /// Executes `text`. Unless `auto_commit` is set, a transaction is opened first when
+    /// there is none. `COPY` statements exchanging data with the client read or write
+    /// `copy_file`.
+    ///
+    /// A previously open cursor is closed, so only the latest result can be fetched further.
+    async fn execute(
+        &mut self,
+        text: &str,
+        params: &[QueryParam],
+        copy_file: Option<&Path>,
+        auto_commit: bool,
+        fetch_size: usize,
+    ) -> Result<(Vec<PgResponse>, QueryStats)> {
+        self.close_cursor().await?;
+        *self.last_query.lock().unwrap() = Some(text.to_string());
+        let mut timer = Timer::start();
+
+        let begin = self.transaction_status == TransactionStatus::Idle;
+        if !auto_commit && begin {
+            self.client.batch_execute("BEGIN").await?;
+            self.transaction_status = TransactionStatus::InTransaction;
+        }
+
+        let result = match CopyStatement::parse(text) {
+            Some(copy) if copy.direction == CopyDirection::ToStdout => {
+                self.copy_out(text, &copy, copy_file, &mut timer).await
+            }
+            Some(_) => self.copy_in(text, copy_file).await,
+            None => {
+                let implicit_transaction = auto_commit && begin;
+                self.query_typed(text, params, implicit_transaction, fetch_size, &mut timer)
+                    .await
+            }
+        };
+        let rows = result.as_deref().ok().and_then(PgResponse::row_count);
+        let stats = timer.stats(rows);
+        self.transaction_status = match status_after(self.transaction_status, text, result.is_err())
+        {
+            // the transaction holding the cursor stays open until the cursor is closed
+            _ if self.has_implicit_transaction() => TransactionStatus::InTransaction,
+            Some(status) => status,
+            // the error of the statement is what gets reported, not that of the probe
+            None => self.probe_transaction_status().await.unwrap_or_else(|err| {
+                log::warn!("Reading the transaction status failed: {}", err);
+                TransactionStatus::Failed
+            }),
+        };
+
+        result.map(|responses| (responses, stats))
+    }
+
+    /// Runs `text` with the extended query protocol, so that values come back in binary
+    /// format together with their types.
+    ///
+    /// The extended protocol accepts a single statement only, scripts without parameters fall
+    /// back to the simple protocol and are reported as text. Queries are fetched through a cursor
+    /// page by page, `implicit_transaction` opens a transaction block to hold it.
+    ///
+    /// Parameters are bound separately from the statement text, never interpolated into it.
+    async fn query_typed(
+        &mut self,
+        text: &str,
+        params: &[QueryParam],
+        implicit_transaction: bool,
+        fetch_size: usize,
+        timer: &mut Timer,
+    ) -> Result<Vec<PgResponse>> {
+        let types = params
+            .iter()
+            .map(QueryParam::declared_type)
+            .collect::<Vec<_>>();
+
+        let statement = match self.client.prepare_typed(text, &types).await {
+            Ok(statement) => statement,
+            Err(err) if params.is_empty() && is_multiple_commands(&err) => {
+                let batches = self.client.simple_query(text).await?;
+                timer.rows_received();
+                return Ok(PgResponse::process_batches(batches));
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let columns = statement.columns();
+        if columns.is_empty() || !is_cursor_query(text) {
+            if params.is_empty() && !pg_response::decodes_binary(columns) {
+                // the server prints values of these types itself
+                let messages = self.client.simple_query(text).await?;
+                timer.rows_received();
+
+                let rows = pg_response::decode_text_rows(columns, &messages);
+                let rows_affected = messages.iter().find_map(|message| match message {
+                    SimpleQueryMessage::CommandComplete(rows) => Some(*rows),
+                    _ => None,
+                });
+                return Ok(PgResponse::from_rows(columns, rows, rows_affected));
+            }
+
+            let stream = self.client.query_raw(&statement, params.iter()).await?;
+            pin_mut!(stream);
+
+            let mut rows = vec![];
+            while let Some(row) = stream.try_next().await? {
+                timer.row_streamed();
+                rows.push(row);
+            }
+
+            let rows = pg_response::decode_rows(&rows)?;
+            return Ok(PgResponse::from_rows(columns, rows, stream.rows_affected()));
+        }
+
+        if implicit_transaction {
+            self.client.batch_execute("BEGIN").await?;
+        }
+
+        let fetch = match self.declare_cursor(text, &types, params, fetch_size).await {
+            Ok(fetch) => fetch,
+            Err(err) => {
+                if implicit_transaction {
+                    self.client.batch_execute("ROLLBACK").await?;
+                }
+                return Err(err);
+            }
+        };
+
+        self.cursor = Some(Cursor {
+            fetch: fetch.clone(),
+            fetch_size,
+            implicit_transaction,
+            text_format: !pg_response::decodes_binary(fetch.columns()),
+        });
+
+        let rows = self.fetch_page(timer).await?;
+
+        Ok(PgResponse::from_rows(fetch.columns(), rows, None))
+    }
+
+    /// Runs `COPY ... TO STDOUT`, writing its output to `file` or reading it as a table
+    async fn copy_out(
+        &mut self,
+        text: &str,
+        copy: &CopyStatement,
+        file: Option<&Path>,
+        timer: &mut Timer,
+    ) -> Result<Vec<PgResponse>> {
+        // an interrupted copy leaves an existing file as it was
+        let mut out = match file {
+            Some(path) => Some(PartialFile::create(path)?),
+            None => None,
+        };
+
+        let stream = self.client.copy_out(text).await?;
+        pin_mut!(stream);
+
+        let mut data = vec![];
+        let mut messages = 0;
+        while let Some(chunk) = stream.try_next().await? {
+            timer.row_streamed();
+            messages += 1;
+
+            match &mut out {
+                Some(file) => block_in_place(|| file.write_all(&chunk))?,
+                None => data.extend_from_slice(&chunk),
+            }
+        }
+
+        match out {
+            Some(file) => {
+                file.persist()?;
+                Ok(vec![PgResponse::CommandComplete(
+                    copy.output_rows(messages),
+                )])
+            }
+            None => Ok(vec![PgResponse::Table(copy.decode(&data)?)]),
+        }
+    }
+
+    /// Runs `COPY ... FROM STDIN`, sending the content of `file` as it is
+    async fn copy_in(&mut self, text: &str, file: Option<&Path>) -> Result<Vec<PgResponse>> {
+        const CHUNK_SIZE: usize = 64 * 1024;
+
+        let path = file.ok_or_else(|| anyhow!("COPY FROM STDIN needs a local file to read"))?;
+        let mut file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+
+        let sink = self.client.copy_in::<_, Bytes>(text).await?;
+        pin_mut!(sink);
+
+        // dropping the sink on a read error aborts the copy
+        let mut buffer = vec![0; CHUNK_SIZE];
+        loop {
+            let read = block_in_place(|| file.read(&mut buffer))?;
+            if read == 0 {
+                break;
+            }
+            sink.send(Bytes::copy_from_slice(&buffer[..read])).await?;
+        }
+
+        let rows = sink.as_mut().finish().await?;
+
+        Ok(vec![PgResponse::CommandComplete(rows)])
+    }
+
+    async fn notify(&mut self, command: &NotifyCommand) -> Result<()> {
+        match command {
+            NotifyCommand::Listen(channel) => {
+                self.client
+                    .batch_execute(&notification::listen_sql(channel))
+                    .await?;
+                self.channels.insert(channel.clone());
+            }
+            NotifyCommand::Unlisten(channel) => {
+                self.client
+                    .batch_execute(&notification::unlisten_sql(channel))
+                    .await?;
+                self.channels.remove(channel);
+            }
+            NotifyCommand::Notify { channel, payload } => {
+                self.client
+                    .execute("SELECT pg_notify($1, $2)", &[channel, payload])
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn load_catalog(&self, query: &CatalogQuery) -> Result<Vec<CatalogObject>> {
+        let params = query.params();
+        let params = params
+            .iter()
+            .map(|param| param as &(dyn ToSql + Sync))
+            .collect::<Vec<_>>();
+
+        let rows = self.client.query(query.sql().as_str(), &params).await?;
+
+        let objects = rows
+            .iter()
+            .map(|row| query.object(row.get(0), row.get(1), row.get(2), row.get(3), row.get(4)))
+            .collect();
+
+        Ok(objects)
+    }
+
+    async fn load_catalog_cache(&self) -> Result<CatalogCache> {
+        let mut cache = CatalogCache::default();
+
+        for row in self.client.query(completion::SCHEMAS_SQL, &[]).await? {
+            cache.schemas.push(row.get(0));
+        }
+
+        for row in self.client.query(completion::SEARCH_PATH_SQL, &[]).await? {
+            cache.search_path.push(row.get(0));
+        }
+
+        for row in self.client.query(completion::RELATIONS_SQL, &[]).await? {
+            let column = match (row.get(2), row.get(3)) {
+                (Some(name), Some(ty)) => Some((name, ty)),
+                _ => None,
+            };
+            cache.push_relation_column(row.get(0), row.get(1), column);
+        }
+
+        for row in self.client.query(completion::FUNCTIONS_SQL, &[]).await? {
+            cache.functions.push(completion::FunctionInfo {
+                schema: row.get(0),
+                name: row.get(1),
+                arguments: row.get(2),
+                // procedures have no result
+                result: row.get::<_, Option<String>>(3).unwrap_or_default(),
+            });
+        }
+
+        Ok(cache)
+    }
+
+    /// Generates the DDL of `target`, returns it with the name of the object
+    async fn generate_ddl(&self, target: &DdlTarget) -> Result<(String, String)> {
+        let object = match target {
+            DdlTarget::Object(object) => object.clone(),
+            DdlTarget::Name(name) => ddl::resolve(&self.client, name).await?,
+        };
+
+        let text = ddl::generate(&self.client, &object).await?;
+
+        Ok((object.qualified_name(), text))
+    }
+
+    /// Declares the cursor over `text` and prepares the statement fetching its pages
+    async fn declare_cursor(
+        &self,
+        text: &str,
+        types: &[tokio_postgres::types::Type],
+        params: &[QueryParam],
+        fetch_size: usize,
+    ) -> Result<Statement> {
+        let declare = format!("DECLARE {} NO SCROLL CURSOR FOR {}", CURSOR_NAME, text);
+        let declare = self.client.prepare_typed(&declare, types).await?;
+        self.client.execute_raw(&declare, params.iter()).await?;
+
+        let fetch = format!("FETCH FORWARD {} FROM {}", fetch_size, CURSOR_NAME);
+        Ok(self.client.prepare(&fetch).await?)
+    }
+
+    /// Fetches the next page of the open cursor, the cursor is closed once it is exhausted
+    async fn fetch_page(&mut self, timer: &mut Timer) -> Result<Vec<pg_response::Row>> {
+        let cursor = match &self.cursor {
+            Some(cursor) => cursor,
+            None => return Ok(vec![]),
+        };
+        let fetch_size = cursor.fetch_size;
+
+        let result = if cursor.text_format {
+            let fetch = format!("FETCH FORWARD {} FROM {}", fetch_size, CURSOR_NAME);
+            match self.client.simple_query(&fetch).await {
+                Ok(messages) => {
+                    timer.rows_received();
+                    Ok(pg_response::decode_text_rows(
+                        cursor.fetch.columns(),
+                        &messages,
+                    ))
+                }
+                Err(err) => Err(err),
+            }
+        } else {
+            let result = self
+                .client
+                .query_raw(&cursor.fetch, std::iter::empty::<&(dyn ToSql + Sync)>())
+                .await;
+            match result {
+                Ok(stream) => collect_rows(stream, timer)
+                    .await
+                    .and_then(|rows| pg_response::decode_rows(&rows)),
+                Err(err) => Err(err),
+            }
+        };
+
+        match result {
+            Ok(rows) if rows.len() < fetch_size => {
+                self.close_cursor().await?;
+                Ok(rows)
+            }
+            Ok(rows) => Ok(rows),
+            Err(err) => {
+                // the error aborted the transaction holding the cursor
+                match self.cursor.take() {
+                    Some(Cursor {
+                        implicit_transaction: true,
+                        ..
+                    }) => {
+                        self.client.batch_execute("ROLLBACK").await?;
+                        self.transaction_status = TransactionStatus::Idle;
+                    }
+                    _ => self.transaction_status = TransactionStatus::Failed,
+                }
+                Err(err.into())
+            }
+        }
     }
 
-    async fn exec_simple_query(&mut self, text: &str) -> Result<Vec<SimpleQueryMessage>> {
-        let tx = self.client.transaction().await?;
-        let batches = tx.simple_query(text).await?;
+    async fn close_cursor(&mut self) -> Result<()> {
+        match self.cursor.take() {
+            Some(cursor) if cursor.implicit_transaction => {
+                self.client.batch_execute("COMMIT").await?;
+                self.transaction_status = TransactionStatus::Idle;
+            }
+            // an aborted transaction dropped the cursor already
+            Some(_) if self.transaction_status == TransactionStatus::Failed => {}
+            Some(_) => {
+                self.client
+                    .batch_execute(&format!("CLOSE {}", CURSOR_NAME))
+                    .await?
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// Whether a transaction was opened in auto-commit mode only to hold the cursor
+    fn has_implicit_transaction(&self) -> bool {
+        matches!(
+            self.cursor,
+            Some(Cursor {
+                implicit_transaction: true,
+                ..
+            })
+        )
+    }
+
+    async fn end_transaction(&mut self, commit: bool) -> Result<()> {
+        // ending the transaction closes the cursor as well
+        self.cursor = None;
+
+        // COMMIT of a failed transaction is turned into a rollback by the server, and a COMMIT
+        // failing e.g. on a deferred constraint ends the transaction as well
+        let result = self
+            .client
+            .batch_execute(if commit { "COMMIT" } else { "ROLLBACK" })
+            .await;
+        self.transaction_status = TransactionStatus::Idle;
 
-        tx.commit().await?;
+        Ok(result?)
+    }
+
+    /// Asks the server whether the session is inside a transaction block, for when it cannot
+    /// be told from the statements run, see `status_after`.
+    ///
+    /// Within a block `now()` is frozen at the transaction start, outside of it the probe runs
+    /// in its own transaction and both timestamps are equal.
+    async fn probe_transaction_status(&self) -> Result<TransactionStatus> {
+        let probe = self
+            .client
+            .simple_query("SELECT now() <> statement_timestamp()")
+            .await;
+
+        match probe {
+            Ok(messages) => {
+                let in_transaction = messages.iter().any(|msg| match msg {
+                    SimpleQueryMessage::Row(row) => row.get(0) == Some("t"),
+                    _ => false,
+                });
+
+                Ok(if in_transaction {
+                    TransactionStatus::InTransaction
+                } else {
+                    TransactionStatus::Idle
+                })
+            }
+            Err(err) if err.code() == Some(&SqlState::IN_FAILED_SQL_TRANSACTION) => {
+                Ok(TransactionStatus::Failed)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn transaction_status_from_statements() {
+        use TransactionStatus::*;
+
+        assert_eq!(status_after(Idle, "select 1", false), Some(Idle));
+        assert_eq!(
+            status_after(Idle, "begin; insert into t values (1)", false),
+            Some(InTransaction)
+        );
+        assert_eq!(
+            status_after(InTransaction, "update t set a = 1", false),
+            Some(InTransaction)
+        );
+        assert_eq!(status_after(InTransaction, "COMMIT", false), Some(Idle));
+        assert_eq!(
+            status_after(InTransaction, "commit and chain", false),
+            Some(InTransaction)
+        );
+        assert_eq!(
+            status_after(Failed, "rollback to savepoint s", false),
+            Some(InTransaction)
+        );
+        assert_eq!(status_after(Failed, "rollback", false), Some(Idle));
+        assert_eq!(
+            status_after(InTransaction, "prepare transaction 'tx'", false),
+            Some(Idle)
+        );
+    }
+
+    #[test]
+    fn transaction_status_after_failures() {
+        use TransactionStatus::*;
+
+        assert_eq!(status_after(Idle, "select 1/0", true), Some(Idle));
+        assert_eq!(
+            status_after(InTransaction, "select 1/0", true),
+            Some(Failed)
+        );
+        assert_eq!(status_after(Failed, "select 1", true), Some(Failed));
+        // not known whether the failure came before or after BEGIN
+        assert_eq!(status_after(Idle, "select 1/0; begin", true), None);
+    }
+
+    #[test]
+    fn transfer_measured_for_streamed_rows_only() {
+        let mut timer = Timer::start();
+        timer.rows_received();
+        assert_eq!(timer.stats(Some(3)).transfer, None);
+
+        let mut timer = Timer::start();
+        timer.row_streamed();
+        timer.row_streamed();
+        let stats = timer.stats(Some(2));
+        assert!(stats.transfer.is_some());
+        assert_eq!(stats.rows, Some(2));
+    }
+
+    #[test]
+    fn reconnect_delay_doubles_up_to_cap() {
+        assert_eq!(reconnect_delay(1), Duration::from_secs(1));
+        assert_eq!(reconnect_delay(2), Duration::from_secs(2));
+        assert_eq!(reconnect_delay(4), Duration::from_secs(8));
+        assert_eq!(reconnect_delay(6), MAX_RECONNECT_DELAY);
+        assert_eq!(reconnect_delay(u32::MAX), MAX_RECONNECT_DELAY);
+    }
 
-        Ok(batches)
+    #[test]
+    fn transient_connection_failures() {
+        assert!(is_transient(&anyhow!("connection refused")));
+        assert!(is_transient_state(None));
+        assert!(is_transient_state(Some(&SqlState::CANNOT_CONNECT_NOW)));
+        assert!(is_transient_state(Some(&SqlState::TOO_MANY_CONNECTIONS)));
+        assert!(!is_transient_state(Some(&SqlState::INVALID_PASSWORD)));
+        assert!(!is_transient_state(Some(&SqlState::INVALID_CATALOG_NAME)));
     }
 }
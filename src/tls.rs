@@ -0,0 +1,114 @@
+use std::fs;
+
+use anyhow::{bail, Context, Result};
+use native_tls::{Certificate, Identity, TlsConnector};
+use postgres_native_tls::MakeTlsConnector;
+
+use crate::model::connection_profile::{ConnectionProfile, SslMode};
+
+/// Builds TLS connector honoring the profile's `sslmode` and certificate settings, see
+/// [`verification`]
+pub fn make_tls_connector(profile: &ConnectionProfile) -> Result<MakeTlsConnector> {
+    let mut builder = TlsConnector::builder();
+
+    if let Some(path) = &profile.sslrootcert {
+        let pem = fs::read(path)
+            .with_context(|| format!("Failed to read sslrootcert {}", path.display()))?;
+        builder.add_root_certificate(Certificate::from_pem(&pem)?);
+    }
+
+    match (&profile.sslcert, &profile.sslkey) {
+        (Some(cert_path), Some(key_path)) => {
+            let cert = fs::read(cert_path)
+                .with_context(|| format!("Failed to read sslcert {}", cert_path.display()))?;
+            let key = fs::read(key_path)
+                .with_context(|| format!("Failed to read sslkey {}", key_path.display()))?;
+            builder.identity(Identity::from_pkcs8(&cert, &key)?);
+        }
+        (None, None) => {}
+        _ => bail!("sslcert and sslkey have to be provided together"),
+    }
+
+    let (verify_cert, verify_hostname) = verification(profile);
+    builder.danger_accept_invalid_certs(!verify_cert);
+    builder.danger_accept_invalid_hostnames(!verify_hostname);
+
+    Ok(MakeTlsConnector::new(builder.build()?))
+}
+
+/// Whether the server certificate and its host name are verified.
+///
+/// Mirrors libpq: `prefer` and `require` skip certificate verification, except `require` with
+/// a root certificate configured, which behaves like `verify-ca`.
+fn verification(profile: &ConnectionProfile) -> (bool, bool) {
+    match profile.sslmode {
+        SslMode::Disable | SslMode::Prefer => (false, false),
+        SslMode::Require => (profile.sslrootcert.is_some(), false),
+        SslMode::VerifyCa => (true, false),
+        SslMode::VerifyFull => (true, true),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn profile(sslmode: SslMode) -> ConnectionProfile {
+        ConnectionProfile {
+            sslmode,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn verification_by_sslmode() {
+        assert_eq!(verification(&profile(SslMode::Disable)), (false, false));
+        assert_eq!(verification(&profile(SslMode::Prefer)), (false, false));
+        assert_eq!(verification(&profile(SslMode::Require)), (false, false));
+        assert_eq!(verification(&profile(SslMode::VerifyCa)), (true, false));
+        assert_eq!(verification(&profile(SslMode::VerifyFull)), (true, true));
+    }
+
+    #[test]
+    fn require_with_rootcert_verifies_ca() {
+        let with_rootcert = |sslmode| ConnectionProfile {
+            sslrootcert: Some(PathBuf::from("root.crt")),
+            ..profile(sslmode)
+        };
+
+        assert_eq!(
+            verification(&with_rootcert(SslMode::Require)),
+            (true, false)
+        );
+        assert_eq!(
+            verification(&with_rootcert(SslMode::Prefer)),
+            (false, false)
+        );
+        assert_eq!(
+            verification(&with_rootcert(SslMode::VerifyFull)),
+            (true, true)
+        );
+    }
+
+    #[test]
+    fn client_certificate_needs_key() {
+        let cert_only = ConnectionProfile {
+            sslcert: Some(PathBuf::from("client.crt")),
+            ..profile(SslMode::Require)
+        };
+        let key_only = ConnectionProfile {
+            sslkey: Some(PathBuf::from("client.key")),
+            ..profile(SslMode::Require)
+        };
+
+        for profile in [cert_only, key_only].iter() {
+            let err = make_tls_connector(profile).err().unwrap();
+            assert_eq!(
+                err.to_string(),
+                "sslcert and sslkey have to be provided together"
+            );
+        }
+    }
+}
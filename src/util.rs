@@ -0,0 +1,173 @@
+//! Helpers shared by models and widgets
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+
+/// Short human readable duration, e.g. `850 ms`, `12.4 s` or `3 min 05 s`
+pub fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+
+    if millis < 1000 {
+        format!("{} ms", millis)
+    } else if millis < 60_000 {
+        format!("{:.1} s", duration.as_secs_f64())
+    } else {
+        let secs = duration.as_secs();
+        format!("{} min {:02} s", secs / 60, secs % 60)
+    }
+}
+
+/// File written under a temporary name next to its path, which it replaces on `persist`. Dropped
+/// before that, the temporary file is removed and whatever was at the path stays.
+pub struct PartialFile {
+    file: File,
+    temp: PathBuf,
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl PartialFile {
+    pub fn create(path: &Path) -> Result<Self> {
+        Self::open(path, false)
+    }
+
+    /// Like `create`, but only the owner may read and write the file, for files holding secrets
+    pub fn create_private(path: &Path) -> Result<Self> {
+        Self::open(path, true)
+    }
+
+    fn open(path: &Path, private: bool) -> Result<Self> {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".part");
+        let temp = path.with_file_name(name);
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        if private {
+            // the mode is given to new files only, not to a leftover of an interrupted write
+            let _ = fs::remove_file(&temp);
+            owner_only(&mut options);
+        }
+        let file = options
+            .open(&temp)
+            .with_context(|| format!("Failed to create {}", temp.display()))?;
+
+        Ok(Self {
+            file,
+            temp,
+            path: path.to_path_buf(),
+            persisted: false,
+        })
+    }
+
+    pub fn persist(mut self) -> Result<()> {
+        self.file.flush()?;
+        fs::rename(&self.temp, &self.path)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        self.persisted = true;
+
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn owner_only(options: &mut OpenOptions) {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    options.mode(0o600);
+}
+
+#[cfg(not(unix))]
+fn owner_only(_: &mut OpenOptions) {}
+
+impl Write for PartialFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// Directory of a single test, removed with its content when dropped, even if the test fails
+#[cfg(test)]
+pub struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    /// Creates a directory named after `name`, unique to the process and the call
+    pub fn new(name: &str) -> Self {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static CREATED: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "slonik-{}-{}-{}",
+            name,
+            std::process::id(),
+            CREATED.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(Duration::from_millis(850)), "850 ms");
+        assert_eq!(format_duration(Duration::from_millis(12_400)), "12.4 s");
+        assert_eq!(format_duration(Duration::from_secs(185)), "3 min 05 s");
+    }
+
+    #[test]
+    fn partial_file_replaces_path_on_persist() {
+        let dir = TempDir::new("util");
+        let path = dir.join("out.csv");
+        fs::write(&path, "old").unwrap();
+
+        let mut file = PartialFile::create(&path).unwrap();
+        file.write_all(b"new").unwrap();
+        drop(file);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+        let mut file = PartialFile::create(&path).unwrap();
+        file.write_all(b"new").unwrap();
+        file.persist().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -1,9 +1,30 @@
+mod completion;
+mod connection_picker;
+mod copy_dialog;
 mod editor;
+mod editor_tabs;
+mod error_panel;
+mod explorer;
+mod exporter;
+mod history;
+mod importer;
 mod main_window;
 mod messages;
+mod notifications;
 mod output;
+mod params_dialog;
 
+pub use connection_picker::*;
+pub use copy_dialog::*;
 pub use editor::*;
+pub use editor_tabs::*;
+pub use error_panel::*;
+pub use explorer::*;
+pub use exporter::*;
+pub use history::*;
+pub use importer::*;
 pub use main_window::*;
 pub use messages::*;
+pub use notifications::*;
 pub use output::*;
+pub use params_dialog::*;
@@ -0,0 +1,207 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use gtk4::{gio, glib, prelude::*, subclass::prelude::*};
+use sourceview5::prelude::*;
+use sourceview5::subclass::prelude::*;
+
+use crate::model::completion::{complete, CatalogCache, Proposal, ProposalKind};
+
+mod imp {
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    pub struct SqlProposal {
+        pub proposal: RefCell<Option<Proposal>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SqlProposal {
+        const NAME: &'static str = "SlonikSqlProposal";
+        type Type = super::SqlProposal;
+        type ParentType = glib::Object;
+        type Interfaces = (sourceview5::CompletionProposal,);
+    }
+
+    impl ObjectImpl for SqlProposal {}
+    impl CompletionProposalImpl for SqlProposal {}
+
+    #[derive(Debug, Default)]
+    pub struct SqlCompletionProvider {
+        pub cache: RefCell<Arc<CatalogCache>>,
+        /// Character offset of the word being completed
+        pub word_start: Cell<i32>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SqlCompletionProvider {
+        const NAME: &'static str = "SlonikSqlCompletionProvider";
+        type Type = super::SqlCompletionProvider;
+        type ParentType = glib::Object;
+        type Interfaces = (sourceview5::CompletionProvider,);
+    }
+
+    impl ObjectImpl for SqlCompletionProvider {}
+
+    impl CompletionProviderImpl for SqlCompletionProvider {
+        fn title(&self, _provider: &Self::Type) -> Option<glib::GString> {
+            Some("SQL".into())
+        }
+
+        fn is_trigger(&self, _provider: &Self::Type, _iter: &gtk4::TextIter, c: char) -> bool {
+            c == '.'
+        }
+
+        fn populate_future(
+            &self,
+            provider: &Self::Type,
+            context: &sourceview5::CompletionContext,
+        ) -> Pin<Box<dyn Future<Output = Result<gio::ListModel, glib::Error>> + 'static>> {
+            let store = gio::ListStore::new(super::SqlProposal::static_type());
+            provider.fill(&store, context);
+
+            Box::pin(async move { Ok(store.upcast::<gio::ListModel>()) })
+        }
+
+        fn refilter(
+            &self,
+            provider: &Self::Type,
+            context: &sourceview5::CompletionContext,
+            model: &gio::ListModel,
+        ) {
+            if let Some(store) = model.downcast_ref::<gio::ListStore>() {
+                provider.fill(store, context);
+            }
+        }
+
+        fn display(
+            &self,
+            _provider: &Self::Type,
+            _context: &sourceview5::CompletionContext,
+            proposal: &sourceview5::CompletionProposal,
+            cell: &sourceview5::CompletionCell,
+        ) {
+            let proposal = match proposal.downcast_ref::<super::SqlProposal>() {
+                Some(proposal) => proposal.proposal(),
+                None => return,
+            };
+
+            match cell.column() {
+                sourceview5::CompletionColumn::Icon => cell.set_icon_name(icon_name(proposal.kind)),
+                sourceview5::CompletionColumn::TypedText => cell.set_text(Some(&proposal.label)),
+                sourceview5::CompletionColumn::After => cell.set_text(proposal.detail.as_deref()),
+                _ => cell.set_text(None),
+            }
+        }
+
+        fn activate(
+            &self,
+            _provider: &Self::Type,
+            context: &sourceview5::CompletionContext,
+            proposal: &sourceview5::CompletionProposal,
+        ) {
+            let (proposal, buffer) = match (
+                proposal.downcast_ref::<super::SqlProposal>(),
+                context.buffer(),
+            ) {
+                (Some(proposal), Some(buffer)) => (proposal.proposal(), buffer),
+                _ => return,
+            };
+
+            let mut begin = buffer.iter_at_offset(self.word_start.get());
+            let mut end = buffer.iter_at_offset(buffer.cursor_position());
+
+            buffer.begin_user_action();
+            buffer.delete(&mut begin, &mut end);
+            buffer.insert(&mut begin, &proposal.text);
+            buffer.end_user_action();
+        }
+    }
+}
+
+glib::wrapper! {
+    /// Completion proposal of `SqlCompletionProvider`
+    pub struct SqlProposal(ObjectSubclass<imp::SqlProposal>)
+        @implements sourceview5::CompletionProposal;
+}
+
+impl SqlProposal {
+    fn new(proposal: Proposal) -> Self {
+        let item: Self = glib::Object::new(&[]).expect("Failed to create SqlProposal");
+        imp::SqlProposal::from_instance(&item)
+            .proposal
+            .replace(Some(proposal));
+
+        item
+    }
+
+    fn proposal(&self) -> Proposal {
+        imp::SqlProposal::from_instance(self)
+            .proposal
+            .borrow()
+            .clone()
+            .expect("SqlProposal without proposal")
+    }
+}
+
+glib::wrapper! {
+    /// Completes keywords, snippets and names known to the catalog of the editor's session
+    pub struct SqlCompletionProvider(ObjectSubclass<imp::SqlCompletionProvider>)
+        @implements sourceview5::CompletionProvider;
+}
+
+impl SqlCompletionProvider {
+    pub fn new() -> Self {
+        glib::Object::new(&[]).expect("Failed to create SqlCompletionProvider")
+    }
+
+    pub fn set_cache(&self, cache: Arc<CatalogCache>) {
+        imp::SqlCompletionProvider::from_instance(self)
+            .cache
+            .replace(cache);
+    }
+
+    /// Replaces proposals in `store` with those for the cursor position
+    fn fill(&self, store: &gio::ListStore, context: &sourceview5::CompletionContext) {
+        store.remove_all();
+
+        let buffer = match context.buffer() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+
+        let (begin, end) = buffer.bounds();
+        let text = buffer.text(&begin, &end, false);
+        let cursor = text
+            .char_indices()
+            .nth(buffer.cursor_position() as usize)
+            .map_or(text.len(), |(idx, _)| idx);
+
+        let imp = imp::SqlCompletionProvider::from_instance(self);
+        let completion = complete(&text, cursor, &imp.cache.borrow());
+        imp.word_start
+            .set(text[..completion.start].chars().count() as i32);
+
+        for proposal in completion.proposals {
+            store.append(&SqlProposal::new(proposal));
+        }
+    }
+}
+
+impl Default for SqlCompletionProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn icon_name(kind: ProposalKind) -> &'static str {
+    match kind {
+        ProposalKind::Column => "insert-text-symbolic",
+        ProposalKind::Relation => "x-office-spreadsheet-symbolic",
+        ProposalKind::Schema => "folder-documents-symbolic",
+        ProposalKind::Function => "system-run-symbolic",
+        ProposalKind::Keyword => "format-text-bold-symbolic",
+        ProposalKind::Snippet => "edit-paste-symbolic",
+    }
+}
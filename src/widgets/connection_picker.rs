@@ -0,0 +1,300 @@
+use std::{cell::RefCell, path::PathBuf, rc::Rc, sync::Arc};
+
+use glib::clone;
+use gtk4::prelude::*;
+
+use crate::{
+    config::ProfileStore,
+    event::{AppAction, AppEvent, Emitter, EventListener},
+    model::connection_profile::{ConnectionProfile, SslMode},
+    object_or_expect,
+};
+
+/// Dialog listing stored connection profiles
+pub struct ConnectionPicker {
+    window: gtk4::ApplicationWindow,
+    emitter: Emitter,
+}
+
+impl ConnectionPicker {
+    pub fn create(builder: &gtk4::Builder, emitter: Emitter) -> Self {
+        let window = object_or_expect(builder, "main_window");
+
+        Self { window, emitter }
+    }
+
+    fn show_dialog(&self) {
+        // reload on every open so that manual edits of the file are picked up
+        let store = match ProfileStore::load_default() {
+            Ok(store) => Rc::new(RefCell::new(store)),
+            Err(err) => return self.emitter.emit(AppEvent::Err(err)),
+        };
+
+        let dialog = gtk4::Dialog::with_buttons(
+            Some("Connect to database"),
+            Some(&self.window),
+            gtk4::DialogFlags::MODAL | gtk4::DialogFlags::DESTROY_WITH_PARENT,
+            &[
+                ("Cancel", gtk4::ResponseType::Cancel),
+                ("Connect", gtk4::ResponseType::Accept),
+            ],
+        );
+        dialog.set_default_response(gtk4::ResponseType::Accept);
+
+        let list = gtk4::ListBox::new();
+        list.set_size_request(220, -1);
+        fill_list(&list, store.borrow().profiles());
+
+        let form = Rc::new(ProfileForm::new());
+
+        let save = gtk4::Button::with_label("Save");
+        let delete = gtk4::Button::with_label("Delete");
+        let buttons = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+        buttons.set_halign(gtk4::Align::End);
+        buttons.append(&delete);
+        buttons.append(&save);
+        form.grid.attach(&buttons, 0, 12, 2, 1);
+
+        list.connect_row_selected(clone!(@strong store, @strong form => move |_, row| {
+            let store = store.borrow();
+            let profile = row.and_then(|row| store.profiles().get(row.index() as usize));
+
+            if let Some(profile) = profile {
+                form.fill(profile);
+            }
+        }));
+
+        let emitter = self.emitter.clone();
+        save.connect_clicked(clone!(@strong store, @strong form, @weak list => move |_| {
+            let profile = match form.profile() {
+                Some(profile) => profile,
+                None => return,
+            };
+
+            let mut store = store.borrow_mut();
+            store.upsert(profile);
+            if let Err(err) = store.save() {
+                emitter.emit(AppEvent::Err(err));
+            }
+            fill_list(&list, store.profiles());
+        }));
+
+        let emitter = self.emitter.clone();
+        delete.connect_clicked(clone!(@strong store, @strong form, @weak list => move |_| {
+            let mut store = store.borrow_mut();
+            if store.remove(&form.name.text()).is_none() {
+                return;
+            }
+
+            if let Err(err) = store.save() {
+                emitter.emit(AppEvent::Err(err));
+            }
+            fill_list(&list, store.profiles());
+        }));
+
+        let emitter = self.emitter.clone();
+        dialog.connect_response(clone!(@strong form => move |dialog, response| {
+            if response == gtk4::ResponseType::Accept {
+                if let Some(profile) = form.profile() {
+                    emitter.emit(AppEvent::ProfileRequested(Arc::new(profile)));
+                }
+            }
+
+            dialog.close();
+        }));
+
+        let scrolled_list = gtk4::ScrolledWindow::new();
+        scrolled_list.set_child(Some(&list));
+        scrolled_list.set_vexpand(true);
+
+        let content = gtk4::Box::new(gtk4::Orientation::Horizontal, 12);
+        content.set_margin_top(12);
+        content.set_margin_bottom(12);
+        content.set_margin_start(12);
+        content.set_margin_end(12);
+        content.append(&scrolled_list);
+        content.append(&form.grid);
+
+        dialog.content_area().append(&content);
+        dialog.show();
+    }
+}
+
+impl EventListener for ConnectionPicker {
+    fn on_event(&mut self, event: &AppEvent) {
+        if let AppEvent::AppAction(AppAction::SelectConnection) = event {
+            self.show_dialog();
+        }
+    }
+}
+
+fn fill_list(list: &gtk4::ListBox, profiles: &[ConnectionProfile]) {
+    while let Some(child) = list.last_child() {
+        list.remove(&child);
+    }
+
+    for profile in profiles {
+        let label = gtk4::Label::new(None);
+        label.set_xalign(0.0);
+        label.set_markup(&profile_markup(profile));
+        list.append(&label);
+    }
+}
+
+fn profile_markup(profile: &ConnectionProfile) -> String {
+    let tag = match &profile.color {
+        Some(color) => format!(
+            "<span foreground=\"{}\">●</span> ",
+            glib::markup_escape_text(color)
+        ),
+        None => String::new(),
+    };
+
+    format!(
+        "{}<b>{}</b>\n<small>{}@{}:{}/{}</small>",
+        tag,
+        glib::markup_escape_text(&profile.name),
+        glib::markup_escape_text(&profile.user),
+        glib::markup_escape_text(&profile.host),
+        profile.port,
+        glib::markup_escape_text(&profile.dbname),
+    )
+}
+
+struct ProfileForm {
+    grid: gtk4::Grid,
+    name: gtk4::Entry,
+    host: gtk4::Entry,
+    port: gtk4::SpinButton,
+    user: gtk4::Entry,
+    password: gtk4::PasswordEntry,
+    dbname: gtk4::Entry,
+    options: gtk4::Entry,
+    color: gtk4::Entry,
+    sslmode: gtk4::ComboBoxText,
+    sslrootcert: gtk4::Entry,
+    sslcert: gtk4::Entry,
+    sslkey: gtk4::Entry,
+}
+
+impl ProfileForm {
+    fn new() -> Self {
+        let grid = gtk4::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(6);
+
+        let password = gtk4::PasswordEntry::new();
+        password.set_show_peek_icon(true);
+
+        let sslmode = gtk4::ComboBoxText::new();
+        for mode in SslMode::ALL.iter() {
+            sslmode.append(Some(mode.as_str()), mode.as_str());
+        }
+
+        let form = Self {
+            grid,
+            name: gtk4::Entry::new(),
+            host: gtk4::Entry::new(),
+            port: gtk4::SpinButton::with_range(1.0, 65535.0, 1.0),
+            user: gtk4::Entry::new(),
+            password,
+            dbname: gtk4::Entry::new(),
+            options: gtk4::Entry::new(),
+            color: gtk4::Entry::new(),
+            sslmode,
+            sslrootcert: gtk4::Entry::new(),
+            sslcert: gtk4::Entry::new(),
+            sslkey: gtk4::Entry::new(),
+        };
+
+        form.options
+            .set_placeholder_text(Some("-c search_path=app,public"));
+        form.color.set_placeholder_text(Some("#e01b24"));
+        form.sslrootcert.set_placeholder_text(Some("root.crt"));
+        form.sslcert.set_placeholder_text(Some("client.crt"));
+        form.sslkey
+            .set_placeholder_text(Some("client.key (PKCS #8)"));
+
+        let fields: [(&str, &gtk4::Widget); 12] = [
+            ("Name", form.name.upcast_ref()),
+            ("Host", form.host.upcast_ref()),
+            ("Port", form.port.upcast_ref()),
+            ("User", form.user.upcast_ref()),
+            ("Password", form.password.upcast_ref()),
+            ("Database", form.dbname.upcast_ref()),
+            ("Options", form.options.upcast_ref()),
+            ("Color", form.color.upcast_ref()),
+            ("SSL mode", form.sslmode.upcast_ref()),
+            ("Root CA", form.sslrootcert.upcast_ref()),
+            ("Client cert", form.sslcert.upcast_ref()),
+            ("Client key", form.sslkey.upcast_ref()),
+        ];
+
+        for (row, (title, widget)) in fields.iter().enumerate() {
+            let label = gtk4::Label::new(Some(title));
+            label.set_xalign(1.0);
+            widget.set_hexpand(true);
+
+            form.grid.attach(&label, 0, row as i32, 1, 1);
+            form.grid.attach(*widget, 1, row as i32, 1, 1);
+        }
+
+        form.fill(&ConnectionProfile::default());
+
+        form
+    }
+
+    fn fill(&self, profile: &ConnectionProfile) {
+        self.name.set_text(&profile.name);
+        self.host.set_text(&profile.host);
+        self.port.set_value(profile.port as f64);
+        self.user.set_text(&profile.user);
+        self.password
+            .set_text(profile.password.as_deref().unwrap_or_default());
+        self.dbname.set_text(&profile.dbname);
+        self.options
+            .set_text(profile.options.as_deref().unwrap_or_default());
+        self.color
+            .set_text(profile.color.as_deref().unwrap_or_default());
+        self.sslmode.set_active_id(Some(profile.sslmode.as_str()));
+        self.sslrootcert.set_text(&path_text(&profile.sslrootcert));
+        self.sslcert.set_text(&path_text(&profile.sslcert));
+        self.sslkey.set_text(&path_text(&profile.sslkey));
+    }
+
+    /// Profile described by the form, `None` when it has no name
+    fn profile(&self) -> Option<ConnectionProfile> {
+        let name = self.name.text().trim().to_string();
+        if name.is_empty() {
+            return None;
+        }
+
+        let non_empty = |text: glib::GString| Some(text.to_string()).filter(|t| !t.is_empty());
+        let sslmode = self
+            .sslmode
+            .active_id()
+            .and_then(|id| id.parse().ok())
+            .unwrap_or_default();
+
+        Some(ConnectionProfile {
+            name,
+            host: self.host.text().into(),
+            port: self.port.value_as_int() as u16,
+            user: self.user.text().into(),
+            password: non_empty(self.password.text()),
+            dbname: self.dbname.text().into(),
+            options: non_empty(self.options.text()),
+            color: non_empty(self.color.text()),
+            sslmode,
+            sslrootcert: non_empty(self.sslrootcert.text()).map(PathBuf::from),
+            sslcert: non_empty(self.sslcert.text()).map(PathBuf::from),
+            sslkey: non_empty(self.sslkey.text()).map(PathBuf::from),
+        })
+    }
+}
+
+fn path_text(path: &Option<PathBuf>) -> String {
+    path.as_deref()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,135 @@
+use std::{path::PathBuf, rc::Rc};
+
+use gtk4::prelude::*;
+
+use super::exporter::FileChooserSlot;
+use crate::{
+    event::{AppEvent, Emitter, EventListener, PgRequest},
+    model::copy::{CopyDirection, CopyFormat, CopyStatement},
+    object_or_expect,
+};
+
+/// Response showing the output of `COPY ... TO STDOUT` as a result
+const SHOW_RESULT: gtk4::ResponseType = gtk4::ResponseType::Other(1);
+
+/// Picks the local file `COPY ... FROM STDIN` reads or `COPY ... TO STDOUT` writes, like psql's
+/// `\copy`. The output can be shown as a result instead of being saved.
+pub struct CopyDialog {
+    window: gtk4::ApplicationWindow,
+    file_chooser: FileChooserSlot,
+    emitter: Emitter,
+}
+
+impl CopyDialog {
+    pub fn create(builder: &gtk4::Builder, emitter: Emitter) -> Self {
+        let window = object_or_expect(builder, "main_window");
+
+        Self {
+            window,
+            file_chooser: FileChooserSlot::default(),
+            emitter,
+        }
+    }
+
+    fn ask_destination(&self, id: usize, text: String, copy: CopyStatement) {
+        let dialog = gtk4::MessageDialog::new(
+            Some(&self.window),
+            gtk4::DialogFlags::MODAL | gtk4::DialogFlags::DESTROY_WITH_PARENT,
+            gtk4::MessageType::Question,
+            gtk4::ButtonsType::None,
+            "Where should the output of COPY go?",
+        );
+        dialog.add_button("Cancel", gtk4::ResponseType::Cancel);
+        dialog.add_button("Show as result", SHOW_RESULT);
+        dialog.add_button("Save to file…", gtk4::ResponseType::Accept);
+        dialog.set_default_response(gtk4::ResponseType::Accept);
+
+        let window = self.window.clone();
+        let slot = Rc::clone(&self.file_chooser);
+        let emitter = self.emitter.clone();
+        dialog.connect_response(move |dialog, response| {
+            dialog.close();
+
+            if response == SHOW_RESULT {
+                send(&emitter, id, text.clone(), None);
+            } else if response == gtk4::ResponseType::Accept {
+                choose_file(&window, &slot, id, text.clone(), &copy, emitter.clone());
+            }
+        });
+
+        dialog.show();
+    }
+}
+
+impl EventListener for CopyDialog {
+    fn on_event(&mut self, event: &AppEvent) {
+        if let AppEvent::PgCopyFileRequired { id, text, copy } = event {
+            let text = text.clone();
+            match (copy.direction, copy.format) {
+                (CopyDirection::ToStdout, CopyFormat::Text)
+                | (CopyDirection::ToStdout, CopyFormat::Csv) => {
+                    self.ask_destination(*id, text, copy.clone())
+                }
+                _ => choose_file(
+                    &self.window,
+                    &self.file_chooser,
+                    *id,
+                    text,
+                    copy,
+                    self.emitter.clone(),
+                ),
+            }
+        }
+    }
+}
+
+fn choose_file(
+    window: &gtk4::ApplicationWindow,
+    slot: &FileChooserSlot,
+    id: usize,
+    text: String,
+    copy: &CopyStatement,
+    emitter: Emitter,
+) {
+    let dialog = match copy.direction {
+        CopyDirection::FromStdin => gtk4::FileChooserNative::new(
+            Some("Copy from file"),
+            Some(window),
+            gtk4::FileChooserAction::Open,
+            Some("Copy"),
+            Some("Cancel"),
+        ),
+        CopyDirection::ToStdout => {
+            let dialog = gtk4::FileChooserNative::new(
+                Some("Copy to file"),
+                Some(window),
+                gtk4::FileChooserAction::Save,
+                Some("Save"),
+                Some("Cancel"),
+            );
+            dialog.set_current_name(&format!("output.{}", copy.format.extension()));
+            dialog
+        }
+    };
+    dialog.set_modal(true);
+
+    dialog.connect_response(move |dialog, response| {
+        if response == gtk4::ResponseType::Accept {
+            if let Some(path) = dialog.file().and_then(|file| file.path()) {
+                send(&emitter, id, text.clone(), Some(path));
+            }
+        }
+    });
+
+    dialog.show();
+    slot.replace(Some(dialog));
+}
+
+fn send(emitter: &Emitter, id: usize, text: String, copy_file: Option<PathBuf>) {
+    emitter.emit(AppEvent::PgRequest(PgRequest {
+        id,
+        text,
+        params: vec![],
+        copy_file,
+    }));
+}
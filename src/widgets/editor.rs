@@ -1,67 +1,353 @@
-use crate::event::{AppAction, AppEvent, Emitter, EventListener, PgRequest};
+use std::{cell::RefCell, time::Duration};
+
+use crate::{
+    event::{
+        AppAction, AppEvent, DdlTarget, Emitter, EventListener, FetchCount, PgRequest,
+        TransactionControl, TransactionStatus,
+    },
+    model::{
+        copy::CopyStatement,
+        explain::ExplainMode,
+        query_error::{token_range, QueryError},
+        query_params::parse_placeholders,
+        sql_splitter::{identifier_at, statement_at},
+    },
+};
+use glib::clone;
 use gtk4::prelude::*;
+use sourceview5::prelude::*;
+
+use super::completion::SqlCompletionProvider;
+
+const STATEMENT_HIGHLIGHT_TAG: &str = "statement-highlight";
+const STATEMENT_HIGHLIGHT_DURATION: Duration = Duration::from_millis(600);
+const ERROR_TAG: &str = "error-position";
 
+/// SQL buffer running its statements in session `id`
 pub struct Editor {
-    _widget: sourceview5::View,
+    id: usize,
+    widget: sourceview5::View,
     buffer: sourceview5::Buffer,
+    completion: SqlCompletionProvider,
+    /// Buffer offset and text of the last executed statements, if they came from the buffer
+    executed: RefCell<Option<(i32, String)>>,
+    /// Statements of the session run inside a transaction block
+    in_transaction: bool,
     emitter: Emitter,
 }
 
 impl Editor {
-    pub fn create(parent: &gtk4::ScrolledWindow, emitter: Emitter) -> Self {
-        let lang_mgr = sourceview5::LanguageManager::new();
-        let sql = lang_mgr.language("sql");
-        let style_scheme_mgr = sourceview5::StyleSchemeManager::new();
-        let style_scheme = style_scheme_mgr.scheme("oblivion").unwrap();
-
-        let buffer = sourceview5::Buffer::builder()
-            .highlight_syntax(true)
-            .highlight_matching_brackets(true)
-            .language(&sql.unwrap())
-            .enable_undo(true)
-            .style_scheme(&style_scheme)
-            .build();
-
-        let widget = sourceview5::ViewBuilder::new()
-            .editable(true)
-            .monospace(true)
-            .show_line_numbers(true)
-            .highlight_current_line(true)
-            .visible(true)
-            .insert_spaces_instead_of_tabs(true)
-            .tab_width(2)
-            .indent(2)
-            .auto_indent(true)
-            .wrap_mode(gtk4::WrapMode::None)
-            .buffer(&buffer)
-            .build();
+    pub fn create(parent: &gtk4::ScrolledWindow, id: usize, emitter: Emitter) -> Self {
+        let buffer = sql_buffer();
+        let widget = sql_view(&buffer);
+
+        buffer.create_tag(
+            Some(STATEMENT_HIGHLIGHT_TAG),
+            &[("background", &"rgba(255, 255, 0, 0.25)")],
+        );
+        buffer.create_tag(
+            Some(ERROR_TAG),
+            &[
+                ("underline", &gtk4::pango::Underline::Error),
+                ("background", &"rgba(255, 0, 0, 0.2)"),
+            ],
+        );
+
+        // the mark is stale once the text is edited
+        buffer.connect_changed(|buffer| {
+            let (start, finish) = buffer.bounds();
+            buffer.remove_tag_by_name(ERROR_TAG, &start, &finish);
+        });
+
+        let completion = SqlCompletionProvider::new();
+        widget.completion().unwrap().add_provider(&completion);
 
         parent.set_child(Some(&widget));
 
         Self {
-            _widget: widget,
+            id,
+            widget,
             buffer,
+            completion,
+            executed: RefCell::new(None),
+            in_transaction: false,
             emitter,
         }
     }
+
+    /// Replaces the content, which cannot be undone, and marks the buffer unmodified
+    pub fn set_text(&self, text: &str) {
+        self.buffer.begin_irreversible_action();
+        self.buffer.set_text(text);
+        self.buffer.end_irreversible_action();
+        self.buffer.set_modified(false);
+        self.place_cursor(0);
+    }
+
+    pub fn text(&self) -> String {
+        let (begin, end) = self.buffer.bounds();
+        self.buffer.text(&begin, &end, false).into()
+    }
+
+    pub fn cursor(&self) -> i32 {
+        self.buffer.cursor_position()
+    }
+
+    pub fn place_cursor(&self, offset: i32) {
+        self.buffer
+            .place_cursor(&self.buffer.iter_at_offset(offset));
+    }
+
+    /// Whether the buffer changed since it was opened or saved
+    pub fn is_modified(&self) -> bool {
+        self.buffer.is_modified()
+    }
+
+    pub fn set_modified(&self, modified: bool) {
+        self.buffer.set_modified(modified);
+    }
+
+    pub fn connect_modified_changed<F: Fn(bool) + 'static>(&self, f: F) {
+        self.buffer
+            .connect_modified_changed(move |buffer| f(buffer.is_modified()));
+    }
+
+    pub fn grab_focus(&self) {
+        self.widget.grab_focus();
+    }
 }
 
-impl EventListener for Editor {
-    fn on_event(&mut self, event: &AppEvent) {
-        if let AppEvent::AppAction(AppAction::FetchRows) = event {
-            let buffer = &self.buffer;
+/// Buffer highlighting SQL
+pub fn sql_buffer() -> sourceview5::Buffer {
+    let lang_mgr = sourceview5::LanguageManager::new();
+    let sql = lang_mgr.language("sql");
+    let style_scheme_mgr = sourceview5::StyleSchemeManager::new();
+    let style_scheme = style_scheme_mgr.scheme("oblivion").unwrap();
+
+    sourceview5::Buffer::builder()
+        .highlight_syntax(true)
+        .highlight_matching_brackets(true)
+        .language(&sql.unwrap())
+        .enable_undo(true)
+        .style_scheme(&style_scheme)
+        .build()
+}
 
-            let (begin, end) = if buffer.has_selection() {
-                buffer.selection_bounds().unwrap()
-            } else {
-                buffer.bounds()
-            };
+pub fn sql_view(buffer: &sourceview5::Buffer) -> sourceview5::View {
+    sourceview5::ViewBuilder::new()
+        .editable(true)
+        .monospace(true)
+        .show_line_numbers(true)
+        .highlight_current_line(true)
+        .visible(true)
+        .insert_spaces_instead_of_tabs(true)
+        .tab_width(2)
+        .indent(2)
+        .auto_indent(true)
+        .wrap_mode(gtk4::WrapMode::None)
+        .buffer(buffer)
+        .build()
+}
 
-            let text = buffer.text(&begin, &end, false);
+impl Editor {
+    fn fetch_rows(&self) {
+        let buffer = &self.buffer;
+
+        let (begin, end) = if buffer.has_selection() {
+            buffer.selection_bounds().unwrap()
+        } else {
+            buffer.bounds()
+        };
+
+        let text = buffer.text(&begin, &end, false);
+        self.execute_at(begin.offset(), text.into());
+    }
+
+    fn run_statement(&self) {
+        if let Some((offset, statement)) = self.statement_at_cursor() {
+            self.execute_at(offset, statement);
+        }
+    }
+
+    /// Runs the statement under the cursor wrapped in `EXPLAIN`
+    fn explain(&self, mode: ExplainMode) {
+        if let Some((_, statement)) = self.statement_at_cursor() {
+            self.execute(mode.wrap(&statement, self.in_transaction));
+        }
+    }
+
+    /// Highlights the statement under the cursor, returns it with its character offset
+    fn statement_at_cursor(&self) -> Option<(i32, String)> {
+        let buffer = &self.buffer;
+        let (begin, end) = buffer.bounds();
+        let text = buffer.text(&begin, &end, false);
+
+        // buffer offsets count characters, the splitter works on bytes
+        let cursor = char_to_byte(&text, buffer.cursor_position() as usize);
+        let range = statement_at(&text, cursor)?;
+
+        let begin = buffer.iter_at_offset(text[..range.start].chars().count() as i32);
+        let end = buffer.iter_at_offset(text[..range.end].chars().count() as i32);
+        self.highlight(&begin, &end);
+
+        Some((begin.offset(), text[range].to_string()))
+    }
+
+    /// Marks the range for a moment to show what is being executed
+    fn highlight(&self, begin: &gtk4::TextIter, end: &gtk4::TextIter) {
+        let buffer = &self.buffer;
+        let (start, finish) = buffer.bounds();
+        buffer.remove_tag_by_name(STATEMENT_HIGHLIGHT_TAG, &start, &finish);
+        buffer.apply_tag_by_name(STATEMENT_HIGHLIGHT_TAG, begin, end);
+
+        glib::timeout_add_local_once(
+            STATEMENT_HIGHLIGHT_DURATION,
+            clone!(@weak buffer => move || {
+                let (start, finish) = buffer.bounds();
+                buffer.remove_tag_by_name(STATEMENT_HIGHLIGHT_TAG, &start, &finish);
+            }),
+        );
+    }
+
+    /// Executes `text` which does not come from the buffer
+    pub fn execute(&self, text: String) {
+        self.executed.replace(None);
+        self.send(text);
+    }
+
+    /// Executes `text` found at character `offset` of the buffer, errors are marked there
+    fn execute_at(&self, offset: i32, text: String) {
+        self.executed.replace(Some((offset, text.clone())));
+        self.send(text);
+    }
+
+    fn send(&self, text: String) {
+        let (start, finish) = self.buffer.bounds();
+        self.buffer.remove_tag_by_name(ERROR_TAG, &start, &finish);
+
+        if let Some(copy) = CopyStatement::parse(&text) {
+            return self.emitter.emit(AppEvent::PgCopyFileRequired {
+                id: self.id,
+                text,
+                copy,
+            });
+        }
+
+        let query = parse_placeholders(&text);
+
+        if query.params.is_empty() {
             self.emitter.emit(AppEvent::PgRequest(PgRequest {
-                id: 0,
-                text: text.into(),
+                id: self.id,
+                text,
+                params: vec![],
+                copy_file: None,
             }));
+        } else {
+            self.emitter
+                .emit(AppEvent::PgParamsRequired { id: self.id, query });
         }
     }
+
+    /// Requests the DDL of the selected name or the one under the cursor
+    fn show_definition(&self) {
+        let buffer = &self.buffer;
+
+        let name = if let Some((begin, end)) = buffer.selection_bounds() {
+            buffer.text(&begin, &end, false).trim().to_string()
+        } else {
+            let (begin, end) = buffer.bounds();
+            let text = buffer.text(&begin, &end, false);
+            let cursor = char_to_byte(&text, buffer.cursor_position() as usize);
+
+            match identifier_at(&text, cursor) {
+                Some(range) => text[range].to_string(),
+                None => return,
+            }
+        };
+
+        if !name.is_empty() {
+            self.emitter
+                .emit(AppEvent::DdlRequest(DdlTarget::Name(name)));
+        }
+    }
+
+    /// Underlines the token the error points at and moves the cursor there
+    fn on_query_failed(&self, error: &QueryError) {
+        let executed = self.executed.borrow();
+        let (offset, text, position) = match (&*executed, error.position) {
+            (Some((offset, text)), Some(position)) => (*offset, text, position),
+            _ => return,
+        };
+
+        // the server counts in the statement as sent, with `:name` placeholders rewritten
+        let position = parse_placeholders(text).original_position(text, position);
+        let range = match token_range(text, position) {
+            Some(range) => range,
+            None => return,
+        };
+
+        let begin = self.buffer.iter_at_offset(offset + range.start as i32);
+        let end = self.buffer.iter_at_offset(offset + range.end as i32);
+        self.buffer.apply_tag_by_name(ERROR_TAG, &begin, &end);
+        self.buffer.place_cursor(&begin);
+
+        self.widget
+            .scroll_to_mark(&self.buffer.get_insert(), 0.1, false, 0.0, 0.0);
+        self.widget.grab_focus();
+    }
+
+    fn fetch(&self, count: FetchCount) {
+        self.emitter.emit(AppEvent::PgFetch { id: self.id, count });
+    }
+
+    fn transaction_control(&self, control: TransactionControl) {
+        self.emitter.emit(AppEvent::PgTransactionControl {
+            id: self.id,
+            control,
+        });
+    }
+}
+
+impl EventListener for Editor {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::AppAction(AppAction::FetchRows) => self.fetch_rows(),
+            AppEvent::AppAction(AppAction::RunStatement) => self.run_statement(),
+            AppEvent::AppAction(AppAction::ShowDefinition) => self.show_definition(),
+            AppEvent::AppAction(AppAction::Explain(mode)) => self.explain(*mode),
+            AppEvent::AppAction(AppAction::FetchMore) => self.fetch(FetchCount::Page),
+            AppEvent::AppAction(AppAction::FetchAll) => self.fetch(FetchCount::All),
+            AppEvent::AppAction(AppAction::CancelQuery) => {
+                self.emitter.emit(AppEvent::PgCancel { id: self.id })
+            }
+            AppEvent::AppAction(AppAction::Commit) => {
+                self.transaction_control(TransactionControl::Commit)
+            }
+            AppEvent::AppAction(AppAction::Rollback) => {
+                self.transaction_control(TransactionControl::Rollback)
+            }
+            AppEvent::AppAction(AppAction::SetAutoCommit(enabled)) => {
+                self.transaction_control(TransactionControl::SetAutoCommit(*enabled))
+            }
+            AppEvent::PgCatalogCache { id, cache } if *id == self.id => {
+                self.completion.set_cache(cache.clone())
+            }
+            AppEvent::PgQueryFailed { id, error } if *id == self.id => self.on_query_failed(error),
+            AppEvent::PgTransactionStatus {
+                id,
+                status,
+                auto_commit,
+            } if *id == self.id => {
+                // without auto-commit the session opens a transaction before the statement
+                self.in_transaction = *status != TransactionStatus::Idle || !*auto_commit
+            }
+            _ => {}
+        }
+    }
+}
+
+fn char_to_byte(text: &str, char_offset: usize) -> usize {
+    text.char_indices()
+        .nth(char_offset)
+        .map_or(text.len(), |(idx, _)| idx)
 }
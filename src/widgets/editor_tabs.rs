@@ -0,0 +1,571 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use glib::clone;
+use gtk4::{gio, prelude::*};
+
+use crate::{
+    event::{AppAction, AppEvent, Emitter, EventListener, OutputModeChange, TransactionStatus},
+    object_or_expect,
+    workspace::{BufferState, Workspace},
+};
+
+use super::{editor::Editor, output::Output};
+
+const UNTITLED: &str = "Query";
+
+/// Seconds between saves of the workspace
+const AUTOSAVE_INTERVAL: u32 = 10;
+
+/// Editor together with the output of its session
+struct Tab {
+    id: usize,
+    page: gtk4::ScrolledWindow,
+    output_page: gtk4::ScrolledWindow,
+    title: gtk4::Label,
+    /// File the buffer was opened from or saved to
+    path: Option<PathBuf>,
+    editor: Editor,
+    output: Output,
+}
+
+/// Notebook of editors. Every tab runs its statements in a session of its own and shows the
+/// results in its own output, the output pane follows the current tab.
+///
+/// Open buffers, including unsaved ones, are saved to the workspace periodically and on quit,
+/// and reopened on the next launch.
+pub struct EditorTabs {
+    notebook: gtk4::Notebook,
+    outputs: gtk4::Stack,
+    tabs: Vec<Tab>,
+    next_id: usize,
+    output_mode: OutputModeChange,
+    /// Sessions with a transaction that would be rolled back on close
+    open_transactions: HashSet<usize>,
+    workspace: Workspace,
+    /// Workspace as last written to disk
+    saved_workspace: Workspace,
+    /// Cleared when the workspace file could neither be read nor moved aside, so that it is
+    /// not overwritten
+    save_enabled: bool,
+    recent_menu: gio::Menu,
+    /// File dialog, kept alive while shown
+    file_chooser: Option<gtk4::FileChooserNative>,
+    emitter: Emitter,
+}
+
+impl EditorTabs {
+    pub fn create(builder: &gtk4::Builder, emitter: Emitter) -> Self {
+        let notebook: gtk4::Notebook = object_or_expect(builder, "editor_tabs");
+        let outputs = object_or_expect(builder, "outputs");
+        let recent_files: gtk4::MenuButton = object_or_expect(builder, "recent_files");
+
+        Output::connect_mode_buttons(builder, emitter.clone());
+
+        let recent_menu = gio::Menu::new();
+        recent_files.set_menu_model(Some(&recent_menu));
+
+        // pages are named after the session id of their tab
+        notebook.connect_switch_page(clone!(@strong emitter => move |_, page, _| {
+            if let Ok(id) = page.widget_name().parse() {
+                emitter.emit(AppEvent::EditorTabSelected { id });
+            }
+        }));
+
+        glib::timeout_add_seconds_local(
+            AUTOSAVE_INTERVAL,
+            clone!(@strong emitter => move || {
+                emitter.emit(AppEvent::Autosave);
+                glib::Continue(true)
+            }),
+        );
+
+        let (workspace, save_enabled) = match Workspace::load_default() {
+            Ok(workspace) => (workspace, true),
+            Err(err) => {
+                emitter.emit(AppEvent::Err(err));
+                (Workspace::default(), !Workspace::default_path().exists())
+            }
+        };
+
+        let mut editor_tabs = Self {
+            notebook,
+            outputs,
+            tabs: vec![],
+            next_id: 0,
+            output_mode: OutputModeChange::Tabular,
+            open_transactions: HashSet::new(),
+            workspace: Workspace::default(),
+            saved_workspace: workspace.clone(),
+            save_enabled,
+            recent_menu,
+            file_chooser: None,
+            emitter,
+        };
+        editor_tabs.restore(workspace);
+
+        editor_tabs
+    }
+
+    /// Reopens buffers of the previous launch
+    fn restore(&mut self, workspace: Workspace) {
+        for buffer in workspace.buffers.iter() {
+            let text = match (&buffer.unsaved, &buffer.path) {
+                (Some(text), _) => text.clone(),
+                (None, Some(path)) => match read_file(path) {
+                    Ok(text) => text,
+                    Err(err) => {
+                        self.emitter.emit(AppEvent::Err(err));
+                        continue;
+                    }
+                },
+                (None, None) => continue,
+            };
+
+            let tab = self.open(buffer.path.clone(), None, &text);
+            let modified = match buffer.path {
+                Some(_) => buffer.unsaved.is_some(),
+                None => !text.is_empty(),
+            };
+            tab.editor.set_modified(modified);
+            tab.editor.place_cursor(buffer.cursor);
+        }
+
+        if self.tabs.is_empty() {
+            self.open(None, None, "");
+        }
+
+        self.notebook
+            .set_current_page(Some(workspace.current as u32));
+        self.workspace = workspace;
+        self.update_recent_menu();
+    }
+
+    /// Opens a tab with a new session. Tabs are titled after their file, untitled ones are
+    /// numbered after their session.
+    fn open(&mut self, path: Option<PathBuf>, title: Option<&str>, text: &str) -> &Tab {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let page = gtk4::ScrolledWindow::new();
+        page.set_widget_name(&id.to_string());
+        let editor = Editor::create(&page, id, self.emitter.clone());
+        editor.set_text(text);
+
+        let output_page = gtk4::ScrolledWindow::new();
+        self.outputs.add_named(&output_page, Some(&id.to_string()));
+        let output = Output::create(
+            id,
+            output_page.clone(),
+            self.output_mode,
+            self.emitter.clone(),
+        );
+
+        let title = match (&path, title) {
+            (Some(path), _) => file_name(path),
+            (None, Some(title)) => title.to_string(),
+            (None, None) => format!("{} {}", UNTITLED, id + 1),
+        };
+        let (label, title, modified) = tab_label(id, &title, &self.emitter);
+        if let Some(path) = &path {
+            title.set_tooltip_text(Some(&path.display().to_string()));
+        }
+        editor.connect_modified_changed(move |is_modified| modified.set_visible(is_modified));
+
+        let position = self.notebook.append_page(&page, Some(&label));
+        self.notebook.set_tab_reorderable(&page, true);
+        self.notebook.set_current_page(Some(position));
+
+        self.emitter.emit(AppEvent::PgConnect { id });
+
+        self.tabs.push(Tab {
+            id,
+            page,
+            output_page,
+            title,
+            path,
+            editor,
+            output,
+        });
+
+        self.tabs.last().unwrap()
+    }
+
+    fn current(&self) -> Option<&Tab> {
+        let page = self.notebook.nth_page(self.notebook.current_page())?;
+        self.tab_of_page(&page)
+    }
+
+    fn tab_of_page(&self, page: &gtk4::Widget) -> Option<&Tab> {
+        self.tabs
+            .iter()
+            .find(|tab| tab.page.upcast_ref::<gtk4::Widget>() == page)
+    }
+
+    fn on_tab_selected(&self, id: usize) {
+        if let Some(tab) = self.tabs.iter().find(|tab| tab.id == id) {
+            self.outputs.set_visible_child(&tab.output_page);
+            tab.editor.grab_focus();
+        }
+    }
+
+    fn request_close(&self, id: usize) {
+        let tab = match self.tabs.iter().find(|tab| tab.id == id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let mut consequences = vec![];
+        if self.open_transactions.contains(&id) {
+            consequences.push("Uncommitted changes will be rolled back.");
+        }
+        if tab.editor.is_modified() {
+            consequences.push("Unsaved changes to the script will be lost.");
+        }
+
+        if consequences.is_empty() {
+            self.emitter.emit(AppEvent::PgCloseSession { id });
+            return;
+        }
+
+        let dialog = gtk4::MessageDialogBuilder::new()
+            .modal(true)
+            .message_type(gtk4::MessageType::Warning)
+            .text(&format!("Close {}?", tab.title.text()))
+            .secondary_text(&consequences.join(" "))
+            .build();
+        dialog.set_transient_for(self.window().as_ref());
+
+        dialog.add_buttons(&[
+            ("Cancel", gtk4::ResponseType::Cancel),
+            ("Close", gtk4::ResponseType::Accept),
+        ]);
+
+        let emitter = self.emitter.clone();
+        dialog.connect_response(move |dialog, response| {
+            dialog.close();
+            if response == gtk4::ResponseType::Accept {
+                emitter.emit(AppEvent::PgCloseSession { id });
+            }
+        });
+
+        dialog.show();
+    }
+
+    fn on_session_closed(&mut self, id: usize) {
+        self.open_transactions.remove(&id);
+
+        let position = match self.tabs.iter().position(|tab| tab.id == id) {
+            Some(position) => position,
+            None => return,
+        };
+
+        let tab = self.tabs.remove(position);
+        self.notebook.detach_tab(&tab.page);
+        self.outputs.remove(&tab.output_page);
+
+        // there is always an editor to type into
+        if self.tabs.is_empty() {
+            self.open(None, None, "");
+        }
+
+        self.save_workspace();
+    }
+
+    fn open_file(&mut self, path: &Path) {
+        if let Some(tab) = self
+            .tabs
+            .iter()
+            .find(|tab| tab.path.as_deref() == Some(path))
+        {
+            let position = self.notebook.page_num(&tab.page);
+            self.notebook.set_current_page(position);
+            return;
+        }
+
+        match read_file(path) {
+            Ok(text) => {
+                self.open(Some(path.to_path_buf()), None, &text);
+                self.workspace.add_recent(path);
+            }
+            Err(err) => {
+                self.workspace.recent_files.retain(|recent| recent != path);
+                self.emitter.emit(AppEvent::Err(err));
+            }
+        }
+
+        self.update_recent_menu();
+        self.save_workspace();
+    }
+
+    fn save_file(&mut self, id: usize, path: PathBuf) {
+        let tab = match self.tabs.iter_mut().find(|tab| tab.id == id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let result = fs::write(&path, tab.editor.text())
+            .with_context(|| format!("Failed to write {}", path.display()));
+        if let Err(err) = result {
+            return self.emitter.emit(AppEvent::Err(err));
+        }
+
+        tab.editor.set_modified(false);
+        tab.title.set_text(&file_name(&path));
+        tab.title
+            .set_tooltip_text(Some(&path.display().to_string()));
+        tab.path = Some(path.clone());
+
+        self.workspace.add_recent(&path);
+        self.update_recent_menu();
+        self.save_workspace();
+    }
+
+    fn save(&mut self) {
+        let (id, path) = match self.current() {
+            Some(tab) => (tab.id, tab.path.clone()),
+            None => return,
+        };
+
+        match path {
+            Some(path) => self.save_file(id, path),
+            None => self.save_as(),
+        }
+    }
+
+    fn save_as(&mut self) {
+        let (id, name) = match self.current() {
+            Some(tab) => (tab.id, tab.title.text()),
+            None => return,
+        };
+
+        let suggested = if name.ends_with(".sql") {
+            name.to_string()
+        } else {
+            format!("{}.sql", name)
+        };
+
+        let emitter = self.emitter.clone();
+        self.choose_file(
+            gtk4::FileChooserAction::Save,
+            Some(&suggested),
+            move |path| emitter.emit(AppEvent::SaveFile { id, path }),
+        );
+    }
+
+    fn choose_file<F: Fn(PathBuf) + 'static>(
+        &mut self,
+        action: gtk4::FileChooserAction,
+        suggested: Option<&str>,
+        on_chosen: F,
+    ) {
+        let (title, accept) = match action {
+            gtk4::FileChooserAction::Save => ("Save as", "Save"),
+            _ => ("Open", "Open"),
+        };
+
+        let dialog = gtk4::FileChooserNative::new(
+            Some(title),
+            self.window().as_ref(),
+            action,
+            Some(accept),
+            Some("Cancel"),
+        );
+        dialog.set_modal(true);
+
+        let sql_files = gtk4::FileFilter::new();
+        sql_files.set_name(Some("SQL scripts"));
+        sql_files.add_pattern("*.sql");
+        dialog.add_filter(&sql_files);
+
+        let all_files = gtk4::FileFilter::new();
+        all_files.set_name(Some("All files"));
+        all_files.add_pattern("*");
+        dialog.add_filter(&all_files);
+
+        if let Some(name) = suggested {
+            dialog.set_current_name(name);
+        }
+
+        dialog.connect_response(move |dialog, response| {
+            if response == gtk4::ResponseType::Accept {
+                if let Some(path) = dialog.file().and_then(|file| file.path()) {
+                    on_chosen(path);
+                }
+            }
+        });
+
+        dialog.show();
+        self.file_chooser = Some(dialog);
+    }
+
+    fn update_recent_menu(&self) {
+        self.recent_menu.remove_all();
+
+        for path in self.workspace.recent_files.iter() {
+            // underscores would be taken for mnemonics
+            let label = path.display().to_string().replace('_', "__");
+            let item = gio::MenuItem::new(Some(&label), None);
+            item.set_action_and_target_value(
+                Some("app.open_recent"),
+                Some(&path.to_string_lossy().to_variant()),
+            );
+            self.recent_menu.append_item(&item);
+        }
+    }
+
+    /// Writes open buffers to the workspace file unless they are unchanged since the last save
+    fn save_workspace(&mut self) {
+        let pages = (0..self.notebook.n_pages()).filter_map(|n| self.notebook.nth_page(Some(n)));
+
+        let buffers = pages
+            .filter_map(|page| self.tab_of_page(&page))
+            .map(|tab| {
+                let unsaved = tab.path.is_none() || tab.editor.is_modified();
+
+                BufferState {
+                    path: tab.path.clone(),
+                    unsaved: if unsaved {
+                        Some(tab.editor.text())
+                    } else {
+                        None
+                    },
+                    cursor: tab.editor.cursor(),
+                }
+            })
+            .collect();
+
+        self.workspace.buffers = buffers;
+        self.workspace.current = self.notebook.current_page().unwrap_or(0) as usize;
+
+        if self.workspace == self.saved_workspace || !self.save_enabled {
+            return;
+        }
+
+        // remembered even if writing fails, so that the error is not repeated on every autosave
+        self.saved_workspace = self.workspace.clone();
+        if let Err(err) = self.workspace.save_default() {
+            self.emitter.emit(AppEvent::Err(err));
+        }
+    }
+
+    fn window(&self) -> Option<gtk4::Window> {
+        self.notebook.root()?.downcast().ok()
+    }
+
+    fn on_action(&mut self, action: AppAction) {
+        match action {
+            AppAction::NewTab => {
+                self.open(None, None, "");
+            }
+            AppAction::CloseTab => {
+                if let Some(tab) = self.current() {
+                    self.request_close(tab.id);
+                }
+            }
+            AppAction::Open => {
+                let emitter = self.emitter.clone();
+                self.choose_file(gtk4::FileChooserAction::Open, None, move |path| {
+                    emitter.emit(AppEvent::OpenFile(path))
+                });
+            }
+            AppAction::Save => self.save(),
+            AppAction::SaveAs => self.save_as(),
+            AppAction::Quit => self.save_workspace(),
+            AppAction::Export => match self.current().and_then(|tab| tab.output.last_result()) {
+                Some((responses, partial)) => self
+                    .emitter
+                    .emit(AppEvent::ExportTable { responses, partial }),
+                None => self.emitter.emit(AppEvent::Err(anyhow!(
+                    "The current tab has no rows to export"
+                ))),
+            },
+            action => {
+                if let Some(tab) = self.current() {
+                    tab.editor.on_event(&AppEvent::AppAction(action));
+                }
+            }
+        }
+    }
+}
+
+impl EventListener for EditorTabs {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::AppAction(action) => return self.on_action(*action),
+            AppEvent::OpenScript { title, text } => {
+                self.open(None, Some(title), text);
+            }
+            AppEvent::OpenFile(path) => self.open_file(path),
+            AppEvent::SaveFile { id, path } => self.save_file(*id, path.clone()),
+            AppEvent::Autosave => self.save_workspace(),
+            AppEvent::RunQuery(text) => {
+                if let Some(tab) = self.current() {
+                    tab.editor.execute(text.clone());
+                }
+            }
+            AppEvent::EditorTabSelected { id } => self.on_tab_selected(*id),
+            AppEvent::CloseEditorTab { id } => self.request_close(*id),
+            AppEvent::PgCloseSession { id } => self.on_session_closed(*id),
+            AppEvent::PgTransactionStatus { id, status, .. } => {
+                if *status == TransactionStatus::Idle {
+                    self.open_transactions.remove(id);
+                } else {
+                    self.open_transactions.insert(*id);
+                }
+            }
+            AppEvent::OutputModeChanged(mode) => self.output_mode = *mode,
+            // sessions of the new profile are opened right away, as for new tabs
+            AppEvent::ProfileSelected(_) => {
+                for tab in self.tabs.iter() {
+                    self.emitter.emit(AppEvent::PgConnect { id: tab.id });
+                }
+            }
+            _ => {}
+        }
+
+        for tab in self.tabs.iter_mut() {
+            tab.editor.on_event(event);
+            tab.output.on_event(event);
+        }
+    }
+}
+
+/// Label of a tab: its title, a mark shown while the buffer has unsaved changes, and a button
+/// closing it
+fn tab_label(id: usize, title: &str, emitter: &Emitter) -> (gtk4::Box, gtk4::Label, gtk4::Label) {
+    let label = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
+
+    let title = gtk4::Label::new(Some(title));
+    label.append(&title);
+
+    let modified = gtk4::Label::new(Some("*"));
+    modified.set_visible(false);
+    modified.set_tooltip_text(Some("Unsaved changes"));
+    label.append(&modified);
+
+    let close = gtk4::Button::from_icon_name(Some("window-close-symbolic"));
+    close.set_has_frame(false);
+    close.set_tooltip_text(Some("Close tab (Ctrl+W)"));
+    close.connect_clicked(clone!(@strong emitter => move |_| {
+        emitter.emit(AppEvent::CloseEditorTab { id });
+    }));
+    label.append(&close);
+
+    (label, title, modified)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_else(|| path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
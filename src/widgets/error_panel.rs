@@ -0,0 +1,97 @@
+use glib::markup_escape_text;
+use gtk4::prelude::*;
+
+use crate::{
+    event::{AppEvent, EventListener},
+    model::query_error::QueryError,
+    object_or_expect,
+};
+
+/// Bar above the status bar describing the last error, kept until dismissed or until the
+/// session it came from succeeds
+pub struct ErrorPanel {
+    bar: gtk4::InfoBar,
+    text: gtk4::Label,
+    /// Session whose query failed, `None` for errors of other origin
+    session: Option<usize>,
+}
+
+impl ErrorPanel {
+    pub fn create(builder: &gtk4::Builder) -> Self {
+        let bar: gtk4::InfoBar = object_or_expect(builder, "error_bar");
+        let text = object_or_expect(builder, "error_text");
+
+        bar.connect_response(|bar, _| bar.set_revealed(false));
+
+        Self {
+            bar,
+            text,
+            session: None,
+        }
+    }
+
+    fn show(&mut self, session: Option<usize>, markup: &str) {
+        self.session = session;
+        self.text.set_markup(markup);
+        self.bar.set_revealed(true);
+    }
+
+    fn hide_for(&mut self, id: usize) {
+        if self.session == Some(id) {
+            self.session = None;
+            self.bar.set_revealed(false);
+        }
+    }
+
+    fn on_error(&mut self, err: &anyhow::Error) {
+        use tokio_postgres::Error as PgError;
+
+        let markup = match err.downcast_ref::<PgError>().and_then(PgError::as_db_error) {
+            Some(db_err) => query_error_markup(&QueryError::from(db_err)),
+            None => format!(
+                "<b>Error</b>: {}",
+                markup_escape_text(&format!("{:#}", err))
+            ),
+        };
+
+        self.show(None, &markup);
+    }
+}
+
+impl EventListener for ErrorPanel {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::PgQueryFailed { id, error } => {
+                self.show(Some(*id), &query_error_markup(error))
+            }
+            AppEvent::Err(err) => self.on_error(err),
+            AppEvent::PgResponses { id, .. } | AppEvent::PgCloseSession { id } => {
+                self.hide_for(*id)
+            }
+            _ => {}
+        }
+    }
+}
+
+fn query_error_markup(error: &QueryError) -> String {
+    let mut markup = format!(
+        "<b>{} {}</b>: {}",
+        markup_escape_text(&error.severity),
+        markup_escape_text(&error.code),
+        markup_escape_text(&error.message)
+    );
+
+    if let Some(position) = error.position {
+        markup.push_str(&format!("\n<b>Position</b>: {}", position));
+    }
+
+    for (label, value) in error.fields() {
+        markup.push_str(&format!(
+            "\n<b>{}</b>: {}",
+            label,
+            markup_escape_text(value)
+        ));
+    }
+
+    markup
+}
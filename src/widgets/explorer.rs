@@ -0,0 +1,416 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use glib::{clone, markup_escape_text};
+use gtk4::prelude::*;
+
+use crate::{
+    event::{AppEvent, DdlTarget, Emitter, EventListener},
+    model::catalog::{self, CatalogFolder, CatalogObject, CatalogQuery, ObjectKind},
+    object_or_expect,
+};
+
+const COL_MARKUP: i32 = 0;
+const COL_ICON: i32 = 1;
+/// Plain name matched by the filter
+const COL_NAME: i32 = 2;
+/// Index of the row `Node`
+const COL_NODE: i32 = 3;
+
+const SELECT_TOP_LIMIT: usize = 100;
+
+/// Browser of database objects, folders are loaded from the catalog once expanded
+pub struct Explorer {
+    tree: Tree,
+}
+
+impl Explorer {
+    pub fn create(builder: &gtk4::Builder, emitter: Emitter) -> Self {
+        let view: gtk4::TreeView = object_or_expect(builder, "explorer");
+        let filter_entry: gtk4::SearchEntry = object_or_expect(builder, "explorer_filter");
+        let refresh: gtk4::Button = object_or_expect(builder, "explorer_refresh");
+
+        let store = gtk4::TreeStore::new(&[
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::U32,
+        ]);
+        let filtered = gtk4::TreeModelFilter::new(&store, None);
+
+        let filter_text = Rc::new(RefCell::new(String::new()));
+        filtered.set_visible_func(clone!(@strong filter_text => move |model, iter| {
+            is_visible(model, iter, &filter_text.borrow())
+        }));
+
+        view.set_model(Some(&filtered));
+        view.append_column(&object_column());
+
+        let tree = Tree {
+            store,
+            filtered,
+            nodes: Default::default(),
+            pending: Default::default(),
+            emitter,
+        };
+
+        view.connect_row_expanded(clone!(@strong tree => move |_, iter, _| {
+            let iter = tree.filtered.convert_iter_to_child_iter(iter);
+            tree.load_children(&iter, false);
+        }));
+
+        filter_entry.connect_search_changed(clone!(@strong tree => move |entry| {
+            filter_text.replace(entry.text().to_lowercase());
+            tree.filtered.refilter();
+        }));
+
+        refresh.connect_clicked(clone!(@strong tree => move |_| tree.reset()));
+
+        let click = gtk4::GestureClick::new();
+        click.set_button(gdk4::BUTTON_SECONDARY);
+        click.connect_pressed(clone!(@strong tree, @weak view => move |_, _, x, y| {
+            tree.show_context_menu(&view, x, y);
+        }));
+        view.add_controller(&click);
+
+        tree.reset();
+
+        Self { tree }
+    }
+}
+
+impl EventListener for Explorer {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::ProfileSelected(_) => self.tree.reset(),
+            AppEvent::CatalogLoaded { query, objects } => self
+                .tree
+                .on_loaded(query, objects.as_deref().map(Vec::as_slice)),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    /// Lists objects of the query once expanded
+    Folder(CatalogQuery),
+    Object(CatalogObject),
+    /// Stands in for children which are not loaded yet
+    Placeholder,
+}
+
+/// Tree store with the nodes of its rows, shared with the signal handlers
+#[derive(Clone)]
+struct Tree {
+    store: gtk4::TreeStore,
+    filtered: gtk4::TreeModelFilter,
+    nodes: Rc<RefCell<Vec<Node>>>,
+    /// Folders waiting for their objects
+    pending: Rc<RefCell<HashMap<CatalogQuery, gtk4::TreeRowReference>>>,
+    emitter: Emitter,
+}
+
+impl Tree {
+    fn reset(&self) {
+        self.store.clear();
+        self.nodes.borrow_mut().clear();
+        self.pending.borrow_mut().clear();
+
+        for folder in catalog::root_folders() {
+            self.append_folder(None, folder);
+        }
+    }
+
+    fn append_node(
+        &self,
+        parent: Option<&gtk4::TreeIter>,
+        node: Node,
+        name: &str,
+        markup: &str,
+        icon: &str,
+    ) -> gtk4::TreeIter {
+        let idx = {
+            let mut nodes = self.nodes.borrow_mut();
+            nodes.push(node);
+            nodes.len() as u32 - 1
+        };
+
+        let iter = self.store.append(parent);
+        self.store.set(
+            &iter,
+            &[
+                (COL_MARKUP as u32, &markup),
+                (COL_ICON as u32, &icon),
+                (COL_NAME as u32, &name),
+                (COL_NODE as u32, &idx),
+            ],
+        );
+
+        iter
+    }
+
+    fn append_folder(&self, parent: Option<&gtk4::TreeIter>, folder: CatalogFolder) {
+        let markup = markup_escape_text(folder.label);
+        let iter = self.append_node(
+            parent,
+            Node::Folder(folder.query),
+            folder.label,
+            &markup,
+            "folder-symbolic",
+        );
+
+        self.append_placeholder(&iter, "Loading…");
+    }
+
+    fn append_placeholder(&self, parent: &gtk4::TreeIter, text: &str) {
+        let markup = format!("<i>{}</i>", markup_escape_text(text));
+        self.append_node(Some(parent), Node::Placeholder, "", &markup, "");
+    }
+
+    fn append_object(&self, parent: &gtk4::TreeIter, object: &CatalogObject) {
+        let mut markup = markup_escape_text(&object.name).to_string();
+        if let Some(detail) = &object.detail {
+            markup.push_str(&format!(
+                " <span alpha=\"60%\">{}</span>",
+                markup_escape_text(detail)
+            ));
+        }
+
+        let iter = self.append_node(
+            Some(parent),
+            Node::Object(object.clone()),
+            &object.name,
+            &markup,
+            icon_name(object.kind),
+        );
+
+        for folder in object.folders() {
+            self.append_folder(Some(&iter), folder);
+        }
+    }
+
+    fn node(&self, iter: &gtk4::TreeIter) -> Option<Node> {
+        let idx = self.store.get::<u32>(iter, COL_NODE);
+        self.nodes.borrow().get(idx as usize).cloned()
+    }
+
+    /// Requests objects of a folder, loaded folders are requested again only when forced
+    fn load_children(&self, iter: &gtk4::TreeIter, force: bool) {
+        let query = match self.node(iter) {
+            Some(Node::Folder(query)) => query,
+            _ => return,
+        };
+
+        let first_child = self.store.iter_children(Some(iter));
+        let loaded = !matches!(
+            first_child.and_then(|child| self.node(&child)),
+            Some(Node::Placeholder)
+        );
+
+        if (loaded && !force) || self.pending.borrow().contains_key(&query) {
+            return;
+        }
+
+        let reference = self
+            .store
+            .path(iter)
+            .and_then(|path| gtk4::TreeRowReference::new(&self.store, &path));
+
+        if let Some(reference) = reference {
+            self.pending.borrow_mut().insert(query.clone(), reference);
+            self.emitter.emit(AppEvent::CatalogRequest(query));
+        }
+    }
+
+    fn on_loaded(&self, query: &CatalogQuery, objects: Option<&[CatalogObject]>) {
+        let reference = match self.pending.borrow_mut().remove(query) {
+            Some(reference) => reference,
+            None => return,
+        };
+
+        let iter = match reference.path().and_then(|path| self.store.iter(&path)) {
+            Some(iter) => iter,
+            None => return,
+        };
+
+        // new rows go in before the stale ones are removed, so that the folder stays expanded
+        let stale = self.store.iter_n_children(Some(&iter));
+
+        match objects {
+            Some(objects) => objects
+                .iter()
+                .for_each(|object| self.append_object(&iter, object)),
+            None => self.append_placeholder(&iter, "Failed to load"),
+        }
+
+        for _ in 0..stale {
+            if let Some(child) = self.store.iter_children(Some(&iter)) {
+                self.store.remove(&child);
+            }
+        }
+    }
+
+    fn show_context_menu(&self, view: &gtk4::TreeView, x: f64, y: f64) {
+        let (bin_x, bin_y) = view.convert_widget_to_bin_window_coords(x as i32, y as i32);
+        let path = match view.path_at_pos(bin_x, bin_y) {
+            Some((Some(path), ..)) => path,
+            _ => return,
+        };
+        view.selection().select_path(&path);
+
+        let iter = match self.filtered.iter(&path) {
+            Some(iter) => self.filtered.convert_iter_to_child_iter(&iter),
+            None => return,
+        };
+
+        let actions = self.context_actions(view, &iter);
+        if actions.is_empty() {
+            return;
+        }
+
+        let menu = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+        let popover = gtk4::Popover::new();
+
+        for (label, action) in actions {
+            let button = gtk4::Button::with_label(label);
+            button.set_has_frame(false);
+            button.connect_clicked(clone!(@weak popover => move |_| {
+                popover.popdown();
+                action();
+            }));
+            menu.append(&button);
+        }
+
+        popover.set_child(Some(&menu));
+        popover.set_parent(view);
+        popover.set_pointing_to(&gdk4::Rectangle {
+            x: x as i32,
+            y: y as i32,
+            width: 1,
+            height: 1,
+        });
+        popover.connect_closed(|popover| popover.unparent());
+        popover.popup();
+    }
+
+    fn context_actions(
+        &self,
+        view: &gtk4::TreeView,
+        iter: &gtk4::TreeIter,
+    ) -> Vec<(&'static str, Box<dyn Fn()>)> {
+        let mut actions: Vec<(&'static str, Box<dyn Fn()>)> = vec![];
+
+        match self.node(iter) {
+            Some(Node::Object(object)) => {
+                if let Some(text) = object.select_top(SELECT_TOP_LIMIT) {
+                    let emitter = self.emitter.clone();
+                    actions.push((
+                        "SELECT top 100",
+                        Box::new(move || emitter.emit(AppEvent::RunQuery(text.clone()))),
+                    ));
+                }
+
+                if !matches!(object.kind, ObjectKind::Database | ObjectKind::Column) {
+                    let emitter = self.emitter.clone();
+                    let object = object.clone();
+                    actions.push((
+                        "Show DDL",
+                        Box::new(move || {
+                            emitter.emit(AppEvent::DdlRequest(DdlTarget::Object(object.clone())))
+                        }),
+                    ));
+                }
+
+                let name = object.qualified_name();
+                let clipboard = view.clipboard();
+                actions.push(("Copy name", Box::new(move || clipboard.set_text(&name))));
+            }
+            Some(Node::Folder(_)) => {
+                if let Some(path) = self.store.path(iter) {
+                    let tree = self.clone();
+                    actions.push((
+                        "Refresh",
+                        Box::new(move || {
+                            if let Some(iter) = tree.store.iter(&path) {
+                                tree.load_children(&iter, true);
+                            }
+                        }),
+                    ));
+                }
+            }
+            Some(Node::Placeholder) | None => {}
+        }
+
+        actions
+    }
+}
+
+fn object_column() -> gtk4::TreeViewColumn {
+    let column = gtk4::TreeViewColumn::new();
+
+    let icon = gtk4::CellRendererPixbuf::new();
+    column.pack_start(&icon, false);
+    column.add_attribute(&icon, "icon-name", COL_ICON);
+
+    let text = gtk4::CellRendererText::new();
+    column.pack_start(&text, true);
+    column.add_attribute(&text, "markup", COL_MARKUP);
+
+    column
+}
+
+fn icon_name(kind: ObjectKind) -> &'static str {
+    match kind {
+        ObjectKind::Database => "drive-harddisk-symbolic",
+        ObjectKind::Schema => "folder-documents-symbolic",
+        ObjectKind::Table => "x-office-spreadsheet-symbolic",
+        ObjectKind::View | ObjectKind::MaterializedView => "view-paged-symbolic",
+        ObjectKind::Function => "system-run-symbolic",
+        ObjectKind::Sequence => "view-sort-ascending-symbolic",
+        ObjectKind::Type => "text-x-generic-symbolic",
+        ObjectKind::Extension => "application-x-addon-symbolic",
+        ObjectKind::Column => "insert-text-symbolic",
+        ObjectKind::Index => "view-list-symbolic",
+        ObjectKind::Trigger => "media-playlist-repeat-symbolic",
+    }
+}
+
+/// Rows matching the filter are shown together with their ancestors and descendants
+fn is_visible(model: &gtk4::TreeModel, iter: &gtk4::TreeIter, filter: &str) -> bool {
+    if filter.is_empty() || name_matches(model, iter, filter) {
+        return true;
+    }
+
+    let mut parent = model.iter_parent(iter);
+    while let Some(ancestor) = parent {
+        if name_matches(model, &ancestor, filter) {
+            return true;
+        }
+        parent = model.iter_parent(&ancestor);
+    }
+
+    has_matching_descendant(model, iter, filter)
+}
+
+fn has_matching_descendant(model: &gtk4::TreeModel, iter: &gtk4::TreeIter, filter: &str) -> bool {
+    let child = match model.iter_children(Some(iter)) {
+        Some(child) => child,
+        None => return false,
+    };
+
+    loop {
+        if name_matches(model, &child, filter) || has_matching_descendant(model, &child, filter) {
+            return true;
+        }
+        if !model.iter_next(&child) {
+            return false;
+        }
+    }
+}
+
+fn name_matches(model: &gtk4::TreeModel, iter: &gtk4::TreeIter, filter: &str) -> bool {
+    model
+        .get::<String>(iter, COL_NAME)
+        .to_lowercase()
+        .contains(filter)
+}
@@ -0,0 +1,337 @@
+use std::{
+    cell::RefCell,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+    thread,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use glib::clone;
+use gtk4::prelude::*;
+
+use crate::{
+    event::{AppEvent, Emitter, EventListener},
+    model::{
+        export::{export, CsvOptions, ExportFormat},
+        pg_response::PgResponse,
+    },
+    object_or_expect,
+    util::PartialFile,
+};
+
+/// Native file chooser, which has to be kept alive until it responds
+pub(super) type FileChooserSlot = Rc<RefCell<Option<gtk4::FileChooserNative>>>;
+
+/// Messages of the thread writing an export
+enum Progress {
+    Rows(usize),
+    Done(Result<()>),
+}
+
+/// Dialogs choosing the format and the file results are exported to, the file is written in the
+/// background
+pub struct Exporter {
+    window: gtk4::ApplicationWindow,
+    file_chooser: FileChooserSlot,
+    emitter: Emitter,
+}
+
+impl Exporter {
+    pub fn create(builder: &gtk4::Builder, emitter: Emitter) -> Self {
+        let window = object_or_expect(builder, "main_window");
+
+        Self {
+            window,
+            file_chooser: FileChooserSlot::default(),
+            emitter,
+        }
+    }
+
+    /// Asks for the format to export the last table of `responses` in, telling how many rows
+    /// are left out while the result is `partial`
+    fn show_dialog(&self, responses: Arc<Vec<PgResponse>>, partial: bool) {
+        let dialog = gtk4::Dialog::with_buttons(
+            Some("Export result"),
+            Some(&self.window),
+            gtk4::DialogFlags::MODAL | gtk4::DialogFlags::DESTROY_WITH_PARENT,
+            &[
+                ("Cancel", gtk4::ResponseType::Cancel),
+                ("Export", gtk4::ResponseType::Accept),
+            ],
+        );
+        dialog.set_default_response(gtk4::ResponseType::Accept);
+
+        let form = Rc::new(FormatForm::new());
+        let fetched = PgResponse::last_table(&responses).map_or(0, |table| table.rows.len());
+
+        let window = self.window.clone();
+        let file_chooser = Rc::clone(&self.file_chooser);
+        let emitter = self.emitter.clone();
+        dialog.connect_response(clone!(@strong form => move |dialog, response| {
+            if response == gtk4::ResponseType::Accept {
+                match form.format() {
+                    Ok(format) => {
+                        let responses = Arc::clone(&responses);
+                        choose_file(&window, &file_chooser, format, responses, emitter.clone())
+                    }
+                    Err(err) => return emitter.emit(AppEvent::Err(err)),
+                }
+            }
+
+            dialog.close();
+        }));
+
+        form.grid.set_margin_top(12);
+        form.grid.set_margin_bottom(12);
+        form.grid.set_margin_start(12);
+        form.grid.set_margin_end(12);
+
+        dialog.content_area().append(&form.grid);
+
+        if partial {
+            let warning = gtk4::Label::new(Some(&format!(
+                "Only the {} rows fetched so far are exported.\nFetch all rows first to export \
+                 the whole result.",
+                fetched
+            )));
+            warning.set_xalign(0.0);
+            warning.set_margin_bottom(12);
+            warning.set_margin_start(12);
+            warning.set_margin_end(12);
+            dialog.content_area().append(&warning);
+        }
+
+        dialog.show();
+    }
+}
+
+impl EventListener for Exporter {
+    fn on_event(&mut self, event: &AppEvent) {
+        if let AppEvent::ExportTable { responses, partial } = event {
+            self.show_dialog(Arc::clone(responses), *partial);
+        }
+    }
+}
+
+/// Format and its options
+struct FormatForm {
+    grid: gtk4::Grid,
+    format: gtk4::ComboBoxText,
+    delimiter: gtk4::Entry,
+    quote: gtk4::Entry,
+    header: gtk4::CheckButton,
+    null: gtk4::Entry,
+    table: gtk4::Entry,
+}
+
+impl FormatForm {
+    fn new() -> Self {
+        let grid = gtk4::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(6);
+
+        let format = gtk4::ComboBoxText::new();
+        format.append(Some("csv"), "CSV");
+        format.append(Some("tsv"), "TSV");
+        format.append(Some("json"), "JSON");
+        format.append(Some("jsonl"), "JSON Lines");
+        format.append(Some("md"), "Markdown");
+        format.append(Some("html"), "HTML");
+        format.append(Some("sql"), "SQL INSERT statements");
+        format.set_active_id(Some("csv"));
+
+        let defaults = CsvOptions::default();
+
+        let form = Self {
+            grid,
+            format,
+            delimiter: gtk4::Entry::new(),
+            quote: gtk4::Entry::new(),
+            header: gtk4::CheckButton::new(),
+            null: gtk4::Entry::new(),
+            table: gtk4::Entry::new(),
+        };
+
+        form.delimiter
+            .set_text(&char::from(defaults.delimiter).to_string());
+        form.delimiter.set_tooltip_text(Some("\\t for a tab"));
+        form.quote.set_text(&char::from(defaults.quote).to_string());
+        form.header.set_active(defaults.header);
+        form.null.set_text(&defaults.null);
+        form.null.set_placeholder_text(Some("empty"));
+        form.table.set_placeholder_text(Some("schema.table"));
+
+        let fields: [(&str, &gtk4::Widget); 6] = [
+            ("Format", form.format.upcast_ref()),
+            ("Delimiter", form.delimiter.upcast_ref()),
+            ("Quote", form.quote.upcast_ref()),
+            ("Header", form.header.upcast_ref()),
+            ("Null as", form.null.upcast_ref()),
+            ("Table", form.table.upcast_ref()),
+        ];
+
+        for (row, (title, widget)) in fields.iter().enumerate() {
+            let label = gtk4::Label::new(Some(title));
+            label.set_xalign(1.0);
+            widget.set_hexpand(true);
+
+            form.grid.attach(&label, 0, row as i32, 1, 1);
+            form.grid.attach(*widget, 1, row as i32, 1, 1);
+        }
+
+        let csv_options: [gtk4::Widget; 4] = [
+            form.delimiter.clone().upcast(),
+            form.quote.clone().upcast(),
+            form.header.clone().upcast(),
+            form.null.clone().upcast(),
+        ];
+        let table = form.table.clone();
+        let update_sensitivity = move |format: &gtk4::ComboBoxText| {
+            let id = format.active_id();
+            for widget in csv_options.iter() {
+                widget.set_sensitive(id.as_deref() == Some("csv"));
+            }
+            table.set_sensitive(id.as_deref() == Some("sql"));
+        };
+        update_sensitivity(&form.format);
+        form.format.connect_changed(update_sensitivity);
+
+        form
+    }
+
+    fn format(&self) -> Result<ExportFormat> {
+        let format = match self.format.active_id().as_deref() {
+            Some("tsv") => ExportFormat::Tsv,
+            Some("json") => ExportFormat::Json,
+            Some("jsonl") => ExportFormat::JsonLines,
+            Some("md") => ExportFormat::Markdown,
+            Some("html") => ExportFormat::Html,
+            Some("sql") => {
+                let table = self.table.text().trim().to_string();
+                if table.is_empty() {
+                    bail!("Name the table to insert into");
+                }
+                ExportFormat::SqlInsert { table }
+            }
+            _ => ExportFormat::Csv(CsvOptions {
+                delimiter: single_byte("Delimiter", &self.delimiter.text())?,
+                quote: single_byte("Quote", &self.quote.text())?,
+                header: self.header.is_active(),
+                null: self.null.text().to_string(),
+            }),
+        };
+
+        Ok(format)
+    }
+}
+
+pub(super) fn single_byte(field: &str, text: &str) -> Result<u8> {
+    match text {
+        "\\t" => Ok(b'\t'),
+        text if text.len() == 1 && text.is_ascii() => Ok(text.as_bytes()[0]),
+        _ => bail!("{} has to be a single ASCII character", field),
+    }
+}
+
+fn choose_file(
+    window: &gtk4::ApplicationWindow,
+    slot: &FileChooserSlot,
+    format: ExportFormat,
+    responses: Arc<Vec<PgResponse>>,
+    emitter: Emitter,
+) {
+    let dialog = gtk4::FileChooserNative::new(
+        Some("Export as"),
+        Some(window),
+        gtk4::FileChooserAction::Save,
+        Some("Export"),
+        Some("Cancel"),
+    );
+    dialog.set_modal(true);
+    dialog.set_current_name(&format!("result.{}", format.extension()));
+
+    dialog.connect_response(clone!(@weak window => move |dialog, response| {
+        if response == gtk4::ResponseType::Accept {
+            if let Some(path) = dialog.file().and_then(|file| file.path()) {
+                let responses = Arc::clone(&responses);
+                write_in_background(&window, path, format.clone(), responses, emitter.clone());
+            }
+        }
+    }));
+
+    dialog.show();
+    slot.replace(Some(dialog));
+}
+
+/// Writes the last table of `responses` to the file on a separate thread, showing its progress
+fn write_in_background(
+    window: &gtk4::ApplicationWindow,
+    path: PathBuf,
+    format: ExportFormat,
+    responses: Arc<Vec<PgResponse>>,
+    emitter: Emitter,
+) {
+    let total = PgResponse::last_table(&responses).map_or(0, |table| table.rows.len());
+
+    let progress_bar = gtk4::ProgressBar::new();
+    progress_bar.set_show_text(true);
+    progress_bar.set_text(Some(&format!("0 of {} rows", total)));
+    progress_bar.set_margin_top(12);
+    progress_bar.set_margin_bottom(12);
+    progress_bar.set_margin_start(12);
+    progress_bar.set_margin_end(12);
+
+    let dialog = gtk4::Dialog::new();
+    dialog.set_title(Some(&format!("Exporting to {}", path.display())));
+    dialog.set_transient_for(Some(window));
+    dialog.set_deletable(false);
+    dialog.set_default_size(400, -1);
+    dialog.content_area().append(&progress_bar);
+    dialog.show();
+
+    let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+
+    thread::spawn(move || {
+        let progress = sender.clone();
+        let result = write_file(&responses, &format, &path, &mut |rows| {
+            progress.send(Progress::Rows(rows)).ok();
+        })
+        .with_context(|| format!("Failed to export to {}", path.display()));
+
+        sender.send(Progress::Done(result)).ok();
+    });
+
+    receiver.attach(None, move |progress| match progress {
+        Progress::Rows(rows) => {
+            if total > 0 {
+                progress_bar.set_fraction(rows as f64 / total as f64);
+            }
+            progress_bar.set_text(Some(&format!("{} of {} rows", rows, total)));
+            glib::Continue(true)
+        }
+        Progress::Done(result) => {
+            dialog.close();
+            if let Err(err) = result {
+                emitter.emit(AppEvent::Err(err));
+            }
+            glib::Continue(false)
+        }
+    });
+}
+
+/// Exports the last table of `responses`, the file at `path` is replaced only once it is complete
+fn write_file(
+    responses: &[PgResponse],
+    format: &ExportFormat,
+    path: &Path,
+    progress: &mut dyn FnMut(usize),
+) -> Result<()> {
+    let table = PgResponse::last_table(responses)
+        .ok_or_else(|| anyhow!("The result has no rows to export"))?;
+
+    let mut file = PartialFile::create(path)?;
+    export(table, format, BufWriter::new(&mut file), progress)?;
+    file.persist()
+}
@@ -0,0 +1,300 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use chrono::Local;
+use glib::{clone, markup_escape_text};
+use gtk4::prelude::*;
+
+use crate::{
+    event::{AppEvent, Emitter, EventListener},
+    history::{HistoryEntry, HistoryFilter, Period, QueryHistory},
+    object_or_expect,
+    util::format_duration,
+};
+
+const COL_STATUS_ICON: i32 = 0;
+const COL_TIME: i32 = 1;
+const COL_DATABASE: i32 = 2;
+const COL_DURATION: i32 = 3;
+const COL_ROWS: i32 = 4;
+/// First line of the query
+const COL_QUERY: i32 = 5;
+/// Whole query with its error as markup
+const COL_TOOLTIP: i32 = 6;
+/// Index of the entry in `QueryHistory::entries`
+const COL_ENTRY: i32 = 7;
+
+/// Matching entries shown at most, the newest ones
+const DISPLAY_LIMIT: usize = 1000;
+
+const ALL_DATABASES: &str = "";
+
+/// Pause in typing after which the search is applied
+const SEARCH_DELAY: Duration = Duration::from_millis(250);
+
+/// Panel listing executed queries, newest first, which can be opened in an editor or run again
+pub struct History {
+    panel: Rc<Panel>,
+}
+
+impl History {
+    pub fn create(builder: &gtk4::Builder, emitter: Emitter) -> Self {
+        let history = QueryHistory::load_default().unwrap_or_else(|err| {
+            emitter.emit(AppEvent::Err(err));
+            QueryHistory::in_memory()
+        });
+
+        let view: gtk4::TreeView = object_or_expect(builder, "history");
+        let store = gtk4::ListStore::new(&[
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::U32,
+        ]);
+        view.set_model(Some(&store));
+        view.set_tooltip_column(COL_TOOLTIP);
+
+        view.append_column(&icon_column(COL_STATUS_ICON));
+        view.append_column(&text_column("Time", COL_TIME));
+        view.append_column(&text_column("Database", COL_DATABASE));
+        view.append_column(&text_column("Duration", COL_DURATION));
+        view.append_column(&text_column("Rows", COL_ROWS));
+        view.append_column(&text_column("Query", COL_QUERY));
+
+        let panel = Rc::new(Panel {
+            view,
+            store,
+            search: object_or_expect(builder, "history_search"),
+            database: object_or_expect(builder, "history_database"),
+            period: object_or_expect(builder, "history_period"),
+            status: object_or_expect(builder, "history_status"),
+            history: RefCell::new(history),
+            pending_search: RefCell::new(None),
+            emitter,
+        });
+
+        panel
+            .search
+            .connect_search_changed(clone!(@weak panel => move |_| panel.search_later()));
+        panel
+            .database
+            .connect_changed(clone!(@weak panel => move |_| panel.refill()));
+        panel
+            .period
+            .connect_changed(clone!(@weak panel => move |_| panel.refill()));
+        panel
+            .status
+            .connect_changed(clone!(@weak panel => move |_| panel.refill()));
+
+        panel
+            .view
+            .connect_row_activated(clone!(@weak panel => move |_, _, _| panel.open_selected()));
+
+        let open: gtk4::Button = object_or_expect(builder, "history_open");
+        open.connect_clicked(clone!(@weak panel => move |_| panel.open_selected()));
+
+        let run: gtk4::Button = object_or_expect(builder, "history_run");
+        run.connect_clicked(clone!(@weak panel => move |_| panel.run_selected()));
+
+        panel.fill_databases();
+        panel.refill();
+
+        Self { panel }
+    }
+}
+
+impl EventListener for History {
+    fn on_event(&mut self, event: &AppEvent) {
+        if let AppEvent::PgQueryFinished { entry, .. } = event {
+            self.panel.record(HistoryEntry::clone(entry));
+        }
+    }
+}
+
+/// Widgets of the history panel with the history they show, shared with the signal handlers
+struct Panel {
+    view: gtk4::TreeView,
+    store: gtk4::ListStore,
+    search: gtk4::SearchEntry,
+    database: gtk4::ComboBoxText,
+    period: gtk4::ComboBoxText,
+    status: gtk4::ComboBoxText,
+    history: RefCell<QueryHistory>,
+    /// Search waiting for typing to pause
+    pending_search: RefCell<Option<glib::SourceId>>,
+    emitter: Emitter,
+}
+
+impl Panel {
+    fn search_later(self: &Rc<Self>) {
+        if let Some(pending) = self.pending_search.take() {
+            pending.remove();
+        }
+
+        let panel = Rc::downgrade(self);
+        let source = glib::timeout_add_local_once(SEARCH_DELAY, move || {
+            if let Some(panel) = panel.upgrade() {
+                panel.pending_search.replace(None);
+                panel.refill();
+            }
+        });
+        self.pending_search.replace(Some(source));
+    }
+
+    fn record(&self, entry: HistoryEntry) {
+        let new_database = !self
+            .history
+            .borrow()
+            .databases()
+            .contains(&entry.database.as_str());
+
+        if let Err(err) = self.history.borrow_mut().append(entry) {
+            self.emitter.emit(AppEvent::Err(err));
+        }
+
+        if new_database {
+            self.fill_databases();
+        }
+        self.refill();
+    }
+
+    fn filter(&self) -> HistoryFilter {
+        let database = self
+            .database
+            .active_id()
+            .filter(|id| id != ALL_DATABASES)
+            .map(String::from);
+
+        let period = match self.period.active_id().as_deref() {
+            Some("today") => Some(Period::Today),
+            Some("week") => Some(Period::Week),
+            Some("month") => Some(Period::Month),
+            _ => None,
+        };
+
+        let succeeded = match self.status.active_id().as_deref() {
+            Some("succeeded") => Some(true),
+            Some("failed") => Some(false),
+            _ => None,
+        };
+
+        HistoryFilter {
+            text: self.search.text().to_string(),
+            database,
+            period,
+            succeeded,
+        }
+    }
+
+    fn fill_databases(&self) {
+        let active = self.database.active_id();
+
+        self.database.remove_all();
+        self.database.append(Some(ALL_DATABASES), "All databases");
+        for database in self.history.borrow().databases() {
+            self.database.append(Some(database), database);
+        }
+
+        if active.is_none() || !self.database.set_active_id(active.as_deref()) {
+            self.database.set_active_id(Some(ALL_DATABASES));
+        }
+    }
+
+    fn refill(&self) {
+        self.store.clear();
+
+        let filter = self.filter();
+        let now = Local::now();
+        let history = self.history.borrow();
+
+        let matching = history.matching(&filter, now).take(DISPLAY_LIMIT);
+
+        for (idx, entry) in matching {
+            let status = if entry.succeeded() {
+                "emblem-ok-symbolic"
+            } else {
+                "dialog-error-symbolic"
+            };
+            let time = entry
+                .executed_at()
+                .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default();
+            let duration = format_duration(entry.duration());
+            let rows = entry.rows.map(|rows| rows.to_string()).unwrap_or_default();
+            let query = entry.query.trim().lines().next().unwrap_or_default();
+
+            let mut tooltip = markup_escape_text(entry.query.trim()).to_string();
+            if let Some(error) = &entry.error {
+                tooltip.push_str(&format!("\n\n<b>{}</b>", markup_escape_text(error)));
+            }
+
+            self.store.insert_with_values(
+                None,
+                &[
+                    (COL_STATUS_ICON as u32, &status),
+                    (COL_TIME as u32, &time),
+                    (COL_DATABASE as u32, &entry.database),
+                    (COL_DURATION as u32, &duration),
+                    (COL_ROWS as u32, &rows),
+                    (COL_QUERY as u32, &query),
+                    (COL_TOOLTIP as u32, &tooltip),
+                    (COL_ENTRY as u32, &(idx as u32)),
+                ],
+            );
+        }
+    }
+
+    fn selected_query(&self) -> Option<String> {
+        let (model, iter) = self.view.selection().selected()?;
+        let idx = model.get::<u32>(&iter, COL_ENTRY) as usize;
+
+        self.history
+            .borrow()
+            .entries()
+            .get(idx)
+            .map(|entry| entry.query.clone())
+    }
+
+    fn open_selected(&self) {
+        if let Some(text) = self.selected_query() {
+            self.emitter.emit(AppEvent::OpenScript {
+                title: "History".into(),
+                text,
+            });
+        }
+    }
+
+    /// Runs the selected query in the session of the current editor tab
+    fn run_selected(&self) {
+        if let Some(text) = self.selected_query() {
+            self.emitter.emit(AppEvent::RunQuery(text));
+        }
+    }
+}
+
+fn text_column(title: &str, col: i32) -> gtk4::TreeViewColumn {
+    let column = gtk4::TreeViewColumn::new();
+    column.set_title(title);
+    column.set_resizable(true);
+
+    let text = gtk4::CellRendererText::new();
+    text.set_property("ellipsize", gtk4::pango::EllipsizeMode::End)
+        .unwrap();
+    column.pack_start(&text, true);
+    column.add_attribute(&text, "text", col);
+
+    column
+}
+
+fn icon_column(col: i32) -> gtk4::TreeViewColumn {
+    let column = gtk4::TreeViewColumn::new();
+
+    let icon = gtk4::CellRendererPixbuf::new();
+    column.pack_start(&icon, false);
+    column.add_attribute(&icon, "icon-name", col);
+
+    column
+}
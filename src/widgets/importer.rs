@@ -0,0 +1,715 @@
+use std::{cell::RefCell, fs, path::PathBuf, rc::Rc, sync::Arc};
+
+use anyhow::{bail, Result};
+use gtk4::prelude::*;
+
+use super::exporter::{single_byte, FileChooserSlot};
+use crate::{
+    event::{AppAction, AppEvent, Emitter, EventListener},
+    model::{
+        catalog::{CatalogObject, CatalogQuery, ObjectKind},
+        import::{
+            self, ColumnMapping, ImportFormat, ImportReport, ImportRequest, Preview, MAX_ROW_ERRORS,
+        },
+    },
+    object_or_expect,
+    pg_session::IMPORT_SESSION,
+};
+
+/// Response of the wizard opening its statements in an editor tab
+const OPEN_SQL: gtk4::ResponseType = gtk4::ResponseType::Other(1);
+
+/// Rejected rows listed in the report of an import
+const REPORTED_ERRORS: usize = 20;
+
+/// Wizard which is open, kept until its dialog responds
+type WizardSlot = Rc<RefCell<Option<Rc<Wizard>>>>;
+
+/// Wizard loading a local file into a table: the file is previewed, its columns are mapped to
+/// the columns of an existing table or to a new one, then it is copied in on the import session
+pub struct Importer {
+    window: gtk4::ApplicationWindow,
+    file_chooser: FileChooserSlot,
+    wizard: WizardSlot,
+    progress: Option<ProgressDialog>,
+    emitter: Emitter,
+}
+
+impl Importer {
+    pub fn create(builder: &gtk4::Builder, emitter: Emitter) -> Self {
+        let window = object_or_expect(builder, "main_window");
+
+        Self {
+            window,
+            file_chooser: FileChooserSlot::default(),
+            wizard: WizardSlot::default(),
+            progress: None,
+            emitter,
+        }
+    }
+
+    fn choose_file(&self) {
+        let dialog = gtk4::FileChooserNative::new(
+            Some("Import file"),
+            Some(&self.window),
+            gtk4::FileChooserAction::Open,
+            Some("Open"),
+            Some("Cancel"),
+        );
+        dialog.set_modal(true);
+
+        let data_files = gtk4::FileFilter::new();
+        data_files.set_name(Some("CSV, TSV and JSON Lines files"));
+        for pattern in &[
+            "*.csv", "*.tsv", "*.tab", "*.txt", "*.jsonl", "*.ndjson", "*.json",
+        ] {
+            data_files.add_pattern(pattern);
+        }
+        dialog.add_filter(&data_files);
+
+        let all_files = gtk4::FileFilter::new();
+        all_files.set_name(Some("All files"));
+        all_files.add_pattern("*");
+        dialog.add_filter(&all_files);
+
+        let window = self.window.clone();
+        let slot = Rc::clone(&self.wizard);
+        let emitter = self.emitter.clone();
+        dialog.connect_response(move |dialog, response| {
+            if response == gtk4::ResponseType::Accept {
+                if let Some(path) = dialog.file().and_then(|file| file.path()) {
+                    let wizard = Wizard::show(&window, path, &slot, emitter.clone());
+                    slot.replace(Some(wizard));
+                }
+            }
+        });
+
+        dialog.show();
+        self.file_chooser.replace(Some(dialog));
+    }
+}
+
+impl EventListener for Importer {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::AppAction(AppAction::Import) => self.choose_file(),
+            AppEvent::CatalogLoaded { query, objects } => {
+                if let Some(wizard) = self.wizard.borrow().as_ref() {
+                    wizard.on_columns_loaded(query, objects.as_deref().map(Vec::as_slice));
+                }
+            }
+            AppEvent::ImportRequest(request) => {
+                self.progress = Some(ProgressDialog::show(
+                    &self.window,
+                    request,
+                    self.emitter.clone(),
+                ))
+            }
+            AppEvent::ImportProgress { rows, bytes } => {
+                if let Some(progress) = &self.progress {
+                    progress.update(*rows, *bytes);
+                }
+            }
+            AppEvent::ImportFinished(report) => {
+                if let Some(progress) = self.progress.take() {
+                    progress.finish(&self.window, report.as_deref());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Column of the file and where it goes
+struct ColumnRow {
+    source: usize,
+    /// Type of the column when the table is created
+    data_type: gtk4::Entry,
+    /// Column of the table, empty to skip the file column
+    target: gtk4::ComboBoxText,
+}
+
+impl ColumnRow {
+    fn target_entry(&self) -> Option<gtk4::Entry> {
+        self.target.child()?.downcast().ok()
+    }
+
+    fn target(&self) -> String {
+        self.target_entry()
+            .map(|entry| entry.text().trim().to_string())
+            .unwrap_or_default()
+    }
+
+    fn set_target(&self, name: &str) {
+        if let Some(entry) = self.target_entry() {
+            entry.set_text(name);
+        }
+    }
+}
+
+struct Wizard {
+    dialog: gtk4::Dialog,
+    path: PathBuf,
+    format: gtk4::ComboBoxText,
+    delimiter: gtk4::Entry,
+    header: gtk4::CheckButton,
+    preview: gtk4::ScrolledWindow,
+    /// Rows of the preview which could not be read
+    preview_errors: gtk4::Label,
+    table: gtk4::Entry,
+    /// Maps file columns to the columns of the table
+    match_columns: gtk4::Button,
+    create_table: gtk4::CheckButton,
+    dry_run: gtk4::CheckButton,
+    mapping: gtk4::ScrolledWindow,
+    columns: RefCell<Vec<ColumnRow>>,
+    /// Listing of the target table's columns the wizard waits for
+    columns_query: RefCell<Option<CatalogQuery>>,
+    emitter: Emitter,
+}
+
+impl Wizard {
+    fn show(
+        window: &gtk4::ApplicationWindow,
+        path: PathBuf,
+        slot: &WizardSlot,
+        emitter: Emitter,
+    ) -> Rc<Self> {
+        let dialog = gtk4::Dialog::with_buttons(
+            Some(&format!("Import {}", path.display())),
+            Some(window),
+            gtk4::DialogFlags::MODAL | gtk4::DialogFlags::DESTROY_WITH_PARENT,
+            &[
+                ("Open SQL", OPEN_SQL),
+                ("Cancel", gtk4::ResponseType::Cancel),
+                ("Import", gtk4::ResponseType::Accept),
+            ],
+        );
+        dialog.set_default_size(720, 600);
+
+        let format = gtk4::ComboBoxText::new();
+        format.append(Some("csv"), "CSV");
+        format.append(Some("tsv"), "TSV");
+        format.append(Some("jsonl"), "JSON Lines");
+
+        let delimiter = gtk4::Entry::new();
+        delimiter.set_tooltip_text(Some("\\t for a tab"));
+        let header = gtk4::CheckButton::with_label("First line names the columns");
+
+        match ImportFormat::guess(&path) {
+            ImportFormat::JsonLines => format.set_active_id(Some("jsonl")),
+            ImportFormat::Csv {
+                delimiter: b'\t', ..
+            } => format.set_active_id(Some("tsv")),
+            _ => format.set_active_id(Some("csv")),
+        };
+        delimiter.set_text(",");
+        header.set_active(true);
+
+        let table = gtk4::Entry::new();
+        table.set_placeholder_text(Some("schema.table"));
+        table.set_hexpand(true);
+        if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+            table.set_text(&stem.to_lowercase());
+        }
+
+        let wizard = Rc::new(Self {
+            dialog,
+            path,
+            format,
+            delimiter,
+            header,
+            preview: scrolled_window(160),
+            preview_errors: gtk4::Label::new(None),
+            table,
+            match_columns: gtk4::Button::with_label("Match columns"),
+            create_table: gtk4::CheckButton::with_label("Create the table"),
+            dry_run: gtk4::CheckButton::with_label("Dry run, roll back once all rows are checked"),
+            mapping: scrolled_window(200),
+            columns: RefCell::default(),
+            columns_query: RefCell::default(),
+            emitter,
+        });
+
+        wizard.layout();
+        wizard.connect_signals(slot);
+        wizard.reload();
+        wizard.dialog.show();
+
+        wizard
+    }
+
+    fn layout(&self) {
+        let file_options = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+        file_options.append(&gtk4::Label::new(Some("Format")));
+        file_options.append(&self.format);
+        file_options.append(&gtk4::Label::new(Some("Delimiter")));
+        file_options.append(&self.delimiter);
+        file_options.append(&self.header);
+
+        self.preview_errors.set_xalign(0.0);
+        self.preview_errors.set_wrap(true);
+
+        let target = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+        target.append(&gtk4::Label::new(Some("Table")));
+        target.append(&self.table);
+        target.append(&self.match_columns);
+        target.append(&self.create_table);
+
+        let content = gtk4::Box::new(gtk4::Orientation::Vertical, 6);
+        content.set_margin_top(12);
+        content.set_margin_bottom(12);
+        content.set_margin_start(12);
+        content.set_margin_end(12);
+        content.append(&file_options);
+        content.append(&self.preview);
+        content.append(&self.preview_errors);
+        content.append(&target);
+        content.append(&self.mapping);
+        content.append(&self.dry_run);
+
+        self.dialog.content_area().append(&content);
+    }
+
+    fn connect_signals(self: &Rc<Self>, slot: &WizardSlot) {
+        let weak = Rc::downgrade(self);
+        let reload = move || {
+            if let Some(wizard) = weak.upgrade() {
+                wizard.reload();
+            }
+        };
+
+        let on_format = reload.clone();
+        self.format.connect_changed(move |_| on_format());
+        let on_delimiter = reload.clone();
+        self.delimiter.connect_changed(move |_| on_delimiter());
+        self.header.connect_toggled(move |_| reload());
+
+        let weak = Rc::downgrade(self);
+        let match_columns = move || {
+            if let Some(wizard) = weak.upgrade() {
+                wizard.match_columns();
+            }
+        };
+        let on_activate = match_columns.clone();
+        self.table.connect_activate(move |_| on_activate());
+        self.match_columns.connect_clicked(move |_| match_columns());
+
+        let weak = Rc::downgrade(self);
+        let slot = Rc::downgrade(slot);
+        self.dialog.connect_response(move |dialog, response| {
+            if let Some(wizard) = weak.upgrade() {
+                if !wizard.respond(response) {
+                    return;
+                }
+            }
+
+            dialog.close();
+            if let Some(slot) = slot.upgrade() {
+                slot.replace(None);
+            }
+        });
+    }
+
+    /// Returns whether the dialog is done
+    fn respond(&self, response: gtk4::ResponseType) -> bool {
+        let request = match response {
+            gtk4::ResponseType::Accept | OPEN_SQL => self.request(),
+            _ => return true,
+        };
+
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => {
+                self.emitter.emit(AppEvent::Err(err));
+                return false;
+            }
+        };
+
+        if response == OPEN_SQL {
+            let mut text = String::new();
+            if request.create_table {
+                text.push_str(&format!("{};\n\n", request.create_table_sql()));
+            }
+            text.push_str(&format!("{};\n", request.copy_sql()));
+
+            self.emitter.emit(AppEvent::OpenScript {
+                title: format!("Import into {}", request.table),
+                text,
+            });
+            return false;
+        }
+
+        self.emitter
+            .emit(AppEvent::ImportRequest(Arc::new(request)));
+        true
+    }
+
+    fn import_format(&self) -> Result<ImportFormat> {
+        let format = match self.format.active_id().as_deref() {
+            Some("jsonl") => ImportFormat::JsonLines,
+            Some("tsv") => ImportFormat::Csv {
+                delimiter: b'\t',
+                header: self.header.is_active(),
+            },
+            _ => ImportFormat::Csv {
+                delimiter: single_byte("Delimiter", &self.delimiter.text())?,
+                header: self.header.is_active(),
+            },
+        };
+
+        Ok(format)
+    }
+
+    /// Reads the preview again, e.g. after the format changed
+    fn reload(&self) {
+        let id = self.format.active_id();
+        self.delimiter.set_sensitive(id.as_deref() == Some("csv"));
+        self.header.set_sensitive(id.as_deref() != Some("jsonl"));
+
+        let preview = self
+            .import_format()
+            .and_then(|format| import::preview(&self.path, format));
+
+        let preview = match preview {
+            Ok(preview) => preview,
+            Err(err) => {
+                self.preview.set_child(None::<&gtk4::Widget>);
+                self.mapping.set_child(None::<&gtk4::Widget>);
+                self.columns.borrow_mut().clear();
+                self.preview_errors.set_text(&format!("{:#}", err));
+                return;
+            }
+        };
+
+        self.preview.set_child(Some(&preview_view(&preview)));
+        self.preview_errors.set_text(&preview_errors(&preview));
+
+        let (grid, rows) = mapping_grid(&preview);
+        self.mapping.set_child(Some(&grid));
+        self.columns.replace(rows);
+    }
+
+    /// Requests the columns of the target table, see `on_columns_loaded`
+    fn match_columns(&self) {
+        let table = self.table.text().trim().to_string();
+        if table.is_empty() {
+            return;
+        }
+
+        let (schema, relation) = match table.find('.') {
+            Some(dot) => (&table[..dot], &table[dot + 1..]),
+            None => ("public", table.as_str()),
+        };
+
+        let query = CatalogQuery::RelationObjects {
+            schema: schema.to_string(),
+            relation: relation.to_string(),
+            kind: ObjectKind::Column,
+        };
+
+        self.columns_query.replace(Some(query.clone()));
+        self.emitter.emit(AppEvent::CatalogRequest(query));
+    }
+
+    /// Maps the file columns to the table columns of the same name, a table without columns is
+    /// taken as missing and is going to be created
+    fn on_columns_loaded(&self, query: &CatalogQuery, objects: Option<&[CatalogObject]>) {
+        if self.columns_query.borrow().as_ref() != Some(query) {
+            return;
+        }
+        self.columns_query.replace(None);
+
+        // failures are reported by the session
+        let objects = match objects {
+            Some(objects) => objects,
+            None => return,
+        };
+
+        self.create_table.set_active(objects.is_empty());
+        if objects.is_empty() {
+            return;
+        }
+
+        for row in self.columns.borrow().iter() {
+            let current = row.target();
+
+            row.target.remove_all();
+            for object in objects {
+                row.target.append_text(&object.name);
+            }
+
+            match objects
+                .iter()
+                .find(|object| object.name.eq_ignore_ascii_case(&current))
+            {
+                Some(object) => {
+                    row.set_target(&object.name);
+                    row.data_type
+                        .set_text(object.detail.as_deref().unwrap_or_default());
+                }
+                None => row.set_target(""),
+            }
+        }
+    }
+
+    fn request(&self) -> Result<ImportRequest> {
+        let table = self.table.text().trim().to_string();
+        if table.is_empty() {
+            bail!("Name the table to import into");
+        }
+
+        let columns = self
+            .columns
+            .borrow()
+            .iter()
+            .map(|row| ColumnMapping {
+                source: row.source,
+                target: row.target(),
+                data_type: row.data_type.text().trim().to_string(),
+            })
+            .filter(|column| !column.target.is_empty())
+            .collect::<Vec<_>>();
+
+        if columns.is_empty() {
+            bail!("Map at least one column of the file to a column of the table");
+        }
+
+        let create_table = self.create_table.is_active();
+        if create_table {
+            if let Some(column) = columns.iter().find(|column| column.data_type.is_empty()) {
+                bail!("Column {} needs a type to create the table", column.target);
+            }
+        }
+
+        Ok(ImportRequest {
+            path: self.path.clone(),
+            format: self.import_format()?,
+            table,
+            columns,
+            create_table,
+            dry_run: self.dry_run.is_active(),
+        })
+    }
+}
+
+fn scrolled_window(height: i32) -> gtk4::ScrolledWindow {
+    let window = gtk4::ScrolledWindow::new();
+    window.set_min_content_height(height);
+    window.set_vexpand(true);
+    window
+}
+
+/// Previewed records with the inferred types in column titles
+fn preview_view(preview: &Preview) -> gtk4::TreeView {
+    let store = gtk4::ListStore::new(&vec![glib::Type::STRING; preview.columns.len()]);
+
+    for record in &preview.records {
+        let values = (0..preview.columns.len())
+            .map(|idx| {
+                record
+                    .values
+                    .get(idx)
+                    .cloned()
+                    .flatten()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        let columns = values
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| (idx as u32, value as &dyn ToValue))
+            .collect::<Vec<_>>();
+
+        store.insert_with_values(None, &columns);
+    }
+
+    let view = gtk4::TreeView::with_model(&store);
+    for (idx, (name, data_type)) in preview.columns.iter().zip(&preview.types).enumerate() {
+        let column = gtk4::TreeViewColumn::new();
+        column.set_title(&format!("{} ({})", name, data_type));
+        column.set_resizable(true);
+
+        let text = gtk4::CellRendererText::new();
+        column.pack_start(&text, true);
+        column.add_attribute(&text, "text", idx as i32);
+
+        view.append_column(&column);
+    }
+
+    view
+}
+
+fn preview_errors(preview: &Preview) -> String {
+    match preview.errors.as_slice() {
+        [] => String::new(),
+        [error] => format!("Unreadable row at {}", error),
+        [error, rest @ ..] => format!(
+            "Unreadable row at {}, and {} more in the preview",
+            error,
+            rest.len()
+        ),
+    }
+}
+
+/// Row per file column with its type and the table column it goes to, named alike by default
+fn mapping_grid(preview: &Preview) -> (gtk4::Grid, Vec<ColumnRow>) {
+    let grid = gtk4::Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(6);
+
+    for (col, title) in ["File column", "Type", "Table column"].iter().enumerate() {
+        let label = gtk4::Label::new(None);
+        label.set_markup(&format!("<b>{}</b>", title));
+        label.set_xalign(0.0);
+        grid.attach(&label, col as i32, 0, 1, 1);
+    }
+
+    let mut rows = vec![];
+    for (idx, (name, data_type)) in preview.columns.iter().zip(&preview.types).enumerate() {
+        let label = gtk4::Label::new(Some(name));
+        label.set_xalign(0.0);
+
+        let row = ColumnRow {
+            source: idx,
+            data_type: gtk4::Entry::new(),
+            target: gtk4::ComboBoxText::with_entry(),
+        };
+        row.data_type.set_text(data_type);
+        row.data_type
+            .set_tooltip_text(Some("Type of the column when the table is created"));
+        row.target.set_hexpand(true);
+        row.target
+            .set_tooltip_text(Some("Column of the table, empty to skip the file column"));
+        row.set_target(name);
+
+        let line = idx as i32 + 1;
+        grid.attach(&label, 0, line, 1, 1);
+        grid.attach(&row.data_type, 1, line, 1, 1);
+        grid.attach(&row.target, 2, line, 1, 1);
+
+        rows.push(row);
+    }
+
+    (grid, rows)
+}
+
+/// Progress of the running import, which can be canceled from it
+struct ProgressDialog {
+    dialog: gtk4::Dialog,
+    bar: gtk4::ProgressBar,
+    request: Arc<ImportRequest>,
+    /// Size of the file in bytes
+    size: u64,
+}
+
+impl ProgressDialog {
+    fn show(
+        window: &gtk4::ApplicationWindow,
+        request: &Arc<ImportRequest>,
+        emitter: Emitter,
+    ) -> Self {
+        let dialog = gtk4::Dialog::with_buttons(
+            Some(&format!("Importing into {}", request.table)),
+            Some(window),
+            gtk4::DialogFlags::MODAL | gtk4::DialogFlags::DESTROY_WITH_PARENT,
+            &[("Cancel", gtk4::ResponseType::Cancel)],
+        );
+        dialog.set_deletable(false);
+        dialog.set_default_size(400, -1);
+
+        let bar = gtk4::ProgressBar::new();
+        bar.set_show_text(true);
+        bar.set_text(Some("Connecting"));
+        bar.set_margin_top(12);
+        bar.set_margin_bottom(12);
+        bar.set_margin_start(12);
+        bar.set_margin_end(12);
+        dialog.content_area().append(&bar);
+
+        dialog.connect_response(move |dialog, response| {
+            if response == gtk4::ResponseType::Cancel {
+                dialog.set_response_sensitive(gtk4::ResponseType::Cancel, false);
+                emitter.emit(AppEvent::PgCancel { id: IMPORT_SESSION });
+            }
+        });
+        dialog.show();
+
+        Self {
+            dialog,
+            bar,
+            request: Arc::clone(request),
+            size: fs::metadata(&request.path).map_or(0, |meta| meta.len()),
+        }
+    }
+
+    fn update(&self, rows: u64, bytes: u64) {
+        if self.size > 0 {
+            self.bar.set_fraction(bytes as f64 / self.size as f64);
+        }
+        self.bar.set_text(Some(&format!("{} rows", rows)));
+    }
+
+    /// Closes the dialog, reporting the outcome unless the import failed outright
+    fn finish(self, window: &gtk4::ApplicationWindow, report: Option<&ImportReport>) {
+        self.dialog.close();
+
+        if let Some(report) = report {
+            show_report(window, &self.request, report);
+        }
+    }
+}
+
+fn show_report(window: &gtk4::ApplicationWindow, request: &ImportRequest, report: &ImportReport) {
+    let errors = report.errors.len();
+
+    let (message_type, text) = match (errors, report.dry_run) {
+        (0, false) => (
+            gtk4::MessageType::Info,
+            format!("Imported {} rows into {}", report.rows, request.table),
+        ),
+        (0, true) => (
+            gtk4::MessageType::Info,
+            format!(
+                "Dry run: {} rows can be imported into {}",
+                report.rows, request.table
+            ),
+        ),
+        (_, false) => (
+            gtk4::MessageType::Error,
+            format!("Nothing was imported, {} rows were rejected", errors),
+        ),
+        (_, true) => (
+            gtk4::MessageType::Error,
+            format!("Dry run: {} rows would be rejected", errors),
+        ),
+    };
+
+    let mut details = report
+        .errors
+        .iter()
+        .take(REPORTED_ERRORS)
+        .map(|error| error.to_string())
+        .collect::<Vec<_>>();
+    if errors > REPORTED_ERRORS {
+        details.push(format!("… and {} more", errors - REPORTED_ERRORS));
+    }
+    if errors >= MAX_ROW_ERRORS {
+        details.push(format!("Reading stopped after {} rejected rows.", errors));
+    }
+    if report.dry_run {
+        details.push("The transaction was rolled back.".into());
+    }
+
+    let dialog = gtk4::MessageDialogBuilder::new()
+        .transient_for(window)
+        .modal(true)
+        .message_type(message_type)
+        .buttons(gtk4::ButtonsType::Ok)
+        .text(&text)
+        .secondary_text(&details.join("\n"))
+        .build();
+
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show();
+}
@@ -1,23 +1,406 @@
-use crate::object_or_expect;
-use gtk4::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    event::{AppAction, AppEvent, ConnectionState, Emitter, EventListener, TransactionStatus},
+    model::{connection_profile::ConnectionProfile, pg_response::QueryStats},
+    object_or_expect,
+    util::format_duration,
+};
+use glib::clone;
+use gtk4::{gio::SimpleAction, prelude::*};
+
+const TITLE: &str = "slonik";
 
 pub struct MainWindow {
-    _window: gtk4::ApplicationWindow,
+    window: gtk4::ApplicationWindow,
+    busy_spinner: gtk4::Spinner,
+    transaction_status: gtk4::Label,
+    busy_sessions: HashSet<usize>,
+    /// Last transaction status and auto-commit mode of each session
+    transaction_statuses: HashMap<usize, (TransactionStatus, bool)>,
+    /// Session of the current editor tab
+    current_session: usize,
+    /// Sessions with a transaction that would be rolled back on quit
+    open_transactions: HashSet<usize>,
+    status_bar: StatusBar,
+    emitter: Emitter,
+    /// What the status bar shows for each session
+    session_statuses: HashMap<usize, SessionStatus>,
+}
+
+/// Labels at the bottom of the window describing the current session
+pub struct StatusBar {
+    pub connection: gtk4::Label,
+    pub result: gtk4::Label,
+}
+
+/// Connection of a session and figures of its last query
+#[derive(Debug, Default)]
+struct SessionStatus {
+    /// `user@host:port/database`
+    connection: Option<String>,
+    state: Option<ConnectionState>,
+    /// Figures of the last batch, the query or the page fetched last
+    stats: Option<QueryStats>,
+    /// Rows of the result fetched so far
+    rows: Option<u64>,
+    /// More rows can be fetched
+    more: bool,
+    /// Time the output took to show the last results
+    render: Option<Duration>,
+}
+
+impl SessionStatus {
+    fn result_text(&self) -> String {
+        let stats = match &self.stats {
+            Some(stats) => stats,
+            None => return String::new(),
+        };
+
+        let mut parts = vec![];
+        if let Some(rows) = self.rows {
+            let more = if self.more { "+" } else { "" };
+            let noun = if rows == 1 && !self.more {
+                "row"
+            } else {
+                "rows"
+            };
+            parts.push(format!("{}{} {}", rows, more, noun));
+        }
+
+        parts.push(format!("first row {}", format_duration(stats.first_row)));
+        if let Some(transfer) = stats.transfer {
+            parts.push(format!("transfer {}", format_duration(transfer)));
+        }
+        if let Some(render) = self.render {
+            parts.push(format!("render {}", format_duration(render)));
+        }
+
+        parts.join(" · ")
+    }
 }
 
 impl MainWindow {
-    pub fn new(window: gtk4::ApplicationWindow) -> Self {
-        Self { _window: window }
+    pub fn new(
+        window: gtk4::ApplicationWindow,
+        busy_spinner: gtk4::Spinner,
+        transaction_status: gtk4::Label,
+        status_bar: StatusBar,
+        emitter: Emitter,
+    ) -> Self {
+        // closing goes through the quit action, so that open buffers get saved
+        window.connect_close_request(clone!(@strong emitter => move |_| {
+            emitter.emit(AppEvent::AppAction(AppAction::Quit));
+            gtk4::Inhibit(true)
+        }));
+
+        Self {
+            window,
+            busy_spinner,
+            transaction_status,
+            busy_sessions: HashSet::new(),
+            transaction_statuses: HashMap::new(),
+            current_session: 0,
+            open_transactions: HashSet::new(),
+            status_bar,
+            session_statuses: HashMap::new(),
+            emitter,
+        }
     }
 
-    pub fn create(builder: &gtk4::Builder, application: &gtk4::Application) -> Self {
+    pub fn create(
+        builder: &gtk4::Builder,
+        application: &gtk4::Application,
+        emitter: Emitter,
+    ) -> Self {
         let window: gtk4::ApplicationWindow = object_or_expect(builder, "main_window");
+        let busy_spinner = object_or_expect(builder, "busy_spinner");
+        let transaction_status = object_or_expect(builder, "transaction_status");
+        let status_bar = StatusBar {
+            connection: object_or_expect(builder, "status_connection"),
+            result: object_or_expect(builder, "status_result"),
+        };
 
         window.set_application(Some(application));
 
+        window.set_title(Some(TITLE));
         window.set_default_size(1000, 800);
         window.show();
 
-        MainWindow::new(window)
+        let main_window = MainWindow::new(
+            window,
+            busy_spinner,
+            transaction_status,
+            status_bar,
+            emitter,
+        );
+        main_window.show_transaction_status(TransactionStatus::Idle, true);
+        main_window.show_session_status();
+
+        main_window
+    }
+
+    fn on_session_busy(&mut self, id: usize, busy: bool) {
+        if busy {
+            self.busy_sessions.insert(id);
+        } else {
+            self.busy_sessions.remove(&id);
+        }
+
+        self.busy_spinner
+            .set_spinning(!self.busy_sessions.is_empty());
+    }
+
+    fn on_transaction_status(&mut self, id: usize, status: TransactionStatus, auto_commit: bool) {
+        if status == TransactionStatus::Idle {
+            self.open_transactions.remove(&id);
+        } else {
+            self.open_transactions.insert(id);
+        }
+
+        self.transaction_statuses.insert(id, (status, auto_commit));
+        if id == self.current_session {
+            self.show_transaction_status(status, auto_commit);
+        }
+    }
+
+    fn on_tab_selected(&mut self, id: usize) {
+        self.current_session = id;
+
+        let (status, auto_commit) = self
+            .transaction_statuses
+            .get(&id)
+            .copied()
+            .unwrap_or((TransactionStatus::Idle, true));
+        self.show_transaction_status(status, auto_commit);
+
+        // the auto-commit toggle reflects the mode of the current session
+        let action = self
+            .window
+            .application()
+            .and_then(|app| app.lookup_action("auto_commit"))
+            .and_then(|action| action.downcast::<SimpleAction>().ok());
+        if let Some(action) = action {
+            action.set_state(&auto_commit.to_variant());
+        }
+
+        self.show_session_status();
+    }
+
+    fn on_session_closed(&mut self, id: usize) {
+        self.transaction_statuses.remove(&id);
+        self.open_transactions.remove(&id);
+        self.session_statuses.remove(&id);
+        self.on_session_busy(id, false);
+    }
+
+    /// Updates the status of session `id` and shows it if the session is the current one
+    fn update_session_status(&mut self, id: usize, update: impl FnOnce(&mut SessionStatus)) {
+        update(self.session_statuses.entry(id).or_default());
+
+        if id == self.current_session {
+            self.show_session_status();
+        }
+    }
+
+    fn on_connected(&mut self, id: usize, profile: &ConnectionProfile) {
+        let connection = format!(
+            "{}@{}:{}/{}",
+            profile.user, profile.host, profile.port, profile.dbname
+        );
+        self.update_session_status(id, |status| status.connection = Some(connection));
+    }
+
+    fn on_profile_selected(&mut self) {
+        // sessions reconnect lazily to the new profile
+        for status in self.session_statuses.values_mut() {
+            status.connection = None;
+            status.state = None;
+        }
+        self.show_session_status();
+    }
+
+    fn show_session_status(&self) {
+        let status = self.session_statuses.get(&self.current_session);
+
+        let label = &self.status_bar.connection;
+        label.remove_css_class("warning");
+        label.remove_css_class("error");
+
+        let connection = status.and_then(|status| status.connection.as_deref());
+        let text = match (status.and_then(|status| status.state), connection) {
+            (Some(ConnectionState::Connecting { attempt: 0 }), _) => "connecting…".to_string(),
+            (Some(ConnectionState::Connecting { attempt }), connection) => {
+                label.add_css_class("warning");
+                format!(
+                    "reconnecting to {} (try {})",
+                    connection.unwrap_or("server"),
+                    attempt
+                )
+            }
+            (Some(ConnectionState::Lost), connection) => {
+                label.add_css_class("error");
+                match connection {
+                    Some(connection) => format!("connection to {} lost", connection),
+                    None => "connection failed".to_string(),
+                }
+            }
+            (_, Some(connection)) => connection.to_string(),
+            (_, None) => "not connected".to_string(),
+        };
+        label.set_text(&text);
+
+        let result = status.map(SessionStatus::result_text).unwrap_or_default();
+        self.status_bar.result.set_text(&result);
+    }
+
+    fn show_transaction_status(&self, status: TransactionStatus, auto_commit: bool) {
+        let label = &self.transaction_status;
+        label.remove_css_class("warning");
+        label.remove_css_class("error");
+
+        let text = match status {
+            TransactionStatus::Idle if auto_commit => "auto-commit",
+            TransactionStatus::Idle => "idle",
+            TransactionStatus::InTransaction => {
+                label.add_css_class("warning");
+                "in transaction"
+            }
+            TransactionStatus::Failed => {
+                label.add_css_class("error");
+                "transaction failed"
+            }
+        };
+
+        label.set_text(text);
+    }
+
+    fn on_quit(&self) {
+        if self.open_transactions.is_empty() {
+            quit(&self.window);
+        } else {
+            confirm_quit(&self.window);
+        }
+    }
+
+    /// Sessions are reconnected to the new profile, open transactions are rolled back
+    fn on_profile_requested(&self, profile: &Arc<ConnectionProfile>) {
+        if self.open_transactions.is_empty() {
+            self.emitter
+                .emit(AppEvent::ProfileSelected(Arc::clone(profile)));
+        } else {
+            confirm_profile_change(&self.window, self.emitter.clone(), Arc::clone(profile));
+        }
+    }
+}
+
+impl EventListener for MainWindow {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::AppAction(AppAction::Quit) => self.on_quit(),
+            AppEvent::ProfileRequested(profile) => self.on_profile_requested(profile),
+            AppEvent::ProfileSelected(profile) => {
+                let title = format!("{} — {}", TITLE, profile.name);
+                self.window.set_title(Some(&title));
+                self.on_profile_selected();
+            }
+            AppEvent::PgSessionBusy { id, busy } => self.on_session_busy(*id, *busy),
+            AppEvent::PgTransactionStatus {
+                id,
+                status,
+                auto_commit,
+            } => self.on_transaction_status(*id, *status, *auto_commit),
+            AppEvent::EditorTabSelected { id } => self.on_tab_selected(*id),
+            AppEvent::PgCloseSession { id } => self.on_session_closed(*id),
+            AppEvent::PgConnected { id, profile } => self.on_connected(*id, profile),
+            AppEvent::PgConnectionState { id, state } => {
+                self.update_session_status(*id, |status| status.state = Some(*state))
+            }
+            AppEvent::PgResponses { id, stats, .. } => self.update_session_status(*id, |status| {
+                status.stats = Some(*stats);
+                status.rows = stats.rows;
+                status.more = false;
+                status.render = None;
+            }),
+            AppEvent::PgRowsFetched { id, stats, .. } => {
+                self.update_session_status(*id, |status| {
+                    status.stats = Some(*stats);
+                    status.rows = match (status.rows, stats.rows) {
+                        (Some(rows), Some(more)) => Some(rows + more),
+                        (rows, more) => rows.or(more),
+                    };
+                })
+            }
+            AppEvent::PgCursorState { id, more } => {
+                self.update_session_status(*id, |status| status.more = *more)
+            }
+            AppEvent::OutputRendered { id, duration } => {
+                self.update_session_status(*id, |status| status.render = Some(*duration))
+            }
+
+            _ => {}
+        }
+    }
+}
+
+fn confirm_quit(window: &gtk4::ApplicationWindow) {
+    let dialog = gtk4::MessageDialogBuilder::new()
+        .transient_for(window)
+        .modal(true)
+        .message_type(gtk4::MessageType::Warning)
+        .text("Quit with an open transaction?")
+        .secondary_text("Uncommitted changes will be rolled back.")
+        .build();
+
+    dialog.add_buttons(&[
+        ("Cancel", gtk4::ResponseType::Cancel),
+        ("Quit", gtk4::ResponseType::Accept),
+    ]);
+
+    dialog.connect_response(clone!(@weak window => move |dialog, response| {
+        dialog.close();
+        if response == gtk4::ResponseType::Accept {
+            quit(&window);
+        }
+    }));
+
+    dialog.show();
+}
+
+fn confirm_profile_change(
+    window: &gtk4::ApplicationWindow,
+    emitter: Emitter,
+    profile: Arc<ConnectionProfile>,
+) {
+    let dialog = gtk4::MessageDialogBuilder::new()
+        .transient_for(window)
+        .modal(true)
+        .message_type(gtk4::MessageType::Warning)
+        .text("Change the connection with an open transaction?")
+        .secondary_text("Uncommitted changes will be rolled back.")
+        .build();
+
+    dialog.add_buttons(&[
+        ("Cancel", gtk4::ResponseType::Cancel),
+        ("Connect", gtk4::ResponseType::Accept),
+    ]);
+
+    dialog.connect_response(move |dialog, response| {
+        dialog.close();
+        if response == gtk4::ResponseType::Accept {
+            emitter.emit(AppEvent::ProfileSelected(Arc::clone(&profile)));
+        }
+    });
+
+    dialog.show();
+}
+
+fn quit(window: &gtk4::ApplicationWindow) {
+    if let Some(app) = window.application() {
+        app.quit();
     }
 }
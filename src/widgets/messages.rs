@@ -1,15 +1,24 @@
-use std::ops::Deref;
+use std::{cell::RefCell, rc::Rc};
 
+use glib::clone;
 use gtk4::prelude::*;
-use tokio_postgres::AsyncMessage;
 
 use crate::{
     event::{AppEvent, EventListener},
+    model::pg_message::{MessageLog, ServerMessage, Severity},
     object_or_expect,
 };
 
+/// Capacity of the ring buffer of server messages, the lines of a message pushed out are removed
+/// from the top of the text
+const LOG_CAPACITY: usize = 5000;
+
+/// Append-only log of the notices and warnings sent by the server, colored by severity
 pub struct Messages {
     widget: gtk4::TextView,
+    /// Right gravity mark staying behind the last message, scrolled to after each append
+    end: gtk4::TextMark,
+    log: Rc<RefCell<MessageLog>>,
 }
 
 impl Messages {
@@ -17,27 +26,73 @@ impl Messages {
         let widget: gtk4::TextView = object_or_expect(builder, "messages");
         widget.set_monospace(true);
 
-        Self { widget }
+        let buffer = widget.buffer();
+        let end = buffer.create_mark(None, &buffer.end_iter(), false);
+        buffer.create_tag(Some(tag(Severity::Debug)), &[("foreground", &"gray")]);
+        buffer.create_tag(Some(tag(Severity::Log)), &[("foreground", &"gray")]);
+        buffer.create_tag(Some(tag(Severity::Info)), &[("foreground", &"steelblue")]);
+        buffer.create_tag(Some(tag(Severity::Notice)), &[]);
+        buffer.create_tag(
+            Some(tag(Severity::Warning)),
+            &[("foreground", &"darkorange"), ("weight", &700)],
+        );
+
+        let log = Rc::new(RefCell::new(MessageLog::new(LOG_CAPACITY)));
+
+        let clear: gtk4::Button = object_or_expect(builder, "messages_clear");
+        clear.connect_clicked(clone!(@strong log, @weak buffer => move |_| {
+            log.borrow_mut().clear();
+            buffer.set_text("");
+        }));
+
+        let copy: gtk4::Button = object_or_expect(builder, "messages_copy");
+        copy.connect_clicked(clone!(@weak widget => move |_| {
+            let buffer = widget.buffer();
+            let (begin, end) = buffer
+                .selection_bounds()
+                .unwrap_or_else(|| buffer.bounds());
+            widget.clipboard().set_text(&buffer.text(&begin, &end, false));
+        }));
+
+        Self { widget, end, log }
+    }
+
+    fn append(&self, message: &ServerMessage) {
+        let buffer = self.widget.buffer();
+        let text = message.format();
+
+        if let Some(dropped) = self.log.borrow_mut().push(message.clone()) {
+            let mut end = buffer.start_iter();
+            end.forward_lines(dropped.format().lines().count() as i32);
+            buffer.delete(&mut buffer.start_iter(), &mut end);
+        }
+
+        let offset = buffer.end_iter().offset();
+        buffer.insert(&mut buffer.end_iter(), &text);
+        buffer.apply_tag_by_name(
+            tag(message.level),
+            &buffer.iter_at_offset(offset),
+            &buffer.end_iter(),
+        );
+
+        self.widget.scroll_mark_onscreen(&self.end);
     }
 }
 
 impl EventListener for Messages {
     fn on_event(&mut self, event: &AppEvent) {
-        if let AppEvent::PgMessage(msg) = event {
-            let buf = self.widget.buffer();
-            let new_buffer = match msg.deref() {
-                AsyncMessage::Notice(notice) => notice.to_string(),
-                AsyncMessage::Notification(notifcation) => {
-                    let channel = notifcation.channel();
-                    let payload = notifcation.payload();
-                    let pid = notifcation.process_id();
-
-                    format!("[pid={}, channel={}] {}", pid, channel, payload)
-                }
-                _ => unreachable!(),
-            };
-
-            buf.set_text(&new_buffer);
+        if let AppEvent::PgMessage(message) = event {
+            self.append(message);
         }
     }
 }
+
+fn tag(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Debug => "debug",
+        Severity::Log => "log",
+        Severity::Info => "info",
+        Severity::Notice => "notice",
+        Severity::Warning => "warning",
+    }
+}
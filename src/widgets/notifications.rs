@@ -0,0 +1,228 @@
+use std::{cell::RefCell, collections::BTreeSet, rc::Rc, sync::Arc};
+
+use glib::clone;
+use gtk4::prelude::*;
+
+use crate::{
+    event::{AppEvent, Emitter, EventListener, NotifyCommand},
+    model::notification::Notification,
+    object_or_expect,
+};
+
+/// Messages kept in the log, the oldest ones are dropped first
+const LOG_LIMIT: usize = 10_000;
+
+const ALL_CHANNELS: &str = "";
+
+/// Panel listening to channels on a session of its own, with a log of the messages received
+/// which can be filtered by channel. Messages can be sent as well, to try out listeners.
+pub struct Notifications {
+    panel: Rc<Panel>,
+}
+
+impl Notifications {
+    pub fn create(builder: &gtk4::Builder, emitter: Emitter) -> Self {
+        let log: gtk4::TextView = object_or_expect(builder, "notify_log");
+        let buffer = log.buffer();
+        let end = buffer.create_mark(None, &buffer.end_iter(), false);
+
+        let panel = Rc::new(Panel {
+            channel: object_or_expect(builder, "notify_channel"),
+            payload: object_or_expect(builder, "notify_payload"),
+            channels: object_or_expect(builder, "notify_channels"),
+            filter: object_or_expect(builder, "notify_filter"),
+            log,
+            end,
+            listening: Default::default(),
+            messages: Default::default(),
+            emitter,
+        });
+
+        let listen: gtk4::Button = object_or_expect(builder, "notify_listen");
+        listen.connect_clicked(clone!(@weak panel => move |_| panel.listen()));
+        panel
+            .channel
+            .connect_activate(clone!(@weak panel => move |_| panel.listen()));
+
+        let send: gtk4::Button = object_or_expect(builder, "notify_send");
+        send.connect_clicked(clone!(@weak panel => move |_| panel.send()));
+        panel
+            .payload
+            .connect_activate(clone!(@weak panel => move |_| panel.send()));
+
+        let clear: gtk4::Button = object_or_expect(builder, "notify_clear");
+        clear.connect_clicked(clone!(@weak panel => move |_| {
+            panel.messages.borrow_mut().clear();
+            panel.fill_filter();
+            panel.refill();
+        }));
+
+        panel
+            .filter
+            .connect_changed(clone!(@weak panel => move |_| panel.refill()));
+
+        panel.fill_filter();
+
+        Self { panel }
+    }
+}
+
+impl EventListener for Notifications {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::PgNotification(message) => self.panel.record(Arc::clone(message)),
+            AppEvent::NotifyChannels(channels) => self.panel.show_channels(channels.to_vec()),
+            // the session is replaced, listening starts over
+            AppEvent::ProfileSelected(_) => self.panel.show_channels(vec![]),
+            _ => {}
+        }
+    }
+}
+
+/// Widgets of the notifications panel with the messages they show, shared with the signal
+/// handlers
+struct Panel {
+    channel: gtk4::Entry,
+    payload: gtk4::Entry,
+    channels: gtk4::ListBox,
+    filter: gtk4::ComboBoxText,
+    log: gtk4::TextView,
+    /// End of the log, kept in view as messages arrive
+    end: gtk4::TextMark,
+    listening: RefCell<Vec<String>>,
+    messages: RefCell<Vec<Arc<Notification>>>,
+    emitter: Emitter,
+}
+
+impl Panel {
+    fn listen(&self) {
+        let channel = self.channel.text();
+        if !channel.is_empty() {
+            self.emitter
+                .emit(AppEvent::NotifyRequest(NotifyCommand::Listen(
+                    channel.to_string(),
+                )));
+        }
+    }
+
+    fn send(&self) {
+        let channel = self.channel.text();
+        if channel.is_empty() {
+            self.channel.grab_focus();
+            return;
+        }
+
+        self.emitter
+            .emit(AppEvent::NotifyRequest(NotifyCommand::Notify {
+                channel: channel.to_string(),
+                payload: self.payload.text().to_string(),
+            }));
+    }
+
+    fn record(&self, message: Arc<Notification>) {
+        let new_channel = !self.known_channels().contains(message.channel.as_str());
+
+        let mut messages = self.messages.borrow_mut();
+        messages.push(Arc::clone(&message));
+        let overflow = messages.len() > LOG_LIMIT;
+        if overflow {
+            // dropping a tenth at once spares rebuilding the log for every message
+            messages.drain(..LOG_LIMIT / 10);
+        }
+        drop(messages);
+
+        if new_channel {
+            self.fill_filter();
+        }
+
+        if overflow || new_channel {
+            self.refill();
+        } else if shows(self.channel_filter().as_deref(), &message) {
+            let buffer = self.log.buffer();
+            buffer.insert(&mut buffer.end_iter(), &message.format());
+            self.log.scroll_mark_onscreen(&self.end);
+        }
+    }
+
+    fn show_channels(&self, channels: Vec<String>) {
+        while let Some(row) = self.channels.first_child() {
+            self.channels.remove(&row);
+        }
+
+        for channel in &channels {
+            let label = gtk4::Label::new(Some(channel.as_str()));
+            label.set_xalign(0.0);
+            label.set_hexpand(true);
+            label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+
+            let unlisten = gtk4::Button::from_icon_name(Some("list-remove-symbolic"));
+            unlisten.set_has_frame(false);
+            unlisten.set_tooltip_text(Some("Stop listening"));
+            let emitter = self.emitter.clone();
+            let command = NotifyCommand::Unlisten(channel.clone());
+            unlisten.connect_clicked(move |_| {
+                emitter.emit(AppEvent::NotifyRequest(command.clone()));
+            });
+
+            let row = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
+            row.set_margin_start(4);
+            row.append(&label);
+            row.append(&unlisten);
+            self.channels.append(&row);
+        }
+
+        self.listening.replace(channels);
+        self.fill_filter();
+    }
+
+    /// Channels listened to and channels messages were received on, e.g. by editor sessions
+    fn known_channels(&self) -> BTreeSet<String> {
+        let mut channels = self
+            .listening
+            .borrow()
+            .iter()
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        channels.extend(self.messages.borrow().iter().map(|m| m.channel.clone()));
+        channels
+    }
+
+    fn fill_filter(&self) {
+        let active = self.filter.active_id();
+
+        self.filter.remove_all();
+        self.filter.append(Some(ALL_CHANNELS), "All channels");
+        for channel in self.known_channels() {
+            self.filter.append(Some(channel.as_str()), &channel);
+        }
+
+        if active.is_none() || !self.filter.set_active_id(active.as_deref()) {
+            self.filter.set_active_id(Some(ALL_CHANNELS));
+        }
+    }
+
+    fn channel_filter(&self) -> Option<String> {
+        self.filter
+            .active_id()
+            .filter(|id| id != ALL_CHANNELS)
+            .map(String::from)
+    }
+
+    fn refill(&self) {
+        let filter = self.channel_filter();
+        let text = self
+            .messages
+            .borrow()
+            .iter()
+            .filter(|message| shows(filter.as_deref(), message))
+            .map(|message| message.format())
+            .collect::<String>();
+
+        self.log.buffer().set_text(&text);
+        self.log.scroll_mark_onscreen(&self.end);
+    }
+}
+
+fn shows(channel_filter: Option<&str>, message: &Notification) -> bool {
+    !matches!(channel_filter, Some(channel) if channel != message.channel)
+}
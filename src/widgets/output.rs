@@ -1,5 +1,7 @@
 mod csv;
 mod output_mode;
+mod plan;
+mod row_model;
 mod tabular;
 mod tabular_raw;
 mod textutils;
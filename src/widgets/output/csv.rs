@@ -1,7 +1,13 @@
 use csv::ByteRecord;
 
-use super::{output_mode::OutputMode, textutils::format_text};
-use crate::model::pg_response::{PgResponse, Table};
+use super::{
+    output_mode::{Batches, OutputMode},
+    textutils::{append_rows, format_text},
+};
+use crate::model::{
+    pg_response::{Row, Table},
+    value::Value,
+};
 
 #[derive(Debug)]
 pub struct CsvOutputMode {
@@ -22,26 +28,44 @@ impl OutputMode for CsvOutputMode {
         Self { widget }
     }
 
-    fn format_batches(&self, batches: &[PgResponse]) {
-        format_text(&self.widget, batches, format_csv);
+    fn format_batches(&self, batches: &Batches) {
+        format_text(&self.widget, &batches.borrow(), format_csv);
+    }
+
+    fn rows_appended(&self, batches: &Batches, added: usize) {
+        append_rows(&self.widget, batches, added, format_csv_rows);
     }
 }
 
 fn format_csv(table: &Table) -> String {
+    write_csv(table, &table.rows, true)
+}
+
+/// Rows fetched after the first page, they follow the lines shown already
+fn format_csv_rows(table: &Table, rows: &[Row]) -> String {
+    write_csv(table, rows, false)
+}
+
+fn write_csv(table: &Table, rows: &[Row], with_header: bool) -> String {
     let output = vec![];
     let mut writer = csv::WriterBuilder::new()
         .delimiter(b';')
-        .has_headers(true)
+        .has_headers(with_header)
         .from_writer(output);
 
-    let header_record = ByteRecord::from(table.header.columns.as_slice());
-    writer.write_byte_record(&header_record).unwrap();
+    if with_header {
+        let header_record = ByteRecord::from(table.header.names().collect::<Vec<_>>());
+        writer.write_byte_record(&header_record).unwrap();
+    }
 
-    for row in table.rows.iter() {
+    for row in rows.iter() {
         let sanitized_row = row
             .values
             .iter()
-            .map(|x| x.as_deref().map(|x| x.as_bytes()).unwrap_or_default())
+            .map(|x| match x {
+                Value::Null => String::new(),
+                x => x.to_string(),
+            })
             .collect::<Vec<_>>();
 
         let record = ByteRecord::from(sanitized_row.as_slice());
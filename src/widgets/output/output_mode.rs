@@ -1,13 +1,30 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
 use crate::{event::OutputModeChange, model::pg_response::PgResponse};
 
-use super::{csv::CsvOutputMode, tabular::TabularOutputMode, tabular_raw::TabularRawOutputMode};
+use super::{
+    csv::CsvOutputMode, plan::PlanOutputMode, tabular::TabularOutputMode,
+    tabular_raw::TabularRawOutputMode,
+};
+
+/// Responses shown by the output, shared with the event they came with. The last table grows as
+/// further rows are fetched, the responses are copied then only if still shared.
+pub type Batches = Rc<RefCell<Arc<Vec<PgResponse>>>>;
 
 pub trait OutputMode: std::fmt::Debug {
     fn create(parent: &gtk4::ScrolledWindow) -> Self
     where
         Self: Sized;
 
-    fn format_batches(&self, batches: &[PgResponse]);
+    fn format_batches(&self, batches: &Batches);
+
+    /// Called after `added` rows were appended to the last table of `batches`
+    fn rows_appended(&self, batches: &Batches, _added: usize) {
+        self.format_batches(batches);
+    }
+
+    /// Whether the last table can be fetched further
+    fn set_more_rows(&self, _more: bool) {}
 }
 
 pub fn create_output_mode(
@@ -18,5 +35,6 @@ pub fn create_output_mode(
         OutputModeChange::TabularRaw => Box::new(TabularRawOutputMode::create(parent)),
         OutputModeChange::Csv => Box::new(CsvOutputMode::create(parent)),
         OutputModeChange::Tabular => Box::new(TabularOutputMode::create(parent)),
+        OutputModeChange::Plan => Box::new(PlanOutputMode::create(parent)),
     }
 }
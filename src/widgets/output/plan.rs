@@ -0,0 +1,219 @@
+use glib::markup_escape_text;
+use gtk4::prelude::*;
+use serde_json::Value as JsonValue;
+
+use super::output_mode::{Batches, OutputMode};
+use crate::model::explain::{Plan, PlanNode};
+
+const COL_NODE: i32 = 0;
+/// Share of the plan spent in the node itself
+const COL_SHARE: i32 = 1;
+const COL_SELF_TIME: i32 = 2;
+const COL_TOTAL_TIME: i32 = 3;
+const COL_ROWS: i32 = 4;
+const COL_LOOPS: i32 = 5;
+const COL_COST: i32 = 6;
+const COL_BUFFERS: i32 = 7;
+/// Highlight of expensive nodes, unset for the others
+const COL_BACKGROUND: i32 = 8;
+/// Remaining node properties as markup
+const COL_TOOLTIP: i32 = 9;
+
+/// Estimates off by this factor are pointed out
+const MISESTIMATE_FACTOR: f64 = 10.0;
+
+/// Plan of `EXPLAIN (FORMAT JSON)` as a tree of nodes, the most expensive ones highlighted
+#[derive(Debug)]
+pub struct PlanOutputMode {
+    summary: gtk4::Label,
+    view: gtk4::TreeView,
+    store: gtk4::TreeStore,
+}
+
+impl OutputMode for PlanOutputMode {
+    fn create(parent: &gtk4::ScrolledWindow) -> Self
+    where
+        Self: Sized,
+    {
+        let store = gtk4::TreeStore::new(&[
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+            glib::Type::STRING,
+        ]);
+
+        let view = gtk4::TreeView::with_model(&store);
+        view.set_tooltip_column(COL_TOOLTIP);
+        view.set_enable_tree_lines(true);
+        view.append_column(&text_column("Node", COL_NODE));
+        view.append_column(&text_column("Self", COL_SHARE));
+        view.append_column(&text_column("Self time", COL_SELF_TIME));
+        view.append_column(&text_column("Total time", COL_TOTAL_TIME));
+        view.append_column(&text_column("Rows (estimated → actual)", COL_ROWS));
+        view.append_column(&text_column("Loops", COL_LOOPS));
+        view.append_column(&text_column("Cost", COL_COST));
+        view.append_column(&text_column("Buffers", COL_BUFFERS));
+
+        let summary = gtk4::Label::new(None);
+        summary.set_xalign(0.0);
+        summary.set_margin_start(6);
+        summary.set_margin_top(6);
+        summary.set_margin_bottom(6);
+
+        let widget = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+        widget.append(&summary);
+        widget.append(&view);
+        parent.set_child(Some(&widget));
+
+        Self {
+            summary,
+            view,
+            store,
+        }
+    }
+
+    fn format_batches(&self, batches: &Batches) {
+        self.store.clear();
+
+        let plan = match Plan::from_responses(&batches.borrow()) {
+            Some(Ok(plan)) => plan,
+            Some(Err(err)) => return self.summary.set_text(&format!("Malformed plan: {}", err)),
+            None => return self.summary.set_text("The result holds no plan"),
+        };
+
+        self.summary.set_text(&summary(&plan));
+        self.append_node(&plan, &plan.root, None);
+        self.view.expand_all();
+    }
+}
+
+impl PlanOutputMode {
+    fn append_node(&self, plan: &Plan, node: &PlanNode, parent: Option<&gtk4::TreeIter>) {
+        let weight = plan.weight(node);
+        let share = format!("{:.1} %", weight * 100.0);
+        let self_time = node.exclusive_time().map(format_ms).unwrap_or_default();
+        let total_time = node.total_time().map(format_ms).unwrap_or_default();
+        let loops = node
+            .actual_loops
+            .map(|loops| loops.to_string())
+            .unwrap_or_default();
+        let cost = format!("{:.2}..{:.2}", node.startup_cost, node.total_cost);
+
+        let mut rows = match node.actual_rows {
+            Some(actual) => format!("{} → {}", node.plan_rows, actual),
+            None => node.plan_rows.to_string(),
+        };
+        if let Some(factor) = node.misestimate().filter(|f| *f >= MISESTIMATE_FACTOR) {
+            rows.push_str(&format!(" (×{:.0})", factor));
+        }
+
+        let background = heat(weight);
+
+        let iter = self.store.insert_with_values(
+            parent,
+            None,
+            &[
+                (COL_NODE as u32, &node.title()),
+                (COL_SHARE as u32, &share),
+                (COL_SELF_TIME as u32, &self_time),
+                (COL_TOTAL_TIME as u32, &total_time),
+                (COL_ROWS as u32, &rows),
+                (COL_LOOPS as u32, &loops),
+                (COL_COST as u32, &cost),
+                (COL_BUFFERS as u32, &node.buffers()),
+                (COL_BACKGROUND as u32, &background),
+                (COL_TOOLTIP as u32, &tooltip(node)),
+            ],
+        );
+
+        for child in &node.children {
+            self.append_node(plan, child, Some(&iter));
+        }
+    }
+}
+
+fn summary(plan: &Plan) -> String {
+    let mut parts = vec![];
+
+    if let Some(planning) = plan.planning_time {
+        parts.push(format!("Planning {}", format_ms(planning)));
+    }
+    if let Some(execution) = plan.execution_time {
+        parts.push(format!("Execution {}", format_ms(execution)));
+    }
+    parts.push(format!("Total cost {:.2}", plan.root.total_cost));
+    if !plan.is_analyzed() {
+        parts.push("estimates only".into());
+    }
+
+    parts.join(" · ")
+}
+
+/// Background of a node taking `weight` of the whole plan
+fn heat(weight: f64) -> Option<&'static str> {
+    if weight >= 0.5 {
+        Some("rgba(224, 27, 36, 0.35)")
+    } else if weight >= 0.25 {
+        Some("rgba(255, 120, 0, 0.3)")
+    } else if weight >= 0.1 {
+        Some("rgba(246, 211, 45, 0.3)")
+    } else {
+        None
+    }
+}
+
+fn tooltip(node: &PlanNode) -> Option<String> {
+    if node.details.is_empty() {
+        return None;
+    }
+
+    let lines = node
+        .details
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                JsonValue::String(text) => text.clone(),
+                JsonValue::Array(items) => items
+                    .iter()
+                    .map(|item| match item {
+                        JsonValue::String(text) => text.clone(),
+                        item => item.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                value => value.to_string(),
+            };
+
+            format!(
+                "<b>{}</b>: {}",
+                markup_escape_text(key),
+                markup_escape_text(&value)
+            )
+        })
+        .collect::<Vec<_>>();
+
+    Some(lines.join("\n"))
+}
+
+fn format_ms(ms: f64) -> String {
+    format!("{:.3} ms", ms)
+}
+
+fn text_column(title: &str, col: i32) -> gtk4::TreeViewColumn {
+    let column = gtk4::TreeViewColumn::new();
+    column.set_title(title);
+    column.set_resizable(true);
+
+    let text = gtk4::CellRendererText::new();
+    column.pack_start(&text, true);
+    column.add_attribute(&text, "text", col);
+    column.add_attribute(&text, "cell-background", COL_BACKGROUND);
+
+    column
+}
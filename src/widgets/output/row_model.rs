@@ -0,0 +1,98 @@
+use gtk4::{gio, glib, prelude::*, subclass::prelude::*};
+
+mod imp {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    pub struct RowItem {
+        pub index: Cell<u32>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for RowItem {
+        const NAME: &'static str = "SlonikRowItem";
+        type Type = super::RowItem;
+        type ParentType = glib::Object;
+    }
+
+    impl ObjectImpl for RowItem {}
+
+    #[derive(Debug, Default)]
+    pub struct RowModel {
+        pub n_items: Cell<u32>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for RowModel {
+        const NAME: &'static str = "SlonikRowModel";
+        type Type = super::RowModel;
+        type ParentType = glib::Object;
+        type Interfaces = (gio::ListModel,);
+    }
+
+    impl ObjectImpl for RowModel {}
+
+    impl ListModelImpl for RowModel {
+        fn item_type(&self, _list_model: &Self::Type) -> glib::Type {
+            super::RowItem::static_type()
+        }
+
+        fn n_items(&self, _list_model: &Self::Type) -> u32 {
+            self.n_items.get()
+        }
+
+        fn item(&self, _list_model: &Self::Type, position: u32) -> Option<glib::Object> {
+            if position < self.n_items.get() {
+                Some(super::RowItem::new(position).upcast())
+            } else {
+                None
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    /// Index of a row in a result table
+    pub struct RowItem(ObjectSubclass<imp::RowItem>);
+}
+
+impl RowItem {
+    fn new(index: u32) -> Self {
+        let item: Self = glib::Object::new(&[]).expect("Failed to create RowItem");
+        imp::RowItem::from_instance(&item).index.set(index);
+
+        item
+    }
+
+    pub fn index(&self) -> u32 {
+        imp::RowItem::from_instance(self).index.get()
+    }
+}
+
+glib::wrapper! {
+    /// List model over rows of a result table.
+    ///
+    /// It holds no row data, items are created on request so that only the rows a view
+    /// actually shows get materialised.
+    pub struct RowModel(ObjectSubclass<imp::RowModel>) @implements gio::ListModel;
+}
+
+impl RowModel {
+    pub fn new(n_items: u32) -> Self {
+        let model: Self = glib::Object::new(&[]).expect("Failed to create RowModel");
+        imp::RowModel::from_instance(&model).n_items.set(n_items);
+
+        model
+    }
+
+    /// Extends the model by rows appended to its table
+    pub fn append(&self, added: u32) {
+        let n_items = &imp::RowModel::from_instance(self).n_items;
+        let position = n_items.get();
+        n_items.set(position + added);
+
+        self.items_changed(position, 0, added);
+    }
+}
@@ -1,13 +1,41 @@
-use crate::model::pg_response::{PgResponse, Table};
+use std::{cell::RefCell, rc::Rc};
 
-use super::output_mode::OutputMode;
-use glib::ToValue;
-use gtk4::{glib::Type, prelude::*};
-use gtk4::{CellRendererText, Label, ListStore, TextViewBuilder, TreeView, TreeViewColumn};
+use crate::model::{
+    pg_response::{ColumnMeta, PgResponse, Table},
+    value::Value,
+};
+
+use super::{
+    output_mode::{Batches, OutputMode},
+    row_model::{RowItem, RowModel},
+};
+use gtk4::prelude::*;
+use gtk4::{ColumnView, ColumnViewColumn, Label, SignalListItemFactory};
 
 #[derive(Debug)]
 pub struct TabularOutputMode {
-    widget: gtk4::ListBox,
+    widget: gtk4::Notebook,
+    last_table: RefCell<Option<LastTable>>,
+}
+
+/// Page of the last table, which grows as further rows are fetched
+#[derive(Debug)]
+struct LastTable {
+    model: RowModel,
+    title: Label,
+    result: usize,
+    rows: usize,
+    more: bool,
+}
+
+impl LastTable {
+    fn update_title(&self) {
+        let more = if self.more { "+" } else { "" };
+        self.title.set_text(&format!(
+            "Result {} ({}{} rows)",
+            self.result, self.rows, more
+        ));
+    }
 }
 
 impl OutputMode for TabularOutputMode {
@@ -15,78 +43,247 @@ impl OutputMode for TabularOutputMode {
     where
         Self: Sized,
     {
-        let listbox = gtk4::ListBox::new();
+        let notebook = gtk4::Notebook::new();
+        notebook.set_scrollable(true);
 
-        parent.set_child(Some(&listbox));
+        parent.set_child(Some(&notebook));
 
-        Self { widget: listbox }
+        Self {
+            widget: notebook,
+            last_table: RefCell::new(None),
+        }
     }
 
-    fn format_batches(&self, batches: &[PgResponse]) {
-        // remove all previously formatted children
-        while let Some(child) = self.widget.last_child() {
-            self.widget.remove(&child);
+    fn format_batches(&self, batches: &Batches) {
+        // remove all previously formatted pages
+        while self.widget.n_pages() > 0 {
+            self.widget.remove_page(None);
         }
+        self.last_table.replace(None);
 
-        for batch in batches {
-            let child = match batch {
-                PgResponse::Table(t) => format_table(t),
-                PgResponse::CommandComplete(cc) => format_cc(*cc),
+        let responses = batches.borrow();
+        let mut batches_iter = responses.iter().enumerate().peekable();
+        let mut results = 0;
+
+        while let Some((idx, batch)) = batches_iter.next() {
+            let (page, title) = match batch {
+                PgResponse::Table(table) => {
+                    results += 1;
+
+                    // command tag of the statement goes below its rows
+                    let rows_affected = match batches_iter.peek() {
+                        Some((_, PgResponse::CommandComplete(cc))) => {
+                            let cc = *cc;
+                            batches_iter.next();
+                            Some(cc)
+                        }
+                        _ => None,
+                    };
+
+                    let table_ref = TableRef {
+                        batches: Rc::clone(batches),
+                        idx,
+                    };
+                    let (page, model) = format_table(table_ref, table, rows_affected);
+
+                    let last_table = LastTable {
+                        model,
+                        title: Label::new(None),
+                        result: results,
+                        rows: table.rows.len(),
+                        more: false,
+                    };
+                    last_table.update_title();
+
+                    let title = last_table.title.clone();
+                    self.last_table.replace(Some(last_table));
+
+                    (page, title)
+                }
+                PgResponse::CommandComplete(cc) => {
+                    self.last_table.replace(None);
+                    (format_cc(*cc), Label::new(Some("Command")))
+                }
             };
 
-            self.widget.append(&child);
+            self.widget.append_page(&page, Some(&title));
         }
 
         self.widget.show();
     }
+
+    fn rows_appended(&self, _batches: &Batches, added: usize) {
+        if let Some(last_table) = self.last_table.borrow_mut().as_mut() {
+            last_table.rows += added;
+            last_table.model.append(added as u32);
+            last_table.update_title();
+        }
+    }
+
+    fn set_more_rows(&self, more: bool) {
+        if let Some(last_table) = self.last_table.borrow_mut().as_mut() {
+            last_table.more = more;
+            last_table.update_title();
+        }
+    }
+}
+
+/// Table within shared responses, avoids copying rows for the cell factories
+struct TableRef {
+    batches: Batches,
+    idx: usize,
 }
 
-fn format_table(table: &Table) -> gtk4::Widget {
-    let columns_len = table.header.columns.len();
-    let model_ty = (0..columns_len).map(|_| Type::STRING).collect::<Vec<_>>();
+impl TableRef {
+    fn with_table<R>(&self, f: impl FnOnce(&Table) -> R) -> R {
+        match &self.batches.borrow()[self.idx] {
+            PgResponse::Table(table) => f(table),
+            PgResponse::CommandComplete(_) => unreachable!("TableRef points at a table"),
+        }
+    }
+}
+
+fn format_table(
+    table_ref: TableRef,
+    table: &Table,
+    rows_affected: Option<u64>,
+) -> (gtk4::Widget, RowModel) {
+    let table_ref = Rc::new(table_ref);
+    let model = RowModel::new(table.rows.len() as u32);
 
-    let model_ty = model_ty.as_slice();
+    let view = ColumnView::new(None::<&gtk4::SelectionModel>);
+    view.set_show_column_separators(true);
+    view.set_show_row_separators(true);
+    view.add_css_class("data-table");
 
-    let store = ListStore::new(model_ty);
+    // sorted rows are materialised only once a column sort is requested
+    let sorted = gtk4::SortListModel::new(Some(&model), view.sorter().as_ref());
+    view.set_model(Some(&gtk4::MultiSelection::new(Some(&sorted))));
 
-    for row in table.rows.iter() {
-        let iter = store.append();
-        row.values.iter().enumerate().for_each(|(idx, v)| {
-            store.set_value(
-                &iter,
-                idx as u32,
-                &v.as_deref().unwrap_or("[null]").to_value(),
-            )
+    view.append_column(&row_number_column());
+
+    for (idx, meta) in table.header.columns.iter().enumerate() {
+        let numeric = meta.is_numeric();
+        let factory = SignalListItemFactory::new();
+
+        factory.connect_setup(move |_, list_item| {
+            let label = Label::new(None);
+            label.set_xalign(if numeric { 1.0 } else { 0.0 });
+            label.set_single_line_mode(true);
+            label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+            list_item.set_child(Some(&label));
         });
-    }
 
-    let view = TreeView::new();
-    view.set_headers_visible(true);
-    view.set_model(Some(&store));
+        let cells = Rc::clone(&table_ref);
+        factory.connect_bind(move |_, list_item| {
+            let (label, row) = match bound_cell(list_item) {
+                Some(bound) => bound,
+                None => return,
+            };
+
+            cells.with_table(|table| {
+                let value = &table.rows[row as usize].values[idx];
+                label.set_text(&display(value));
+                if value.is_null() {
+                    label.add_css_class("dim-label");
+                } else {
+                    label.remove_css_class("dim-label");
+                }
+            });
+        });
 
-    for (idx, name) in table.header.columns.iter().enumerate() {
-        let column = TreeViewColumn::new();
-        let cell = CellRendererText::new();
+        let sort_table = Rc::clone(&table_ref);
+        let sorter = gtk4::CustomSorter::new(move |a, b| {
+            let row = |item: &glib::Object| item.downcast_ref::<RowItem>().unwrap().index();
+            let (a, b) = (row(a) as usize, row(b) as usize);
 
-        column.pack_start(&cell, true);
-        // Association of the view's column with the model's `id` column.
-        column.add_attribute(&cell, "text", idx as _);
-        column.set_widget(Some(&Label::new(Some(name))));
+            sort_table
+                .with_table(|table| table.rows[a].values[idx].sort_cmp(&table.rows[b].values[idx]))
+                .into()
+        });
+
+        let column = ColumnViewColumn::new(Some(&meta.name), Some(&factory));
+        column.set_resizable(true);
+        column.set_sorter(Some(&sorter));
+        column.set_header_menu(Some(&column_details(meta)));
         view.append_column(&column);
     }
-    view.show();
 
-    view.upcast()
+    let scrolled = gtk4::ScrolledWindow::new();
+    scrolled.set_vexpand(true);
+    scrolled.set_hexpand(true);
+    scrolled.set_child(Some(&view));
+
+    let page = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+    page.append(&scrolled);
+
+    if let Some(rows_affected) = rows_affected {
+        let status = Label::new(Some(&format!("Rows affected: {}", rows_affected)));
+        status.set_xalign(0.0);
+        status.set_margin_start(6);
+        page.append(&status);
+    }
+
+    (page.upcast(), model)
 }
 
-fn format_cc(rows_affected: u64) -> gtk4::Widget {
-    let widget = TextViewBuilder::new()
-        .editable(false)
-        .monospace(true)
-        .build();
+/// Column showing 1-based row numbers, numbers follow the original row order
+fn row_number_column() -> ColumnViewColumn {
+    let factory = SignalListItemFactory::new();
+
+    factory.connect_setup(|_, list_item| {
+        let label = Label::new(None);
+        label.set_xalign(1.0);
+        label.add_css_class("dim-label");
+        list_item.set_child(Some(&label));
+    });
+
+    factory.connect_bind(|_, list_item| {
+        if let Some((label, row)) = bound_cell(list_item) {
+            label.set_text(&(row + 1).to_string());
+        }
+    });
+
+    let column = ColumnViewColumn::new(Some("#"), Some(&factory));
+    column.set_resizable(true);
 
-    let buf = widget.buffer();
-    buf.set_text(&format!("Rows affected: {}", rows_affected));
+    column
+}
+
+fn bound_cell(list_item: &gtk4::ListItem) -> Option<(Label, u32)> {
+    let label = list_item.child()?.downcast::<Label>().ok()?;
+    let row = list_item.item()?.downcast::<RowItem>().ok()?.index();
+
+    Some((label, row))
+}
+
+/// Menu of a column header describing the column, header widgets cannot show tooltips
+fn column_details(meta: &ColumnMeta) -> gtk4::gio::Menu {
+    let menu = gtk4::gio::Menu::new();
+    menu.append(Some(meta.type_display().as_str()), None);
+
+    if let (Some(table_oid), Some(column_id)) = (meta.table_oid, meta.column_id) {
+        let origin = format!("table oid {}, column #{}", table_oid, column_id);
+        menu.append(Some(origin.as_str()), None);
+    }
+
+    menu
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::Bool(true) => "☑".into(),
+        Value::Bool(false) => "☐".into(),
+        value => value.to_string(),
+    }
+}
+
+fn format_cc(rows_affected: u64) -> gtk4::Widget {
+    let label = Label::new(Some(&format!("Rows affected: {}", rows_affected)));
+    label.set_xalign(0.0);
+    label.set_yalign(0.0);
+    label.set_margin_start(6);
+    label.set_margin_top(6);
 
-    widget.upcast()
+    label.upcast()
 }
@@ -1,5 +1,8 @@
-use super::{output_mode::OutputMode, textutils::format_text};
-use crate::model::pg_response::{PgResponse, Table};
+use super::{
+    output_mode::{Batches, OutputMode},
+    textutils::{append_rows, format_text},
+};
+use crate::model::pg_response::{Row, Table};
 
 #[derive(Debug)]
 pub struct TabularRawOutputMode {
@@ -7,8 +10,12 @@ pub struct TabularRawOutputMode {
 }
 
 impl OutputMode for TabularRawOutputMode {
-    fn format_batches(&self, batches: &[PgResponse]) {
-        format_text(&self.widget, batches, format_raw);
+    fn format_batches(&self, batches: &Batches) {
+        format_text(&self.widget, &batches.borrow(), format_raw);
+    }
+
+    fn rows_appended(&self, batches: &Batches, added: usize) {
+        append_rows(&self.widget, batches, added, format_raw_rows);
     }
 
     fn create(parent: &gtk4::ScrolledWindow) -> Self
@@ -27,19 +34,29 @@ impl OutputMode for TabularRawOutputMode {
 
 fn format_raw(table: &Table) -> String {
     let mut prettytable = prettytable::Table::new();
-    prettytable.add_row(table.header.columns.clone().into());
-    table
-        .rows
-        .iter()
-        .map(|row| {
-            row.values
-                .iter()
-                .map(|x| x.as_deref().unwrap_or("[null]"))
-                .collect::<Vec<_>>()
-        })
+    prettytable.add_row(table.header.names().collect::<Vec<_>>().into());
+    add_rows(&mut prettytable, &table.rows);
+
+    prettytable.to_string()
+}
+
+/// Rows fetched after the first page, continuing the table below its bottom border. The columns
+/// are sized for these rows alone.
+fn format_raw_rows(_table: &Table, rows: &[Row]) -> String {
+    let mut prettytable = prettytable::Table::new();
+    add_rows(&mut prettytable, rows);
+
+    let text = prettytable.to_string();
+    match text.find('\n') {
+        Some(top_border) => text[top_border + 1..].to_string(),
+        None => text,
+    }
+}
+
+fn add_rows(prettytable: &mut prettytable::Table, rows: &[Row]) {
+    rows.iter()
+        .map(|row| row.values.iter().map(|x| x.to_string()).collect::<Vec<_>>())
         .for_each(|row| {
             prettytable.add_row(row.into());
         });
-
-    prettytable.to_string()
 }
@@ -1,7 +1,8 @@
 use gtk4::prelude::*;
 use itertools::Itertools;
 
-use crate::model::pg_response::{PgResponse, Table};
+use super::output_mode::Batches;
+use crate::model::pg_response::{PgResponse, Row, Table};
 
 pub fn format_text(
     widget: &gtk4::TextView,
@@ -19,3 +20,27 @@ pub fn format_text(
     let buffer = widget.buffer();
     buffer.set_text(&txt);
 }
+
+/// Appends the text of the last `added` rows of the last table, leaving the text shown so far
+/// as it is
+pub fn append_rows(
+    widget: &gtk4::TextView,
+    batches: &Batches,
+    added: usize,
+    fmt_rows_callback: fn(&Table, &[Row]) -> String,
+) {
+    let batches = batches.borrow();
+    let table = match batches.last() {
+        Some(PgResponse::Table(table)) => table,
+        _ => return,
+    };
+    let rows = &table.rows[table.rows.len().saturating_sub(added)..];
+
+    let buffer = widget.buffer();
+    let mut end = buffer.end_iter();
+    let mut last = end.clone();
+    if last.backward_char() && last.char() != '\n' {
+        buffer.insert(&mut end, "\n");
+    }
+    buffer.insert(&mut end, &fmt_rows_callback(table, rows));
+}
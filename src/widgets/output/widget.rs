@@ -1,26 +1,61 @@
-use std::{sync::Arc, time::Instant};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use gtk4::prelude::*;
-use tokio_postgres::error::DbError;
 
 use crate::{
     event::{AppEvent, Emitter, EventListener, OutputModeChange},
-    model::pg_response::PgResponse,
+    model::{
+        explain::Plan,
+        pg_response::{PgResponse, Row},
+    },
     object_or_expect,
 };
 
-use super::output_mode::{create_output_mode, OutputMode};
+use super::output_mode::{create_output_mode, Batches, OutputMode};
 
+/// Results of a single session
 pub struct Output {
+    id: usize,
+    /// Mode chosen with the output mode buttons
+    mode: OutputModeChange,
+    /// Mode of `output_mode`, differs from `mode` for results holding a plan
+    shown_mode: OutputModeChange,
     output_mode: Box<dyn OutputMode>,
     output_buffer: gtk4::ScrolledWindow,
-    batches: Arc<Vec<PgResponse>>,
+    batches: Batches,
+    /// Last table can be fetched further
+    more: bool,
+    emitter: Emitter,
 }
 
 impl Output {
-    pub fn create(builder: &gtk4::Builder, emitter: Emitter) -> Self {
-        let output_buffer: gtk4::ScrolledWindow = object_or_expect(builder, "output");
+    pub fn create(
+        id: usize,
+        output_buffer: gtk4::ScrolledWindow,
+        mode: OutputModeChange,
+        emitter: Emitter,
+    ) -> Self {
+        let output_mode = create_output_mode(&output_buffer, mode);
+
+        Self {
+            id,
+            mode,
+            shown_mode: mode,
+            output_buffer,
+            output_mode,
+            batches: Default::default(),
+            more: false,
+            emitter,
+        }
+    }
 
+    /// Makes the output mode buttons switch the mode of all outputs
+    pub fn connect_mode_buttons(builder: &gtk4::Builder, emitter: Emitter) {
         let radio_tabular: gtk4::CheckButton = object_or_expect(builder, "output-mode-tabular");
         let radio_csv: gtk4::CheckButton = object_or_expect(builder, "output-mode-csv");
         let radio_tabular_raw: gtk4::CheckButton =
@@ -45,34 +80,74 @@ impl Output {
                 emitter.emit(AppEvent::OutputModeChanged(OutputModeChange::TabularRaw))
             }
         });
+    }
 
-        let output_mode = create_output_mode(&output_buffer, OutputModeChange::Tabular);
+    /// Result holding a table with the rows fetched so far, and whether more can be fetched
+    pub fn last_result(&self) -> Option<(Arc<Vec<PgResponse>>, bool)> {
+        let batches = self.batches.borrow();
+        PgResponse::last_table(&batches)?;
 
-        Self {
-            output_buffer,
-            output_mode,
-            batches: Arc::new(vec![]),
+        Some((Arc::clone(&batches), self.more))
+    }
+
+    fn on_pg_response(&mut self, responses: &Arc<Vec<PgResponse>>) {
+        self.batches = Rc::new(RefCell::new(Arc::clone(responses)));
+        self.more = false;
+        self.show_in_mode();
+    }
+
+    fn on_rows_fetched(&self, rows: &[Row]) {
+        let appended = match Arc::make_mut(&mut self.batches.borrow_mut()).last_mut() {
+            Some(PgResponse::Table(table)) => {
+                table.rows.extend(rows.iter().cloned());
+                true
+            }
+            _ => false,
+        };
+
+        if appended {
+            let instant = Instant::now();
+            self.output_mode.rows_appended(&self.batches, rows.len());
+            log::info!("Appending {} rows took {:?}", rows.len(), instant.elapsed());
+            self.rendered(instant.elapsed());
         }
     }
 
-    fn on_pg_response(&mut self, responses: Arc<Vec<PgResponse>>) {
-        self.batches = responses;
-        self.format_batches();
+    fn on_cursor_state(&mut self, more: bool) {
+        self.more = more;
+        self.output_mode.set_more_rows(more);
     }
 
-    fn on_db_err(&self, err: &DbError) {
+    fn on_query_canceled(&self) {
         let dialog = gtk4::MessageDialogBuilder::new()
-            .modal(true)
-            .title("Database error")
-            .text(err.message())
+            .title("Query canceled")
+            .message_type(gtk4::MessageType::Info)
+            .text("Canceling statement due to user request")
             .buttons(gtk4::ButtonsType::Close)
             .build();
 
+        dialog.connect_response(|dialog, _| dialog.close());
         dialog.show();
     }
 
     fn on_output_mode_changed(&mut self, ty: OutputModeChange) {
-        self.output_mode = create_output_mode(&self.output_buffer, ty);
+        self.mode = ty;
+        self.show_in_mode();
+    }
+
+    /// Formats the batches in the chosen mode, plans are always shown as a tree
+    fn show_in_mode(&mut self) {
+        let mode = if Plan::from_responses(&self.batches.borrow()).is_some() {
+            OutputModeChange::Plan
+        } else {
+            self.mode
+        };
+
+        if mode != self.shown_mode {
+            self.output_mode = create_output_mode(&self.output_buffer, mode);
+            self.shown_mode = mode;
+        }
+
         self.format_batches();
     }
 }
@@ -81,26 +156,30 @@ impl Output {
     fn format_batches(&self) {
         let instant = Instant::now();
         self.output_mode.format_batches(&self.batches);
+        self.output_mode.set_more_rows(self.more);
         log::info!("Formatting batches took {:?}", instant.elapsed());
+        self.rendered(instant.elapsed());
+    }
+
+    fn rendered(&self, duration: Duration) {
+        self.emitter.emit(AppEvent::OutputRendered {
+            id: self.id,
+            duration,
+        });
     }
 }
 
 impl EventListener for Output {
     fn on_event(&mut self, event: &AppEvent) {
-        use tokio_postgres::Error as PgError;
-
         match event {
-            AppEvent::PgResponses { id: _, responses } => {
-                self.on_pg_response(Arc::clone(responses))
+            AppEvent::PgResponses { id, responses, .. } if *id == self.id => {
+                self.on_pg_response(responses)
             }
-            AppEvent::Err(err) => {
-                let db_err = match err.downcast_ref::<PgError>().and_then(PgError::as_db_error) {
-                    Some(err) => err,
-                    None => return,
-                };
-
-                self.on_db_err(db_err);
+            AppEvent::PgRowsFetched { id, rows, .. } if *id == self.id => {
+                self.on_rows_fetched(rows)
             }
+            AppEvent::PgCursorState { id, more } if *id == self.id => self.on_cursor_state(*more),
+            AppEvent::PgQueryCanceled { id } if *id == self.id => self.on_query_canceled(),
             AppEvent::OutputModeChanged(ty) => {
                 self.on_output_mode_changed(*ty);
             }
@@ -0,0 +1,177 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use glib::clone;
+use gtk4::prelude::*;
+
+use crate::{
+    event::{AppEvent, Emitter, EventListener, PgRequest},
+    model::query_params::{ParsedQuery, QueryParam, PARAM_TYPES},
+    object_or_expect,
+};
+
+const AUTO_TYPE: &str = "auto";
+
+/// Asks for values of statement placeholders before the statement is sent
+pub struct ParamsDialog {
+    window: gtk4::ApplicationWindow,
+    emitter: Emitter,
+    /// Last used parameters by statement text, kept for the lifetime of the application
+    history: Rc<RefCell<HashMap<String, Vec<QueryParam>>>>,
+}
+
+impl ParamsDialog {
+    pub fn create(builder: &gtk4::Builder, emitter: Emitter) -> Self {
+        let window = object_or_expect(builder, "main_window");
+
+        Self {
+            window,
+            emitter,
+            history: Default::default(),
+        }
+    }
+
+    fn show_dialog(&self, id: usize, query: &ParsedQuery) {
+        let dialog = gtk4::Dialog::with_buttons(
+            Some("Query parameters"),
+            Some(&self.window),
+            gtk4::DialogFlags::MODAL | gtk4::DialogFlags::DESTROY_WITH_PARENT,
+            &[
+                ("Cancel", gtk4::ResponseType::Cancel),
+                ("Execute", gtk4::ResponseType::Accept),
+            ],
+        );
+        dialog.set_default_response(gtk4::ResponseType::Accept);
+
+        let grid = gtk4::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(6);
+        grid.set_margin_top(12);
+        grid.set_margin_bottom(12);
+        grid.set_margin_start(12);
+        grid.set_margin_end(12);
+
+        for (col, title) in ["Parameter", "Type", "Value", "NULL"].iter().enumerate() {
+            let label = gtk4::Label::new(None);
+            label.set_markup(&format!("<b>{}</b>", title));
+            label.set_xalign(0.0);
+            grid.attach(&label, col as i32, 0, 1, 1);
+        }
+
+        let previous = self.history.borrow().get(&query.text).cloned();
+        let inputs = query
+            .params
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                let last_used = previous
+                    .as_ref()
+                    .and_then(|params| params.iter().find(|p| &p.name == name));
+                let input = ParamInput::new(name, last_used);
+
+                let row = idx as i32 + 1;
+                grid.attach(&gtk4::Label::new(Some(name)), 0, row, 1, 1);
+                grid.attach(&input.ty, 1, row, 1, 1);
+                grid.attach(&input.value, 2, row, 1, 1);
+                grid.attach(&input.null, 3, row, 1, 1);
+
+                input
+            })
+            .collect::<Vec<_>>();
+
+        let emitter = self.emitter.clone();
+        let history = Rc::clone(&self.history);
+        let query = query.clone();
+        dialog.connect_response(move |dialog, response| {
+            if response == gtk4::ResponseType::Accept {
+                let params = inputs.iter().map(ParamInput::param).collect::<Vec<_>>();
+                history
+                    .borrow_mut()
+                    .insert(query.text.clone(), params.clone());
+
+                emitter.emit(AppEvent::PgRequest(PgRequest {
+                    id,
+                    text: query.text.clone(),
+                    params,
+                    copy_file: None,
+                }));
+            }
+
+            dialog.close();
+        });
+
+        dialog.content_area().append(&grid);
+        dialog.show();
+    }
+}
+
+impl EventListener for ParamsDialog {
+    fn on_event(&mut self, event: &AppEvent) {
+        if let AppEvent::PgParamsRequired { id, query } = event {
+            self.show_dialog(*id, query);
+        }
+    }
+}
+
+struct ParamInput {
+    name: String,
+    ty: gtk4::ComboBoxText,
+    value: gtk4::Entry,
+    null: gtk4::CheckButton,
+}
+
+impl ParamInput {
+    fn new(name: &str, last_used: Option<&QueryParam>) -> Self {
+        let ty = gtk4::ComboBoxText::new();
+        ty.append(Some(AUTO_TYPE), AUTO_TYPE);
+        for param_type in PARAM_TYPES {
+            ty.append(Some(param_type.name()), param_type.name());
+        }
+
+        let value = gtk4::Entry::new();
+        value.set_hexpand(true);
+        value.set_activates_default(true);
+
+        let null = gtk4::CheckButton::new();
+        null.connect_toggled(clone!(@weak value => move |null| {
+            value.set_sensitive(!null.is_active());
+        }));
+
+        let type_id = last_used
+            .and_then(|p| p.ty.as_ref())
+            .map_or(AUTO_TYPE, |ty| ty.name());
+        ty.set_active_id(Some(type_id));
+
+        match last_used.map(|p| p.value.as_deref()) {
+            Some(Some(text)) => value.set_text(text),
+            Some(None) => null.set_active(true),
+            None => {}
+        }
+
+        Self {
+            name: name.to_string(),
+            ty,
+            value,
+            null,
+        }
+    }
+
+    fn param(&self) -> QueryParam {
+        let type_id = self.ty.active_id();
+        let ty = PARAM_TYPES
+            .iter()
+            .find(|ty| type_id.as_deref() == Some(ty.name()))
+            .cloned();
+
+        let value = if self.null.is_active() {
+            None
+        } else {
+            Some(self.value.text().to_string())
+        };
+
+        QueryParam {
+            name: self.name.clone(),
+            ty,
+            value,
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+const WORKSPACE_FILE: &str = "workspace.json";
+
+/// Number of files remembered as recently used
+pub const RECENT_FILES_LIMIT: usize = 10;
+
+/// Directory holding state restored on the next launch
+pub fn state_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("slonik")
+}
+
+/// Editor buffers open when the workspace was saved
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Workspace {
+    /// Buffers in the order of their tabs
+    pub buffers: Vec<BufferState>,
+    /// Index of the current buffer
+    pub current: usize,
+    /// Most recently used first
+    pub recent_files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BufferState {
+    /// File the buffer was opened from or saved to
+    pub path: Option<PathBuf>,
+    /// Text not saved to `path`, `None` when the buffer matches its file
+    pub unsaved: Option<String>,
+    /// Character offset of the cursor
+    pub cursor: i32,
+}
+
+impl Workspace {
+    /// Workspace file in [`state_dir`]
+    pub fn default_path() -> PathBuf {
+        state_dir().join(WORKSPACE_FILE)
+    }
+
+    /// Loads the workspace from [`Workspace::default_path`]
+    pub fn load_default() -> Result<Self> {
+        Self::load(Self::default_path())
+    }
+
+    /// Loads the workspace from `path`. Missing file results in an empty workspace.
+    ///
+    /// A file holding no workspace is moved aside first, so that saving the workspace does not
+    /// overwrite it. The error tells where it went. A file which cannot be read at all is left
+    /// where it is.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+
+        let content = match fs::read(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(anyhow!(e).context(format!("Failed to read {}", path.display()))),
+        };
+
+        let err = match String::from_utf8(content) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(workspace) => return Ok(workspace),
+                Err(e) => anyhow!(e),
+            },
+            Err(e) => anyhow!(e),
+        };
+
+        let backup = backup_path(path);
+        let context = match fs::rename(path, &backup) {
+            Ok(()) => format!(
+                "Malformed workspace file {}, it was moved to {}",
+                path.display(),
+                backup.display()
+            ),
+            Err(e) => format!(
+                "Malformed workspace file {}, moving it aside failed: {}",
+                path.display(),
+                e
+            ),
+        };
+
+        Err(err.context(context))
+    }
+
+    pub fn save_default(&self) -> Result<()> {
+        self.save(Self::default_path())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // written next to the workspace and renamed over it, a crash never leaves it half written
+        let temp = path.with_file_name(format!("{}.tmp", file_name(path)));
+        fs::write(&temp, serde_json::to_string_pretty(self)?)
+            .and_then(|()| fs::rename(&temp, path))
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Moves `path` to the front of recent files
+    pub fn add_recent(&mut self, path: &Path) {
+        self.recent_files.retain(|recent| recent != path);
+        self.recent_files.insert(0, path.to_path_buf());
+        self.recent_files.truncate(RECENT_FILES_LIMIT);
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || WORKSPACE_FILE.into(),
+        |name| name.to_string_lossy().into(),
+    )
+}
+
+/// Where a workspace file which cannot be read is kept, earlier backups are not replaced
+fn backup_path(path: &Path) -> PathBuf {
+    path.with_file_name(format!(
+        "{}.{}.bak",
+        file_name(path),
+        Local::now().format("%Y%m%d%H%M%S")
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::TempDir;
+
+    #[test]
+    fn recent_files_most_recent_first() {
+        let mut workspace = Workspace::default();
+
+        for idx in 0..RECENT_FILES_LIMIT + 2 {
+            workspace.add_recent(Path::new(&format!("{}.sql", idx)));
+        }
+        workspace.add_recent(Path::new("5.sql"));
+
+        assert_eq!(workspace.recent_files.len(), RECENT_FILES_LIMIT);
+        assert_eq!(workspace.recent_files[0], Path::new("5.sql"));
+        assert_eq!(workspace.recent_files[1], Path::new("11.sql"));
+        assert_eq!(
+            workspace
+                .recent_files
+                .iter()
+                .filter(|path| *path == Path::new("5.sql"))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn missing_fields_default() {
+        let workspace: Workspace =
+            serde_json::from_str(r#"{ "buffers": [{ "unsaved": "select 1" }] }"#).unwrap();
+
+        assert_eq!(
+            workspace.buffers,
+            vec![BufferState {
+                path: None,
+                unsaved: Some("select 1".into()),
+                cursor: 0,
+            }]
+        );
+        assert_eq!(workspace.current, 0);
+        assert!(workspace.recent_files.is_empty());
+    }
+
+    #[test]
+    fn saved_workspace_loads() {
+        let dir = TempDir::new("save");
+        let path = dir.join(WORKSPACE_FILE);
+        let mut workspace = Workspace::default();
+        workspace.add_recent(Path::new("a.sql"));
+
+        workspace.save(&path).unwrap();
+        workspace.save(&path).unwrap();
+
+        assert_eq!(Workspace::load(&path).unwrap(), workspace);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn malformed_workspace_moved_aside() {
+        let dir = TempDir::new("malformed");
+        let path = dir.join(WORKSPACE_FILE);
+        fs::write(&path, "{ broken").unwrap();
+
+        let err = Workspace::load(&path).unwrap_err();
+        assert!(err.to_string().contains("was moved to"));
+        assert!(!path.exists());
+
+        let backups = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(backups, ["{ broken"]);
+    }
+
+    #[test]
+    fn unreadable_workspace_left_in_place() {
+        let dir = TempDir::new("unreadable");
+        let path = dir.join(WORKSPACE_FILE);
+        fs::create_dir(&path).unwrap();
+
+        let err = Workspace::load(&path).unwrap_err();
+        assert!(err.to_string().starts_with("Failed to read"));
+        assert!(path.is_dir());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        fs::remove_dir(&path).unwrap();
+        fs::write(&path, b"\xff\xfe").unwrap();
+        assert!(Workspace::load(&path).is_err());
+        assert!(!path.exists());
+    }
+}